@@ -0,0 +1,570 @@
+//! Exports recorded sessions as PCAP file, see [rust_ftp_honeypot::honeypot::recorder].
+//!
+//! The captured bytes of the control and data connections are written as synthetic TCP flows
+//! between the attacker and the honeypot, with the time they were captured. Each connection is
+//! opened with a handshake before its first bytes and closed with a FIN exchange when it ended, so
+//! Wireshark and Zeek can reassemble the streams.
+//!
+//! # Usage
+//! ```text
+//! pcap (--session <id> | --attacker <id>) [--output <file>]
+//! ```
+//! - `--session`  - Exports a single session.
+//! - `--attacker` - Exports all sessions of the attacker.
+//! - `--output`   - The file to write, defaults to `session-<id>.pcap` or `attacker-<id>.pcap`.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+
+use rust_ftp_honeypot::database::connection;
+use rust_ftp_honeypot::database::models::captures::{self, Sender};
+use rust_ftp_honeypot::database::service::DatabaseImplementation;
+use rust_ftp_honeypot::database::service_trait::DatabaseTrait;
+
+/// The usage of the binary, printed on invalid arguments.
+const USAGE: &str = "Usage: pcap (--session <id> | --attacker <id>) [--output <file>]";
+
+/// The link type of the packets, Ethernet.
+const LINKTYPE_ETHERNET: u32 = 1;
+/// The maximum payload of a single TCP segment.
+const MSS: usize = 1460;
+/// The MAC address of the client in the Ethernet frames.
+const CLIENT_MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 0x01];
+/// The MAC address of the honeypot in the Ethernet frames.
+const SERVER_MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 0x02];
+/// The initial sequence numbers of the client and the honeypot.
+const CLIENT_ISN: u32 = 1_000;
+const SERVER_ISN: u32 = 2_000_000;
+
+const FIN: u8 = 0x01;
+const SYN: u8 = 0x02;
+const PSH: u8 = 0x08;
+const ACK: u8 = 0x10;
+
+/// The sessions to export, parsed from the arguments.
+#[derive(Debug, PartialEq)]
+enum Selection {
+    Session(i64),
+    Attacker(i64),
+}
+
+/// The state of a synthetic TCP flow, the next sequence number of each side.
+struct Flow {
+    client: SocketAddr,
+    server: SocketAddr,
+    client_seq: u32,
+    server_seq: u32,
+    timestamp: i64,
+}
+
+/// Writes captures as TCP flows into a PCAP file.
+struct PcapWriter {
+    out: Vec<u8>,
+    flows: HashMap<(i64, SocketAddr, SocketAddr), Flow>,
+    /// The time of the last packet in microseconds, packets are never written out of order.
+    timestamp: i64,
+}
+
+/// Connects to the database of the application and exports the sessions given as argument.
+#[tokio::main]
+async fn main() {
+    let (selection, output) = match parse_options(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let db = match connection::set_up_db("").await {
+        Ok(db) => DatabaseImplementation { db },
+        Err(e) => {
+            eprintln!("Could not connect to the database: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let sessions = match selection {
+        Selection::Session(id) => db.get_session_by_id(id).await.into_iter().collect(),
+        Selection::Attacker(id) => db.get_sessions_by_attacker_id(id).await,
+    };
+    if sessions.is_empty() {
+        eprintln!("There are no sessions to export!");
+        std::process::exit(1);
+    }
+
+    let mut captures = vec![];
+    for session in &sessions {
+        captures.extend(db.get_captures_by_session_id(session.id).await);
+    }
+    captures.sort_by_key(|capture| (capture.created, capture.session_id, capture.sequence));
+
+    let mut writer = PcapWriter::new();
+    for capture in &captures {
+        if let Err(e) = writer.add(capture) {
+            eprintln!("Skipping capture {}: {}", capture.id, e);
+        }
+    }
+
+    match std::fs::write(&output, writer.finish()) {
+        Ok(_) => println!(
+            "Exported {} captures of {} sessions to {}",
+            captures.len(),
+            sessions.len(),
+            output
+        ),
+        Err(e) => {
+            eprintln!("Could not write {}: {}", output, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Parses the [Selection] and the output file from the arguments, without the name of the binary.
+///
+/// * `args` - The arguments.
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<(Selection, String), String> {
+    let mut selection = None;
+    let mut output = None;
+
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value of {}!", arg))?;
+
+        match arg.as_str() {
+            "--session" | "--attacker" if selection.is_some() => {
+                return Err("Only one of --session and --attacker can be given!".to_string())
+            }
+            "--session" => {
+                selection = Some(Selection::Session(
+                    value.parse().map_err(|_| "Invalid session id!")?,
+                ))
+            }
+            "--attacker" => {
+                selection = Some(Selection::Attacker(
+                    value.parse().map_err(|_| "Invalid attacker id!")?,
+                ))
+            }
+            "--output" => output = Some(value),
+            _ => return Err(format!("Unexpected argument {}!", arg)),
+        }
+    }
+
+    let selection = selection.ok_or("Missing --session or --attacker!")?;
+    let output = output.unwrap_or_else(|| match selection {
+        Selection::Session(id) => format!("session-{}.pcap", id),
+        Selection::Attacker(id) => format!("attacker-{}.pcap", id),
+    });
+
+    Ok((selection, output))
+}
+
+impl PcapWriter {
+    /// Creates a new [PcapWriter] and writes the global header of the file.
+    fn new() -> Self {
+        let mut out = vec![];
+        out.extend_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+        out.extend_from_slice(&2u16.to_le_bytes());
+        out.extend_from_slice(&4u16.to_le_bytes());
+        out.extend_from_slice(&0i32.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&65535u32.to_le_bytes());
+        out.extend_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+
+        PcapWriter {
+            out,
+            flows: HashMap::new(),
+            timestamp: 0,
+        }
+    }
+
+    /// Writes the capture as packets of its flow.
+    ///
+    /// The flow is opened with a handshake first, if it isn't open yet. Payloads are split into
+    /// segments of at most [MSS] bytes, a capture without payload closes the flow.
+    ///
+    /// * `capture` - The capture to write.
+    fn add(&mut self, capture: &captures::Model) -> Result<(), String> {
+        let (client, server) = endpoints(&capture.client_address, &capture.server_address)?;
+        let from_client = match Sender::from_str(&capture.sender) {
+            Ok(Sender::Client) => true,
+            Ok(Sender::Server) => false,
+            Err(_) => return Err(format!("Invalid sender {}", capture.sender)),
+        };
+
+        self.timestamp = capture.created.timestamp_micros().max(self.timestamp + 1);
+        let key = (capture.session_id, client, server);
+
+        if !self.flows.contains_key(&key) {
+            let mut flow = Flow {
+                client,
+                server,
+                client_seq: CLIENT_ISN,
+                server_seq: SERVER_ISN,
+                timestamp: self.timestamp,
+            };
+            self.write_segment(&mut flow, true, SYN, &[]);
+            self.write_segment(&mut flow, false, SYN | ACK, &[]);
+            self.write_segment(&mut flow, true, ACK, &[]);
+            self.flows.insert(key, flow);
+        }
+
+        let mut flow = self.flows.remove(&key).unwrap();
+        flow.timestamp = self.timestamp;
+
+        if capture.payload.is_empty() {
+            self.close(&mut flow, from_client);
+            return Ok(());
+        }
+
+        for segment in capture.payload.chunks(MSS) {
+            self.write_segment(&mut flow, from_client, PSH | ACK, segment);
+        }
+        self.flows.insert(key, flow);
+
+        Ok(())
+    }
+
+    /// Closes the flows which are still open and returns the PCAP file.
+    fn finish(mut self) -> Vec<u8> {
+        let mut flows: Vec<Flow> = self.flows.drain().map(|(_, flow)| flow).collect();
+        flows.sort_by_key(|flow| flow.timestamp);
+
+        for mut flow in flows {
+            self.close(&mut flow, false);
+        }
+
+        self.out
+    }
+
+    /// Writes the FIN exchange of the flow.
+    ///
+    /// * `flow`        - The [Flow] to close.
+    /// * `from_client` - Whether the client closes the flow.
+    fn close(&mut self, flow: &mut Flow, from_client: bool) {
+        self.write_segment(flow, from_client, FIN | ACK, &[]);
+        self.write_segment(flow, !from_client, FIN | ACK, &[]);
+        self.write_segment(flow, from_client, ACK, &[]);
+    }
+
+    /// Writes a TCP segment of the flow as a packet and advances the sequence number of its
+    /// sender.
+    ///
+    /// * `flow`        - The [Flow] of the segment.
+    /// * `from_client` - Whether the client sends the segment.
+    /// * `flags`       - The TCP flags.
+    /// * `payload`     - The payload of the segment.
+    fn write_segment(&mut self, flow: &mut Flow, from_client: bool, flags: u8, payload: &[u8]) {
+        let (src, dst, seq, ack) = if from_client {
+            (flow.client, flow.server, flow.client_seq, flow.server_seq)
+        } else {
+            (flow.server, flow.client, flow.server_seq, flow.client_seq)
+        };
+        let ack = if flags & ACK != 0 { ack } else { 0 };
+
+        let frame = ethernet_frame(
+            from_client,
+            src,
+            dst,
+            &tcp_segment(src, dst, seq, ack, flags, payload),
+        );
+        self.write_packet(flow.timestamp, &frame);
+
+        let length = payload.len() as u32 + u32::from(flags & (SYN | FIN) != 0);
+        if from_client {
+            flow.client_seq = flow.client_seq.wrapping_add(length);
+        } else {
+            flow.server_seq = flow.server_seq.wrapping_add(length);
+        }
+    }
+
+    /// Writes a packet record.
+    ///
+    /// * `timestamp` - The time of the packet in microseconds.
+    /// * `frame`     - The Ethernet frame.
+    fn write_packet(&mut self, timestamp: i64, frame: &[u8]) {
+        self.out
+            .extend_from_slice(&(timestamp.div_euclid(1_000_000) as u32).to_le_bytes());
+        self.out
+            .extend_from_slice(&(timestamp.rem_euclid(1_000_000) as u32).to_le_bytes());
+        self.out
+            .extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        self.out
+            .extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        self.out.extend_from_slice(frame);
+    }
+}
+
+/// Parses the client and server address of a capture.
+///
+/// IPv4-mapped IPv6 addresses are converted to IPv4. If only one of the addresses is IPv4 after
+/// that, it is mapped to IPv6 again, so both ends of the flow use the same IP version.
+///
+/// * `client` - The address of the client.
+/// * `server` - The address of the honeypot.
+fn endpoints(client: &str, server: &str) -> Result<(SocketAddr, SocketAddr), String> {
+    let parse = |address: &str| {
+        address
+            .parse::<SocketAddr>()
+            .map(|addr| SocketAddr::new(addr.ip().to_canonical(), addr.port()))
+            .map_err(|_| format!("Invalid address {}", address))
+    };
+    let (mut client, mut server) = (parse(client)?, parse(server)?);
+
+    if client.is_ipv4() != server.is_ipv4() {
+        for addr in [&mut client, &mut server] {
+            if let IpAddr::V4(ip) = addr.ip() {
+                addr.set_ip(IpAddr::V6(ip.to_ipv6_mapped()));
+            }
+        }
+    }
+
+    Ok((client, server))
+}
+
+/// Builds a TCP segment with IP header.
+///
+/// * `src`     - The address of the sender.
+/// * `dst`     - The address of the receiver.
+/// * `seq`     - The sequence number.
+/// * `ack`     - The acknowledgment number.
+/// * `flags`   - The TCP flags.
+/// * `payload` - The payload.
+fn tcp_segment(
+    src: SocketAddr,
+    dst: SocketAddr,
+    seq: u32,
+    ack: u32,
+    flags: u8,
+    payload: &[u8],
+) -> Vec<u8> {
+    let mut tcp = vec![];
+    tcp.extend_from_slice(&src.port().to_be_bytes());
+    tcp.extend_from_slice(&dst.port().to_be_bytes());
+    tcp.extend_from_slice(&seq.to_be_bytes());
+    tcp.extend_from_slice(&ack.to_be_bytes());
+    tcp.push(5 << 4);
+    tcp.push(flags);
+    tcp.extend_from_slice(&65535u16.to_be_bytes());
+    tcp.extend_from_slice(&[0, 0, 0, 0]);
+    tcp.extend_from_slice(payload);
+
+    let mut pseudo_header = vec![];
+    let mut ip = vec![];
+    match (src.ip(), dst.ip()) {
+        (IpAddr::V4(src_ip), IpAddr::V4(dst_ip)) => {
+            pseudo_header.extend_from_slice(&src_ip.octets());
+            pseudo_header.extend_from_slice(&dst_ip.octets());
+            pseudo_header.extend_from_slice(&[0, 6]);
+            pseudo_header.extend_from_slice(&(tcp.len() as u16).to_be_bytes());
+
+            ip.push(0x45);
+            ip.push(0);
+            ip.extend_from_slice(&((20 + tcp.len()) as u16).to_be_bytes());
+            ip.extend_from_slice(&[0, 0, 0x40, 0, 64, 6, 0, 0]);
+            ip.extend_from_slice(&src_ip.octets());
+            ip.extend_from_slice(&dst_ip.octets());
+            let checksum = checksum(&ip);
+            ip[10..12].copy_from_slice(&checksum.to_be_bytes());
+        }
+        (src_ip, dst_ip) => {
+            let octets = |ip: IpAddr| match ip {
+                IpAddr::V4(ip) => ip.to_ipv6_mapped().octets(),
+                IpAddr::V6(ip) => ip.octets(),
+            };
+            pseudo_header.extend_from_slice(&octets(src_ip));
+            pseudo_header.extend_from_slice(&octets(dst_ip));
+            pseudo_header.extend_from_slice(&(tcp.len() as u32).to_be_bytes());
+            pseudo_header.extend_from_slice(&[0, 0, 0, 6]);
+
+            ip.extend_from_slice(&[0x60, 0, 0, 0]);
+            ip.extend_from_slice(&(tcp.len() as u16).to_be_bytes());
+            ip.extend_from_slice(&[6, 64]);
+            ip.extend_from_slice(&octets(src_ip));
+            ip.extend_from_slice(&octets(dst_ip));
+        }
+    }
+
+    pseudo_header.extend_from_slice(&tcp);
+    let checksum = checksum(&pseudo_header);
+    tcp[16..18].copy_from_slice(&checksum.to_be_bytes());
+
+    ip.extend_from_slice(&tcp);
+    ip
+}
+
+/// Wraps an IP packet into an Ethernet frame.
+///
+/// * `from_client` - Whether the client sends the packet.
+/// * `src`         - The address of the sender, which selects the EtherType.
+/// * `dst`         - The address of the receiver.
+/// * `packet`      - The IP packet.
+fn ethernet_frame(from_client: bool, src: SocketAddr, dst: SocketAddr, packet: &[u8]) -> Vec<u8> {
+    let (src_mac, dst_mac) = if from_client {
+        (CLIENT_MAC, SERVER_MAC)
+    } else {
+        (SERVER_MAC, CLIENT_MAC)
+    };
+    let ether_type: u16 = if src.is_ipv4() && dst.is_ipv4() {
+        0x0800
+    } else {
+        0x86dd
+    };
+
+    let mut frame = vec![];
+    frame.extend_from_slice(&dst_mac);
+    frame.extend_from_slice(&src_mac);
+    frame.extend_from_slice(&ether_type.to_be_bytes());
+    frame.extend_from_slice(packet);
+    frame
+}
+
+/// Returns the internet checksum of the data.
+///
+/// * `data` - The data to sum up.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| u32::from(u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])))
+        .sum();
+
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use chrono::{Local, TimeZone};
+
+    use rust_ftp_honeypot::database::models::captures;
+
+    use crate::{checksum, endpoints, parse_options, PcapWriter, Selection, ACK, FIN, PSH, SYN};
+
+    fn options(args: &[&str]) -> Result<(Selection, String), String> {
+        parse_options(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn capture(sequence: i32, sender: &str, payload: &[u8]) -> captures::Model {
+        captures::Model {
+            id: sequence as i64,
+            session_id: 1,
+            sequence,
+            channel: "control".to_string(),
+            sender: sender.to_string(),
+            client_address: "10.0.0.1:40000".to_string(),
+            server_address: "10.0.0.2:21".to_string(),
+            payload: payload.to_vec(),
+            created: Local.timestamp_opt(1_000, 0).unwrap(),
+        }
+    }
+
+    /// Returns the TCP flags, sequence number and payload of each IPv4 packet of the file.
+    fn segments(pcap: &[u8]) -> Vec<(u8, u32, Vec<u8>)> {
+        let mut segments = vec![];
+        let mut position = 24;
+
+        while position < pcap.len() {
+            let length = u32::from_le_bytes(pcap[position + 8..position + 12].try_into().unwrap());
+            let frame = &pcap[position + 16..position + 16 + length as usize];
+            let tcp = &frame[14 + 20..];
+
+            segments.push((
+                tcp[13],
+                u32::from_be_bytes(tcp[4..8].try_into().unwrap()),
+                tcp[20..].to_vec(),
+            ));
+            position += 16 + length as usize;
+        }
+
+        segments
+    }
+
+    #[test]
+    fn options_are_parsed() {
+        assert_eq!(
+            options(&["--session", "4"]),
+            Ok((Selection::Session(4), "session-4.pcap".to_string()))
+        );
+        assert_eq!(
+            options(&["--attacker", "7", "--output", "out.pcap"]),
+            Ok((Selection::Attacker(7), "out.pcap".to_string()))
+        );
+        assert!(options(&[]).is_err());
+        assert!(options(&["--session"]).is_err());
+        assert!(options(&["--session", "1", "--attacker", "2"]).is_err());
+    }
+
+    #[test]
+    fn checksum_is_calculated() {
+        let header = [
+            0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+        ];
+
+        assert_eq!(checksum(&header), 0xb861);
+    }
+
+    #[test]
+    fn endpoints_use_the_same_ip_version() {
+        assert_eq!(
+            endpoints("[::ffff:10.0.0.1]:1", "10.0.0.2:21"),
+            Ok((
+                "10.0.0.1:1".parse::<SocketAddr>().unwrap(),
+                "10.0.0.2:21".parse().unwrap()
+            ))
+        );
+        assert_eq!(
+            endpoints("10.0.0.1:1", "[::1]:21"),
+            Ok((
+                "[::ffff:10.0.0.1]:1".parse::<SocketAddr>().unwrap(),
+                "[::1]:21".parse().unwrap()
+            ))
+        );
+        assert!(endpoints("10.0.0.1", "[::1]:21").is_err());
+    }
+
+    #[test]
+    fn flows_are_opened_and_closed() {
+        let mut writer = PcapWriter::new();
+        writer.add(&capture(1, "server", b"220 Hi\r\n")).unwrap();
+        writer.add(&capture(2, "client", b"QUIT\r\n")).unwrap();
+        writer.add(&capture(3, "client", &[])).unwrap();
+        let pcap = writer.finish();
+
+        assert_eq!(&pcap[..4], &[0xd4, 0xc3, 0xb2, 0xa1]);
+        assert_eq!(
+            segments(&pcap),
+            vec![
+                (SYN, 1_000, vec![]),
+                (SYN | ACK, 2_000_000, vec![]),
+                (ACK, 1_001, vec![]),
+                (PSH | ACK, 2_000_001, b"220 Hi\r\n".to_vec()),
+                (PSH | ACK, 1_001, b"QUIT\r\n".to_vec()),
+                (FIN | ACK, 1_007, vec![]),
+                (FIN | ACK, 2_000_009, vec![]),
+                (ACK, 1_008, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn payloads_are_segmented_and_open_flows_closed() {
+        let mut writer = PcapWriter::new();
+        writer.add(&capture(1, "client", &[0; 2000])).unwrap();
+        let pcap = writer.finish();
+
+        let segments = segments(&pcap);
+        assert_eq!(segments.len(), 8);
+        assert_eq!(segments[3].2.len(), 1460);
+        assert_eq!(segments[4].2.len(), 540);
+        assert_eq!(segments[5].0, FIN | ACK);
+        assert_eq!(segments[5].1, 2_000_001);
+    }
+}
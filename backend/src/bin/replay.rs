@@ -0,0 +1,460 @@
+//! Replays a recorded session in the terminal, see [rust_ftp_honeypot::honeypot::recorder].
+//!
+//! The commands of the attacker, the replies of the honeypot and the changes of the filesystem
+//! are shown side by side, with the original timing between them.
+//!
+//! # Usage
+//! ```text
+//! replay <session-id> [--speed <factor>] [--max-delay <seconds>] [--width <columns>]
+//! ```
+//! - `--speed`     - Plays the session faster by the factor, e.g. `4` or `0.5`. Defaults to `1`.
+//! - `--max-delay` - Caps the delay between two lines, so idle times are skipped.
+//! - `--width`     - The width of each column. Defaults to `40`.
+//!
+//! While the session is replayed, these controls are read from stdin, each confirmed with enter:
+//! - `p` pauses or resumes the replay.
+//! - `s` skips the current delay.
+//! - `+` and `-` double or halve the speed.
+//! - `q` quits the replay.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc::{self, Receiver};
+use tokio::time::sleep;
+
+use rust_ftp_honeypot::database::connection;
+use rust_ftp_honeypot::database::models::transcripts::{self, Direction};
+use rust_ftp_honeypot::database::service::DatabaseImplementation;
+use rust_ftp_honeypot::database::service_trait::DatabaseTrait;
+
+/// The usage of the binary, printed on invalid arguments.
+const USAGE: &str =
+    "Usage: replay <session-id> [--speed <factor>] [--max-delay <seconds>] [--width <columns>]";
+
+/// The options of a replay, parsed from the arguments.
+#[derive(Debug, PartialEq)]
+struct Options {
+    session_id: i64,
+    speed: f64,
+    max_delay: Option<Duration>,
+    width: usize,
+}
+
+/// A control of a running replay, read from stdin.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Control {
+    Pause,
+    Skip,
+    Faster,
+    Slower,
+    Quit,
+}
+
+impl FromStr for Control {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "p" => Ok(Control::Pause),
+            "s" | "" => Ok(Control::Skip),
+            "+" => Ok(Control::Faster),
+            "-" => Ok(Control::Slower),
+            "q" => Ok(Control::Quit),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Connects to the database of the application and replays the session given as argument.
+#[tokio::main]
+async fn main() {
+    let options = match parse_options(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let db = match connection::set_up_db("").await {
+        Ok(db) => DatabaseImplementation { db },
+        Err(e) => {
+            eprintln!("Could not connect to the database: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let session = match db.get_session_by_id(options.session_id).await {
+        Some(session) => session,
+        None => {
+            eprintln!("There is no session with id {}!", options.session_id);
+            std::process::exit(1);
+        }
+    };
+    let lines = db.get_transcript_by_session_id(session.id).await;
+
+    println!(
+        "Session {} of {}:{} on {}, started {}, ended {} ({})",
+        session.id,
+        session.ip,
+        session.port,
+        session.listener,
+        session.started.format("%Y-%m-%d %H:%M:%S"),
+        session
+            .ended
+            .map(|ended| ended.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| "-".to_string()),
+        session.outcome.as_deref().unwrap_or("unknown"),
+    );
+    println!("p: pause/resume, s: skip delay, +/-: speed, q: quit");
+    println!(
+        "{}",
+        format_row(["command", "reply", "filesystem"], "", options.width)
+    );
+
+    replay(&lines, session.started, options, read_controls()).await;
+}
+
+/// Parses the [Options] from the arguments, without the name of the binary.
+///
+/// * `args` - The arguments.
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut session_id = None;
+    let mut options = Options {
+        session_id: 0,
+        speed: 1.0,
+        max_delay: None,
+        width: 40,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("Missing value of {}!", name))
+        };
+
+        match arg.as_str() {
+            "--speed" => {
+                options.speed = value("--speed")?
+                    .parse()
+                    .ok()
+                    .filter(|speed: &f64| speed.is_finite() && *speed > 0.0)
+                    .ok_or("Invalid speed!")?
+            }
+            "--max-delay" => {
+                let seconds: f64 = value("--max-delay")?
+                    .parse()
+                    .ok()
+                    .filter(|seconds: &f64| seconds.is_finite() && *seconds >= 0.0)
+                    .ok_or("Invalid max delay!")?;
+                options.max_delay = Some(Duration::from_secs_f64(seconds));
+            }
+            "--width" => {
+                options.width = value("--width")?
+                    .parse()
+                    .ok()
+                    .filter(|width| *width > 0)
+                    .ok_or("Invalid width!")?
+            }
+            _ if session_id.is_none() => {
+                session_id = Some(arg.parse().map_err(|_| "Invalid session id!")?)
+            }
+            _ => return Err(format!("Unexpected argument {}!", arg)),
+        }
+    }
+
+    options.session_id = session_id.ok_or("Missing session id!")?;
+    Ok(options)
+}
+
+/// Reads the [Control]s of the replay from stdin in a new task.
+fn read_controls() -> Receiver<Control> {
+    let (sender, receiver) = mpsc::channel(8);
+
+    tokio::spawn(async move {
+        let mut stdin = BufReader::new(tokio::io::stdin()).lines();
+        while let Ok(Some(line)) = stdin.next_line().await {
+            if let Ok(control) = line.parse() {
+                if sender.send(control).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    receiver
+}
+
+/// Prints the transcript `lines` with the delays between them.
+///
+/// * `lines`    - The transcript lines, ordered by sequence.
+/// * `started`  - The start of the session.
+/// * `options`  - The [Options] of the replay.
+/// * `controls` - The [Control]s of the replay.
+async fn replay(
+    lines: &[transcripts::Model],
+    started: DateTime<Local>,
+    mut options: Options,
+    mut controls: Receiver<Control>,
+) {
+    let mut previous = started;
+    let mut paused = false;
+    let mut controls_open = true;
+
+    for line in lines {
+        let mut remaining = delay(previous, line.created, &options);
+        previous = line.created;
+
+        while paused || !remaining.is_zero() {
+            let waited = tokio::time::Instant::now();
+            let control = tokio::select! {
+                control = controls.recv(), if controls_open => control,
+                _ = sleep(remaining), if !paused => Some(Control::Skip),
+            };
+            if !paused {
+                remaining = remaining.saturating_sub(waited.elapsed());
+            }
+
+            match control {
+                Some(Control::Pause) => {
+                    paused = !paused;
+                    println!(
+                        "{}",
+                        if paused {
+                            "-- paused --"
+                        } else {
+                            "-- resumed --"
+                        }
+                    );
+                }
+                Some(Control::Skip) => {
+                    paused = false;
+                    remaining = Duration::ZERO;
+                }
+                Some(Control::Faster) => {
+                    options.speed *= 2.0;
+                    remaining /= 2;
+                    println!("-- speed {}x --", options.speed);
+                }
+                Some(Control::Slower) => {
+                    options.speed /= 2.0;
+                    remaining *= 2;
+                    println!("-- speed {}x --", options.speed);
+                }
+                Some(Control::Quit) => return,
+                None => {
+                    // Stdin is closed, a paused replay can't be resumed anymore.
+                    controls_open = false;
+                    paused = false;
+                }
+            }
+        }
+
+        let offset = line.created.signed_duration_since(started);
+        let offset = format!("+{:.3}s", offset.num_milliseconds().max(0) as f64 / 1000.0);
+        for row in format_line(line, &offset, options.width) {
+            println!("{}", row);
+        }
+    }
+
+    println!("-- end of session --");
+}
+
+/// Returns the delay between two transcript lines, scaled by the speed and capped at the maximum
+/// delay of the [Options].
+///
+/// * `previous` - The time of the previous line.
+/// * `current`  - The time of the current line.
+/// * `options`  - The [Options] of the replay.
+fn delay(previous: DateTime<Local>, current: DateTime<Local>, options: &Options) -> Duration {
+    let delay = current
+        .signed_duration_since(previous)
+        .to_std()
+        .unwrap_or_default()
+        .div_f64(options.speed);
+
+    match options.max_delay {
+        Some(max_delay) => delay.min(max_delay),
+        None => delay,
+    }
+}
+
+/// Formats a transcript line as rows of the command, reply and filesystem columns.
+///
+/// The line is put into the column of its [Direction] and wrapped at the `width`. The `offset` is
+/// shown in front of the first row.
+///
+/// * `line`   - The transcript line.
+/// * `offset` - The time of the line since the start of the session.
+/// * `width`  - The width of each column.
+fn format_line(line: &transcripts::Model, offset: &str, width: usize) -> Vec<String> {
+    let column = match Direction::from_str(&line.direction) {
+        Ok(Direction::Command) => 0,
+        Ok(Direction::Reply) => 1,
+        Ok(Direction::Filesystem) => 2,
+        Err(_) => 1,
+    };
+
+    let mut rows = vec![];
+    for part in line.line.split('\n') {
+        for chunk in wrap(&sanitize(part), width) {
+            let mut cells = ["", "", ""];
+            cells[column] = &chunk;
+            let offset = if rows.is_empty() { offset } else { "" };
+            rows.push(format_row(cells, offset, width));
+        }
+    }
+    rows
+}
+
+/// Formats a row of the three columns, padded to the `width`.
+///
+/// * `cells`  - The content of the command, reply and filesystem column.
+/// * `offset` - The time shown in front of the row.
+/// * `width`  - The width of each column.
+fn format_row(cells: [&str; 3], offset: &str, width: usize) -> String {
+    format!(
+        "{:>10} {:<width$} | {:<width$} | {}",
+        offset,
+        cells[0],
+        cells[1],
+        cells[2],
+        width = width
+    )
+    .trim_end()
+    .to_string()
+}
+
+/// Splits the text into chunks of at most `width` characters, returns one empty chunk for an
+/// empty text.
+///
+/// * `text`  - The text to split.
+/// * `width` - The maximum number of characters of a chunk.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.is_empty() {
+        return vec![String::new()];
+    }
+
+    chars
+        .chunks(width)
+        .map(|chunk| chunk.iter().collect())
+        .collect()
+}
+
+/// Escapes control characters, so recorded lines can't send escape sequences to the terminal.
+///
+/// * `text` - The recorded text.
+fn sanitize(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_control() {
+                c.escape_default().to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{Local, TimeZone};
+
+    use rust_ftp_honeypot::database::models::transcripts;
+
+    use crate::{delay, format_line, parse_options, sanitize, wrap, Control, Options};
+
+    fn options(args: &[&str]) -> Result<Options, String> {
+        parse_options(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn options_are_parsed() {
+        assert_eq!(
+            options(&["12", "--speed", "4", "--max-delay", "1.5", "--width", "20"]),
+            Ok(Options {
+                session_id: 12,
+                speed: 4.0,
+                max_delay: Some(Duration::from_millis(1500)),
+                width: 20,
+            })
+        );
+        assert_eq!(
+            options(&["3"]),
+            Ok(Options {
+                session_id: 3,
+                speed: 1.0,
+                max_delay: None,
+                width: 40,
+            })
+        );
+        assert!(options(&[]).is_err());
+        assert!(options(&["3", "4"]).is_err());
+        assert!(options(&["3", "--speed", "0"]).is_err());
+        assert!(options(&["3", "--width"]).is_err());
+    }
+
+    #[test]
+    fn controls_are_parsed() {
+        assert_eq!("p".parse(), Ok(Control::Pause));
+        assert_eq!("".parse(), Ok(Control::Skip));
+        assert_eq!("+\n".parse(), Ok(Control::Faster));
+        assert_eq!("q".parse(), Ok(Control::Quit));
+        assert!("x".parse::<Control>().is_err());
+    }
+
+    #[test]
+    fn delay_is_scaled_and_capped() {
+        let previous = Local.timestamp_opt(1_000, 0).unwrap();
+        let current = Local.timestamp_opt(1_010, 0).unwrap();
+        let mut options = options(&["1", "--speed", "2"]).unwrap();
+
+        assert_eq!(delay(previous, current, &options), Duration::from_secs(5));
+        assert_eq!(delay(current, previous, &options), Duration::ZERO);
+
+        options.max_delay = Some(Duration::from_secs(1));
+        assert_eq!(delay(previous, current, &options), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn lines_are_put_into_columns() {
+        let line = |direction: &str, line: &str| transcripts::Model {
+            id: 1,
+            session_id: 1,
+            sequence: 1,
+            direction: direction.to_string(),
+            line: line.to_string(),
+            created: Local::now(),
+        };
+
+        assert_eq!(
+            format_line(&line("command", "USER root"), "+0.000s", 10),
+            vec!["   +0.000s USER root  |            |"]
+        );
+        assert_eq!(
+            format_line(&line("reply", "211-Features:\n UTF8"), "+1.000s", 10),
+            vec![
+                "   +1.000s            | 211-Featur |",
+                "                      | es:        |",
+                "                      |  UTF8      |",
+            ]
+        );
+        assert_eq!(
+            format_line(&line("filesystem", "mkdir /a"), "+2.000s", 10),
+            vec!["   +2.000s            |            | mkdir /a"]
+        );
+    }
+
+    #[test]
+    fn text_is_wrapped_and_sanitized() {
+        assert_eq!(wrap("abcde", 2), vec!["ab", "cd", "e"]);
+        assert_eq!(wrap("", 2), vec![""]);
+        assert_eq!(sanitize("ls\u{1b}[2J\t"), "ls\\u{1b}[2J\\t");
+    }
+}
@@ -1,4 +1,5 @@
 //! Reads the configuration file and creates a global [Config] object. Manages default values and errors
+use std::collections::HashMap;
 use std::fs;
 use std::io::Error as IoError;
 
@@ -12,6 +13,129 @@ struct ConfigTomlFtp {
     welcome_message: Option<String>,
     help_message: Option<String>,
     ftp_port: Option<i32>,
+    bind_addresses: Option<Vec<String>>,
+    max_command_length: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Represents the passive mode settings
+struct ConfigTomlPassiveMode {
+    address: Option<String>,
+    min_port: Option<u16>,
+    max_port: Option<u16>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Represents the FTPS settings
+struct ConfigTomlTls {
+    enabled: Option<bool>,
+    certificate_path: Option<String>,
+    private_key_path: Option<String>,
+    common_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Represents the implicit FTPS listener settings
+struct ConfigTomlImplicitTls {
+    enabled: Option<bool>,
+    port: Option<u16>,
+    certificate_path: Option<String>,
+    private_key_path: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Represents the server personality settings
+struct ConfigTomlPersonality {
+    preset: Option<String>,
+    syst: Option<String>,
+    version: Option<String>,
+    listing: Option<String>,
+    replies: Option<HashMap<String, String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Represents the session timeout settings
+struct ConfigTomlTimeouts {
+    idle: Option<u64>,
+    command: Option<u64>,
+    data_connect: Option<u64>,
+    data_transfer: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Represents the tarpit settings
+struct ConfigTomlTarpit {
+    enabled: Option<bool>,
+    threshold: Option<i32>,
+    delay: Option<u64>,
+    max_delay: Option<u64>,
+    banner_byte_delay: Option<u64>,
+    max_sessions: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Represents the FTP bounce settings
+struct ConfigTomlBounce {
+    sink: Option<bool>,
+    sink_address: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Represents the vsftpd 2.3.4 backdoor settings
+struct ConfigTomlBackdoor {
+    enabled: Option<bool>,
+    port: Option<u16>,
+    timeout: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Represents the anonymous login settings
+struct ConfigTomlAnonymous {
+    enabled: Option<bool>,
+    writable: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Represents the session recorder settings
+struct ConfigTomlRecorder {
+    enabled: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Represents the traffic capture settings
+struct ConfigTomlCapture {
+    enabled: Option<bool>,
+    max_payload: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Represents the protocol sniffer settings
+struct ConfigTomlSniffer {
+    enabled: Option<bool>,
+    wait: Option<u64>,
+    max_bytes: Option<usize>,
+    http_response: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Represents the PROXY protocol settings
+struct ConfigTomlProxy {
+    enabled: Option<bool>,
+    trusted: Option<Vec<String>>,
+    data_connections: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Represents the settings of a single listener
+struct ConfigTomlListener {
+    name: Option<String>,
+    bind_addresses: Option<Vec<String>>,
+    port: Option<u16>,
+    implicit_tls: Option<bool>,
+    welcome_message: Option<String>,
+    personality: Option<String>,
+    max_concurrent_users: Option<i32>,
+    filesystem: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -62,6 +186,20 @@ struct ConfigToml {
     login: Option<ConfigTomlLogin>,
     file_management: Option<ConfigTomlFileManagement>,
     ftp: Option<ConfigTomlFtp>,
+    passive_mode: Option<ConfigTomlPassiveMode>,
+    tls: Option<ConfigTomlTls>,
+    implicit_tls: Option<ConfigTomlImplicitTls>,
+    personality: Option<ConfigTomlPersonality>,
+    timeouts: Option<ConfigTomlTimeouts>,
+    tarpit: Option<ConfigTomlTarpit>,
+    bounce: Option<ConfigTomlBounce>,
+    backdoor: Option<ConfigTomlBackdoor>,
+    anonymous: Option<ConfigTomlAnonymous>,
+    recorder: Option<ConfigTomlRecorder>,
+    capture: Option<ConfigTomlCapture>,
+    sniffer: Option<ConfigTomlSniffer>,
+    proxy: Option<ConfigTomlProxy>,
+    listener: Option<Vec<ConfigTomlListener>>,
     honeynet: Option<ConfigTomlHoneynet>,
 }
 
@@ -82,6 +220,23 @@ struct ConfigTomlDatabase {
     database_name: Option<String>,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+/// Represents the settings of a listener, each listener accepts connections on its own port
+pub struct ListenerConfig {
+    /// The name of the listener, stored with the sessions and attackers that came in on it.
+    pub name: String,
+    pub bind_addresses: Vec<String>,
+    pub port: u16,
+    /// Whether each connection is wrapped in TLS right away.
+    pub implicit_tls: bool,
+    pub welcome_message: String,
+    /// The personality preset of the listener.
+    pub personality: String,
+    pub max_concurrent_users: i32,
+    /// The template of the filesystems of new attackers.
+    pub filesystem: String,
+}
+
 #[derive(Debug)]
 /// Represents the full configuration
 pub struct Config {
@@ -104,7 +259,50 @@ pub struct Config {
     pub base_save_path: String,
     pub ftp_welcome_message: String,
     pub ftp_help_message: String,
-    pub ftp_port: i32,
+    pub ftp_max_command_length: usize,
+    pub passive_address: String,
+    pub passive_min_port: u16,
+    pub passive_max_port: u16,
+    pub tls_enabled: bool,
+    pub tls_certificate_path: String,
+    pub tls_private_key_path: String,
+    pub tls_common_name: String,
+    pub implicit_tls_certificate_path: String,
+    pub implicit_tls_private_key_path: String,
+    pub personality_preset: String,
+    pub personality_syst: String,
+    pub personality_version: String,
+    pub personality_listing: String,
+    pub personality_replies: HashMap<String, String>,
+    pub timeout_idle: u64,
+    pub timeout_command: u64,
+    pub timeout_data_connect: u64,
+    pub timeout_data_transfer: u64,
+    pub tarpit_enabled: bool,
+    pub tarpit_threshold: i32,
+    pub tarpit_delay: u64,
+    pub tarpit_max_delay: u64,
+    pub tarpit_banner_byte_delay: u64,
+    pub tarpit_max_sessions: usize,
+    pub bounce_sink: bool,
+    pub bounce_sink_address: String,
+    pub backdoor_enabled: bool,
+    pub backdoor_port: u16,
+    pub backdoor_timeout: u64,
+    pub anonymous_enabled: bool,
+    pub anonymous_writable: bool,
+    pub recorder_enabled: bool,
+    pub capture_enabled: bool,
+    pub capture_max_payload: usize,
+    pub sniffer_enabled: bool,
+    pub sniffer_wait: u64,
+    pub sniffer_max_bytes: usize,
+    pub sniffer_http_response: bool,
+    pub proxy_enabled: bool,
+    pub proxy_trusted: Vec<String>,
+    pub proxy_data_connections: bool,
+    /// The configured listeners, or the ones of the ftp and implicit_tls settings if none are.
+    pub listeners: Vec<ListenerConfig>,
     pub honeynet_id: i32,
     pub honeynet_token: String,
     pub honeynet_url: String,
@@ -130,8 +328,8 @@ impl Config {
         for filepath in config_filepaths {
             let result: Result<String, IoError> = fs::read_to_string(filepath);
 
-            if let Ok(..) = result {
-                content = result.unwrap();
+            if let Ok(file_content) = result {
+                content = file_content;
                 break;
             }
         }
@@ -149,6 +347,20 @@ impl Config {
                 login: None,
                 file_management: None,
                 ftp: None,
+                passive_mode: None,
+                tls: None,
+                implicit_tls: None,
+                personality: None,
+                timeouts: None,
+                tarpit: None,
+                bounce: None,
+                backdoor: None,
+                anonymous: None,
+                recorder: None,
+                capture: None,
+                sniffer: None,
+                proxy: None,
+                listener: None,
                 honeynet: None,
             }
         });
@@ -253,32 +465,473 @@ impl Config {
                 }
             };
 
-        let (ftp_welcome_message, ftp_help_message, ftp_port): (String, String, i32) =
-            match config_toml.ftp {
-                Some(ftp) => {
-                    let ftp_welcome_message = ftp.welcome_message.unwrap_or_else(|| {
-                        warn!("Configuration setup: Missing field welcome_message in ftp data.");
-                        "invalid_message".to_owned()
+        let (
+            ftp_welcome_message,
+            ftp_help_message,
+            ftp_port,
+            ftp_bind_addresses,
+            ftp_max_command_length,
+        ): (String, String, i32, Vec<String>, usize) = match config_toml.ftp {
+            Some(ftp) => {
+                // Without a welcome or help message the ones of the personality are used.
+                let ftp_welcome_message = ftp.welcome_message.unwrap_or_default();
+                let ftp_help_message = ftp.help_message.unwrap_or_default();
+
+                let ftp_port = ftp.ftp_port.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field port in ftp data.");
+                    8080.to_owned()
+                });
+
+                let ftp_bind_addresses = ftp.bind_addresses.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field bind_addresses in ftp data.");
+                    vec!["127.0.0.1".to_owned()]
+                });
+
+                let ftp_max_command_length = ftp.max_command_length.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field max_command_length in ftp data.");
+                    4096.to_owned()
+                });
+                (
+                    ftp_welcome_message,
+                    ftp_help_message,
+                    ftp_port,
+                    ftp_bind_addresses,
+                    ftp_max_command_length,
+                )
+            }
+            None => {
+                warn!("Configuration setup: Missing ftp data.");
+                (
+                    "".to_owned(),
+                    "".to_owned(),
+                    8080.to_owned(),
+                    vec!["127.0.0.1".to_owned()],
+                    4096.to_owned(),
+                )
+            }
+        };
+
+        let (passive_address, passive_min_port, passive_max_port): (String, u16, u16) =
+            match config_toml.passive_mode {
+                Some(passive_mode) => {
+                    let passive_address = passive_mode.address.unwrap_or_else(|| {
+                        warn!("Configuration setup: Missing field address in passive_mode data.");
+                        "".to_owned()
                     });
 
-                    let ftp_help_message = ftp.help_message.unwrap_or_else(|| {
-                        warn!("Configuration setup: Missing field help_message in ftp data.");
-                        "invalid_message".to_owned()
+                    let passive_min_port = passive_mode.min_port.unwrap_or_else(|| {
+                        warn!("Configuration setup: Missing field min_port in passive_mode data.");
+                        50000.to_owned()
                     });
 
-                    let ftp_port = ftp.ftp_port.unwrap_or_else(|| {
-                        warn!("Configuration setup: Missing field port in ftp data.");
-                        8080.to_owned()
+                    let passive_max_port = passive_mode.max_port.unwrap_or_else(|| {
+                        warn!("Configuration setup: Missing field max_port in passive_mode data.");
+                        50100.to_owned()
                     });
-                    (ftp_welcome_message, ftp_help_message, ftp_port)
+                    (passive_address, passive_min_port, passive_max_port)
                 }
                 None => {
-                    warn!("Configuration setup: Missing ftp data.");
-                    (
-                        "invalid_message".to_owned(),
-                        "invalid_message".to_owned(),
-                        8080.to_owned(),
-                    )
+                    warn!("Configuration setup: Missing passive_mode data.");
+                    ("".to_owned(), 50000.to_owned(), 50100.to_owned())
+                }
+            };
+
+        let (tls_enabled, tls_certificate_path, tls_private_key_path, tls_common_name): (
+            bool,
+            String,
+            String,
+            String,
+        ) = match config_toml.tls {
+            Some(tls) => {
+                let tls_enabled = tls.enabled.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field enabled in tls data.");
+                    false.to_owned()
+                });
+
+                let tls_certificate_path = tls.certificate_path.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field certificate_path in tls data.");
+                    "".to_owned()
+                });
+
+                let tls_private_key_path = tls.private_key_path.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field private_key_path in tls data.");
+                    "".to_owned()
+                });
+
+                let tls_common_name = tls.common_name.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field common_name in tls data.");
+                    "localhost".to_owned()
+                });
+                (
+                    tls_enabled,
+                    tls_certificate_path,
+                    tls_private_key_path,
+                    tls_common_name,
+                )
+            }
+            None => {
+                warn!("Configuration setup: Missing tls data.");
+                (
+                    false.to_owned(),
+                    "".to_owned(),
+                    "".to_owned(),
+                    "localhost".to_owned(),
+                )
+            }
+        };
+
+        let (
+            implicit_tls_enabled,
+            implicit_tls_port,
+            implicit_tls_certificate_path,
+            implicit_tls_private_key_path,
+        ): (bool, u16, String, String) = match config_toml.implicit_tls {
+            Some(implicit_tls) => {
+                let implicit_tls_enabled = implicit_tls.enabled.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field enabled in implicit_tls data.");
+                    false.to_owned()
+                });
+
+                let implicit_tls_port = implicit_tls.port.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field port in implicit_tls data.");
+                    990.to_owned()
+                });
+
+                let implicit_tls_certificate_path =
+                    implicit_tls.certificate_path.unwrap_or_else(|| {
+                        warn!("Configuration setup: Missing field certificate_path in implicit_tls data.");
+                        "".to_owned()
+                    });
+
+                let implicit_tls_private_key_path =
+                    implicit_tls.private_key_path.unwrap_or_else(|| {
+                        warn!("Configuration setup: Missing field private_key_path in implicit_tls data.");
+                        "".to_owned()
+                    });
+                (
+                    implicit_tls_enabled,
+                    implicit_tls_port,
+                    implicit_tls_certificate_path,
+                    implicit_tls_private_key_path,
+                )
+            }
+            None => {
+                warn!("Configuration setup: Missing implicit_tls data.");
+                (
+                    false.to_owned(),
+                    990.to_owned(),
+                    "".to_owned(),
+                    "".to_owned(),
+                )
+            }
+        };
+
+        let (
+            personality_preset,
+            personality_syst,
+            personality_version,
+            personality_listing,
+            personality_replies,
+        ): (String, String, String, String, HashMap<String, String>) = match config_toml.personality
+        {
+            Some(personality) => {
+                let personality_preset = personality.preset.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field preset in personality data.");
+                    "vsftpd".to_owned()
+                });
+
+                // The following fields override the preset, empty values keep it.
+                let personality_syst = personality.syst.unwrap_or_default();
+                let personality_version = personality.version.unwrap_or_default();
+                let personality_listing = personality.listing.unwrap_or_default();
+                let personality_replies = personality.replies.unwrap_or_default();
+
+                (
+                    personality_preset,
+                    personality_syst,
+                    personality_version,
+                    personality_listing,
+                    personality_replies,
+                )
+            }
+            None => {
+                warn!("Configuration setup: Missing personality data.");
+                (
+                    "vsftpd".to_owned(),
+                    "".to_owned(),
+                    "".to_owned(),
+                    "".to_owned(),
+                    HashMap::new(),
+                )
+            }
+        };
+
+        let (timeout_idle, timeout_command, timeout_data_connect, timeout_data_transfer): (
+            u64,
+            u64,
+            u64,
+            u64,
+        ) = match config_toml.timeouts {
+            Some(timeouts) => {
+                let timeout_idle = timeouts.idle.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field idle in timeouts data.");
+                    300.to_owned()
+                });
+
+                let timeout_command = timeouts.command.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field command in timeouts data.");
+                    60.to_owned()
+                });
+
+                let timeout_data_connect = timeouts.data_connect.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field data_connect in timeouts data.");
+                    30.to_owned()
+                });
+
+                let timeout_data_transfer = timeouts.data_transfer.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field data_transfer in timeouts data.");
+                    300.to_owned()
+                });
+
+                (
+                    timeout_idle,
+                    timeout_command,
+                    timeout_data_connect,
+                    timeout_data_transfer,
+                )
+            }
+            None => {
+                warn!("Configuration setup: Missing timeouts data.");
+                (300.to_owned(), 60.to_owned(), 30.to_owned(), 300.to_owned())
+            }
+        };
+
+        let (
+            tarpit_enabled,
+            tarpit_threshold,
+            tarpit_delay,
+            tarpit_max_delay,
+            tarpit_banner_byte_delay,
+            tarpit_max_sessions,
+        ): (bool, i32, u64, u64, u64, usize) = match config_toml.tarpit {
+            Some(tarpit) => {
+                let tarpit_enabled = tarpit.enabled.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field enabled in tarpit data.");
+                    false.to_owned()
+                });
+
+                let tarpit_threshold = tarpit.threshold.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field threshold in tarpit data.");
+                    20.to_owned()
+                });
+
+                let tarpit_delay = tarpit.delay.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field delay in tarpit data.");
+                    1000.to_owned()
+                });
+
+                let tarpit_max_delay = tarpit.max_delay.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field max_delay in tarpit data.");
+                    30000.to_owned()
+                });
+
+                let tarpit_banner_byte_delay = tarpit.banner_byte_delay.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field banner_byte_delay in tarpit data.");
+                    250.to_owned()
+                });
+
+                let tarpit_max_sessions = tarpit.max_sessions.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field max_sessions in tarpit data.");
+                    25.to_owned()
+                });
+
+                (
+                    tarpit_enabled,
+                    tarpit_threshold,
+                    tarpit_delay,
+                    tarpit_max_delay,
+                    tarpit_banner_byte_delay,
+                    tarpit_max_sessions,
+                )
+            }
+            None => {
+                warn!("Configuration setup: Missing tarpit data.");
+                (
+                    false.to_owned(),
+                    20.to_owned(),
+                    1000.to_owned(),
+                    30000.to_owned(),
+                    250.to_owned(),
+                    25.to_owned(),
+                )
+            }
+        };
+
+        let (bounce_sink, bounce_sink_address): (bool, String) = match config_toml.bounce {
+            Some(bounce) => {
+                let bounce_sink = bounce.sink.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field sink in bounce data.");
+                    false.to_owned()
+                });
+
+                let bounce_sink_address = bounce.sink_address.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field sink_address in bounce data.");
+                    "127.0.0.1:9".to_owned()
+                });
+
+                (bounce_sink, bounce_sink_address)
+            }
+            None => {
+                warn!("Configuration setup: Missing bounce data.");
+                (false.to_owned(), "127.0.0.1:9".to_owned())
+            }
+        };
+
+        let (backdoor_enabled, backdoor_port, backdoor_timeout): (bool, u16, u64) =
+            match config_toml.backdoor {
+                Some(backdoor) => {
+                    let backdoor_enabled = backdoor.enabled.unwrap_or_else(|| {
+                        warn!("Configuration setup: Missing field enabled in backdoor data.");
+                        false.to_owned()
+                    });
+
+                    let backdoor_port = backdoor.port.unwrap_or_else(|| {
+                        warn!("Configuration setup: Missing field port in backdoor data.");
+                        6200.to_owned()
+                    });
+
+                    let backdoor_timeout = backdoor.timeout.unwrap_or_else(|| {
+                        warn!("Configuration setup: Missing field timeout in backdoor data.");
+                        60.to_owned()
+                    });
+
+                    (backdoor_enabled, backdoor_port, backdoor_timeout)
+                }
+                None => {
+                    warn!("Configuration setup: Missing backdoor data.");
+                    (false.to_owned(), 6200.to_owned(), 60.to_owned())
+                }
+            };
+
+        let (anonymous_enabled, anonymous_writable): (bool, bool) = match config_toml.anonymous {
+            Some(anonymous) => {
+                let anonymous_enabled = anonymous.enabled.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field enabled in anonymous data.");
+                    false.to_owned()
+                });
+
+                let anonymous_writable = anonymous.writable.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field writable in anonymous data.");
+                    false.to_owned()
+                });
+
+                (anonymous_enabled, anonymous_writable)
+            }
+            None => {
+                warn!("Configuration setup: Missing anonymous data.");
+                (false.to_owned(), false.to_owned())
+            }
+        };
+
+        let recorder_enabled: bool = match config_toml.recorder {
+            Some(recorder) => recorder.enabled.unwrap_or_else(|| {
+                warn!("Configuration setup: Missing field enabled in recorder data.");
+                true.to_owned()
+            }),
+            None => {
+                warn!("Configuration setup: Missing recorder data.");
+                true.to_owned()
+            }
+        };
+
+        let (capture_enabled, capture_max_payload): (bool, usize) = match config_toml.capture {
+            Some(capture) => {
+                let capture_enabled = capture.enabled.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field enabled in capture data.");
+                    true.to_owned()
+                });
+
+                let capture_max_payload = capture.max_payload.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field max_payload in capture data.");
+                    1048576.to_owned()
+                });
+
+                (capture_enabled, capture_max_payload)
+            }
+            None => {
+                warn!("Configuration setup: Missing capture data.");
+                (true.to_owned(), 1048576.to_owned())
+            }
+        };
+
+        let (sniffer_enabled, sniffer_wait, sniffer_max_bytes, sniffer_http_response): (
+            bool,
+            u64,
+            usize,
+            bool,
+        ) = match config_toml.sniffer {
+            Some(sniffer) => {
+                let sniffer_enabled = sniffer.enabled.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field enabled in sniffer data.");
+                    false.to_owned()
+                });
+
+                let sniffer_wait = sniffer.wait.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field wait in sniffer data.");
+                    150.to_owned()
+                });
+
+                let sniffer_max_bytes = sniffer.max_bytes.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field max_bytes in sniffer data.");
+                    512.to_owned()
+                });
+
+                let sniffer_http_response = sniffer.http_response.unwrap_or_else(|| {
+                    warn!("Configuration setup: Missing field http_response in sniffer data.");
+                    true.to_owned()
+                });
+
+                (
+                    sniffer_enabled,
+                    sniffer_wait,
+                    sniffer_max_bytes,
+                    sniffer_http_response,
+                )
+            }
+            None => {
+                warn!("Configuration setup: Missing sniffer data.");
+                (
+                    false.to_owned(),
+                    150.to_owned(),
+                    512.to_owned(),
+                    true.to_owned(),
+                )
+            }
+        };
+
+        let (proxy_enabled, proxy_trusted, proxy_data_connections): (bool, Vec<String>, bool) =
+            match config_toml.proxy {
+                Some(proxy) => {
+                    let proxy_enabled = proxy.enabled.unwrap_or_else(|| {
+                        warn!("Configuration setup: Missing field enabled in proxy data.");
+                        false.to_owned()
+                    });
+
+                    let proxy_trusted = proxy.trusted.unwrap_or_else(|| {
+                        warn!("Configuration setup: Missing field trusted in proxy data.");
+                        vec![]
+                    });
+
+                    let proxy_data_connections = proxy.data_connections.unwrap_or_else(|| {
+                        warn!("Configuration setup: Missing field data_connections in proxy data.");
+                        false.to_owned()
+                    });
+
+                    (proxy_enabled, proxy_trusted, proxy_data_connections)
+                }
+                None => {
+                    warn!("Configuration setup: Missing proxy data.");
+                    (false.to_owned(), vec![], false.to_owned())
                 }
             };
 
@@ -393,6 +1046,82 @@ impl Config {
                 )
             }
         };
+        let listeners: Vec<ListenerConfig> = match config_toml.listener {
+            Some(listeners) => listeners
+                .into_iter()
+                .enumerate()
+                .map(|(index, listener)| {
+                    let name = listener.name.unwrap_or_else(|| {
+                        warn!("Configuration setup: Missing field name in listener data.");
+                        format!("listener-{}", index + 1)
+                    });
+
+                    let bind_addresses = listener.bind_addresses.unwrap_or_else(|| {
+                        warn!(
+                            "Configuration setup: Missing field bind_addresses in listener data."
+                        );
+                        ftp_bind_addresses.to_owned()
+                    });
+
+                    let port = listener.port.unwrap_or_else(|| {
+                        warn!("Configuration setup: Missing field port in listener data.");
+                        21.to_owned()
+                    });
+
+                    let implicit_tls = listener.implicit_tls.unwrap_or_else(|| {
+                        warn!("Configuration setup: Missing field implicit_tls in listener data.");
+                        false.to_owned()
+                    });
+
+                    // Without the following fields the ones of the other settings are used.
+                    let welcome_message = listener
+                        .welcome_message
+                        .unwrap_or_else(|| ftp_welcome_message.to_owned());
+                    let personality = listener
+                        .personality
+                        .unwrap_or_else(|| personality_preset.to_owned());
+                    let max_concurrent_users = listener
+                        .max_concurrent_users
+                        .unwrap_or(max_concurrent_users);
+                    let filesystem = listener.filesystem.unwrap_or_else(|| "home".to_owned());
+
+                    ListenerConfig {
+                        name,
+                        bind_addresses,
+                        port,
+                        implicit_tls,
+                        welcome_message,
+                        personality,
+                        max_concurrent_users,
+                        filesystem,
+                    }
+                })
+                .collect(),
+            None => {
+                let listener = |name: &str, port: u16, implicit_tls: bool| ListenerConfig {
+                    name: name.to_owned(),
+                    bind_addresses: ftp_bind_addresses.to_owned(),
+                    port,
+                    implicit_tls,
+                    welcome_message: ftp_welcome_message.to_owned(),
+                    personality: personality_preset.to_owned(),
+                    max_concurrent_users,
+                    filesystem: "home".to_owned(),
+                };
+
+                let ftp_port = u16::try_from(ftp_port).unwrap_or_else(|_| {
+                    warn!("Configuration setup: Invalid field port in ftp data.");
+                    8080.to_owned()
+                });
+
+                let mut listeners = vec![listener("ftp", ftp_port, false)];
+                if implicit_tls_enabled {
+                    listeners.push(listener("ftps", implicit_tls_port, true));
+                }
+                listeners
+            }
+        };
+
         Config {
             version,
             max_concurrent_users,
@@ -413,7 +1142,49 @@ impl Config {
             db_database_name: database_name,
             ftp_help_message,
             ftp_welcome_message,
-            ftp_port,
+            ftp_max_command_length,
+            passive_address,
+            passive_min_port,
+            passive_max_port,
+            tls_enabled,
+            tls_certificate_path,
+            tls_private_key_path,
+            tls_common_name,
+            implicit_tls_certificate_path,
+            implicit_tls_private_key_path,
+            personality_preset,
+            personality_syst,
+            personality_version,
+            personality_listing,
+            personality_replies,
+            timeout_idle,
+            timeout_command,
+            timeout_data_connect,
+            timeout_data_transfer,
+            tarpit_enabled,
+            tarpit_threshold,
+            tarpit_delay,
+            tarpit_max_delay,
+            tarpit_banner_byte_delay,
+            tarpit_max_sessions,
+            bounce_sink,
+            bounce_sink_address,
+            backdoor_enabled,
+            backdoor_port,
+            backdoor_timeout,
+            anonymous_enabled,
+            anonymous_writable,
+            recorder_enabled,
+            capture_enabled,
+            capture_max_payload,
+            sniffer_enabled,
+            sniffer_wait,
+            sniffer_max_bytes,
+            sniffer_http_response,
+            proxy_enabled,
+            proxy_trusted,
+            proxy_data_connections,
+            listeners,
             honeynet_token,
             honeynet_id,
             honeynet_url,
@@ -446,9 +1217,59 @@ mod tests {
         assert_eq!(config.virus_total_token, "unknown");
         assert_eq!(config.virus_total_hash_url, "invalid_url");
         assert_eq!(config.virus_total_result_url, "invalid_url");
-        assert_eq!(config.ftp_port, 8080);
-        assert_eq!(config.ftp_welcome_message, "invalid_message");
-        assert_eq!(config.ftp_help_message, "invalid_message");
+        assert_eq!(config.ftp_max_command_length, 4096);
+        assert_eq!(config.passive_address, "");
+        assert_eq!(config.passive_min_port, 50000);
+        assert_eq!(config.passive_max_port, 50100);
+        assert!(!config.tls_enabled);
+        assert_eq!(config.tls_certificate_path, "");
+        assert_eq!(config.tls_private_key_path, "");
+        assert_eq!(config.tls_common_name, "localhost");
+        assert_eq!(config.implicit_tls_certificate_path, "");
+        assert_eq!(config.implicit_tls_private_key_path, "");
+        assert_eq!(config.ftp_welcome_message, "");
+        assert_eq!(config.ftp_help_message, "");
+        assert_eq!(config.personality_preset, "vsftpd");
+        assert_eq!(config.personality_syst, "");
+        assert_eq!(config.personality_version, "");
+        assert_eq!(config.personality_listing, "");
+        assert!(config.personality_replies.is_empty());
+        assert_eq!(config.timeout_idle, 300);
+        assert_eq!(config.timeout_command, 60);
+        assert_eq!(config.timeout_data_connect, 30);
+        assert_eq!(config.timeout_data_transfer, 300);
+        assert!(!config.tarpit_enabled);
+        assert_eq!(config.tarpit_threshold, 20);
+        assert_eq!(config.tarpit_delay, 1000);
+        assert_eq!(config.tarpit_max_delay, 30000);
+        assert_eq!(config.tarpit_banner_byte_delay, 250);
+        assert_eq!(config.tarpit_max_sessions, 25);
+        assert!(!config.bounce_sink);
+        assert_eq!(config.bounce_sink_address, "127.0.0.1:9");
+        assert!(!config.backdoor_enabled);
+        assert_eq!(config.backdoor_port, 6200);
+        assert_eq!(config.backdoor_timeout, 60);
+        assert!(!config.anonymous_enabled);
+        assert!(!config.anonymous_writable);
+        assert!(config.recorder_enabled);
+        assert!(config.capture_enabled);
+        assert_eq!(config.capture_max_payload, 1048576);
+        assert!(!config.sniffer_enabled);
+        assert_eq!(config.sniffer_wait, 150);
+        assert_eq!(config.sniffer_max_bytes, 512);
+        assert!(config.sniffer_http_response);
+        assert!(!config.proxy_enabled);
+        assert!(config.proxy_trusted.is_empty());
+        assert!(!config.proxy_data_connections);
+        assert_eq!(config.listeners.len(), 1);
+        assert_eq!(config.listeners[0].name, "ftp");
+        assert_eq!(config.listeners[0].bind_addresses, vec!["127.0.0.1"]);
+        assert_eq!(config.listeners[0].port, 8080);
+        assert!(!config.listeners[0].implicit_tls);
+        assert_eq!(config.listeners[0].welcome_message, "");
+        assert_eq!(config.listeners[0].personality, "vsftpd");
+        assert_eq!(config.listeners[0].max_concurrent_users, 25);
+        assert_eq!(config.listeners[0].filesystem, "home");
         assert_eq!(config.honeynet_url, "invalid_url");
         assert_eq!(config.honeynet_token, "invalid_token");
         assert_eq!(config.honeynet_id, 1);
@@ -474,9 +1295,76 @@ mod tests {
         assert_eq!(config.file_upload_limit, 5);
         assert_eq!(config.file_size_limit_in_gb, 5);
         assert_eq!(config.base_save_path, "/test/path");
-        assert_eq!(config.ftp_port, 1111);
-        assert_eq!(config.ftp_welcome_message, "welcome_msg");
+        assert_eq!(config.ftp_max_command_length, 512);
+        assert_eq!(config.passive_address, "10.0.0.1");
+        assert_eq!(config.passive_min_port, 40000);
+        assert_eq!(config.passive_max_port, 40010);
+        assert!(config.tls_enabled);
+        assert_eq!(config.tls_certificate_path, "/test/cert.pem");
+        assert_eq!(config.tls_private_key_path, "/test/key.pem");
+        assert_eq!(config.tls_common_name, "ftp.test");
+        assert_eq!(
+            config.implicit_tls_certificate_path,
+            "/test/implicit_cert.pem"
+        );
+        assert_eq!(
+            config.implicit_tls_private_key_path,
+            "/test/implicit_key.pem"
+        );
+        assert_eq!(config.ftp_welcome_message, "welcome_msg\nsecond_line");
         assert_eq!(config.ftp_help_message, "help_msg");
+        assert_eq!(config.personality_preset, "iis");
+        assert_eq!(config.personality_syst, "UNIX Type: L8");
+        assert_eq!(config.personality_version, "Microsoft FTP Service 10.0");
+        assert_eq!(config.personality_listing, "unix");
+        assert_eq!(config.personality_replies["230"], "Welcome.");
+        assert_eq!(config.timeout_idle, 120);
+        assert_eq!(config.timeout_command, 10);
+        assert_eq!(config.timeout_data_connect, 5);
+        assert_eq!(config.timeout_data_transfer, 60);
+        assert!(config.tarpit_enabled);
+        assert_eq!(config.tarpit_threshold, 5);
+        assert_eq!(config.tarpit_delay, 10);
+        assert_eq!(config.tarpit_max_delay, 100);
+        assert_eq!(config.tarpit_banner_byte_delay, 0);
+        assert_eq!(config.tarpit_max_sessions, 2);
+        assert!(config.bounce_sink);
+        assert_eq!(config.bounce_sink_address, "127.0.0.1:2009");
+        assert!(config.backdoor_enabled);
+        assert_eq!(config.backdoor_port, 6201);
+        assert_eq!(config.backdoor_timeout, 5);
+        assert!(config.anonymous_enabled);
+        assert!(config.anonymous_writable);
+        assert!(!config.recorder_enabled);
+        assert!(!config.capture_enabled);
+        assert_eq!(config.capture_max_payload, 4096);
+        assert!(!config.sniffer_enabled);
+        assert_eq!(config.sniffer_wait, 100);
+        assert_eq!(config.sniffer_max_bytes, 64);
+        assert!(!config.sniffer_http_response);
+        assert!(config.proxy_enabled);
+        assert_eq!(config.proxy_trusted, vec!["127.0.0.1", "10.0.0.0/8"]);
+        assert!(config.proxy_data_connections);
+        assert_eq!(config.listeners.len(), 2);
+        assert_eq!(config.listeners[0].name, "ftp");
+        assert_eq!(config.listeners[0].bind_addresses, vec!["0.0.0.0"]);
+        assert_eq!(config.listeners[0].port, 21);
+        assert!(!config.listeners[0].implicit_tls);
+        assert_eq!(config.listeners[0].welcome_message, "listener_welcome");
+        assert_eq!(config.listeners[0].personality, "proftpd");
+        assert_eq!(config.listeners[0].max_concurrent_users, 5);
+        assert_eq!(config.listeners[0].filesystem, "webserver");
+        assert_eq!(config.listeners[1].name, "alt");
+        assert_eq!(config.listeners[1].bind_addresses, vec!["0.0.0.0", "::"]);
+        assert_eq!(config.listeners[1].port, 2121);
+        assert!(config.listeners[1].implicit_tls);
+        assert_eq!(
+            config.listeners[1].welcome_message,
+            "welcome_msg\nsecond_line"
+        );
+        assert_eq!(config.listeners[1].personality, "iis");
+        assert_eq!(config.listeners[1].max_concurrent_users, 10);
+        assert_eq!(config.listeners[1].filesystem, "home");
         assert_eq!(config.honeynet_url, "honey_url");
         assert_eq!(config.honeynet_token, "honey_token");
         assert_eq!(config.honeynet_id, 2222);
@@ -20,7 +20,7 @@ use crate::configuration::config::{get_config, Config};
 ///  - [Database context][crate::configuration::config::Config#structfield.db_context]
 ///
 ///  - [Database name][crate::configuration::config::Config#structfield.db_database_name]
-pub(crate) async fn set_up_db(config_name: &str) -> Result<DatabaseConnection, DbErr> {
+pub async fn set_up_db(config_name: &str) -> Result<DatabaseConnection, DbErr> {
     info!("Setting up Database Connection ... ");
     let config = if config_name.is_empty() {
         get_config()
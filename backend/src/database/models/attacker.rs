@@ -19,6 +19,8 @@ pub struct Model {
     #[sea_orm(column_type = "Timestamp")]
     pub updated: chrono::DateTime<Local>,
     pub file_system: Option<FtpFileSystem>,
+    /// The name of the listener the attacker first came in on.
+    pub listener: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -34,6 +36,12 @@ pub enum Relation {
     Credentials,
     #[sea_orm(has_many = "super::uploaded_files::Entity")]
     UploadedFiles,
+    #[sea_orm(has_many = "super::tls_fingerprints::Entity")]
+    TlsFingerprints,
+    #[sea_orm(has_many = "super::events::Entity")]
+    Events,
+    #[sea_orm(has_many = "super::sessions::Entity")]
+    Sessions,
 }
 
 impl Related<super::uploaded_files::Entity> for Entity {
@@ -42,6 +50,24 @@ impl Related<super::uploaded_files::Entity> for Entity {
     }
 }
 
+impl Related<super::tls_fingerprints::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TlsFingerprints.def()
+    }
+}
+
+impl Related<super::events::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Events.def()
+    }
+}
+
+impl Related<super::sessions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Sessions.def()
+    }
+}
+
 impl Related<super::credentials::Entity> for Entity {
     fn to() -> RelationDef {
         super::attacker_to_credentials::Relation::Credentials.def()
@@ -0,0 +1,71 @@
+//! Model of the 'Captures' table
+
+use chrono::Local;
+use sea_orm::entity::prelude::*;
+use sea_orm::sea_query::{Alias, SeaRc};
+use strum_macros::{Display, EnumString};
+
+/// The connection a [capture][Model] was read from or written to.
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+#[strum(serialize_all = "snake_case")]
+pub enum Channel {
+    /// The control connection of the session.
+    Control,
+    /// A data connection of a transfer or listing.
+    Data,
+}
+
+/// The side of the connection that sent a [capture][Model].
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+#[strum(serialize_all = "snake_case")]
+pub enum Sender {
+    /// The bytes were received from the client.
+    Client,
+    /// The bytes were sent by the honeypot.
+    Server,
+}
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "Captures")]
+/// Main model that is used
+///
+/// A capture without payload marks the end of its connection.
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i64,
+    #[sea_orm(column_name = "sessionId")]
+    pub session_id: i64,
+    pub sequence: i32,
+    pub channel: String,
+    pub sender: String,
+    #[sea_orm(column_name = "clientAddress")]
+    pub client_address: String,
+    #[sea_orm(column_name = "serverAddress")]
+    pub server_address: String,
+    #[sea_orm(column_type = "Binary(BlobSize::Long)")]
+    pub payload: Vec<u8>,
+    /// Stored with microseconds, so the captures of overlapping sessions can be interleaved.
+    #[sea_orm(column_type = "Custom(SeaRc::new(Alias::new(\"TIMESTAMP(6)\")))")]
+    pub created: chrono::DateTime<Local>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+/// Represents the relation to other tables
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::sessions::Entity",
+        from = "Column::SessionId",
+        to = "super::sessions::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Sessions,
+}
+
+impl Related<super::sessions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Sessions.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
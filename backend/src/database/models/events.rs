@@ -0,0 +1,67 @@
+//! Model of the 'Events' table
+
+use chrono::Local;
+use sea_orm::entity::prelude::*;
+use strum_macros::Display;
+
+/// The kinds of notable attacker actions stored as [events][Model].
+#[derive(Clone, Copy, Debug, Display, PartialEq)]
+#[strum(serialize_all = "snake_case")]
+pub enum EventKind {
+    /// A file or directory was renamed, holds the old and new name.
+    Rename,
+    /// A USER ending in `:)` triggered the vsftpd 2.3.4 backdoor, holds the credentials.
+    BackdoorTrigger,
+    /// A command was sent to the fake backdoor shell, holds the command line.
+    BackdoorCommand,
+    /// A file was copied with SITE CPFR and CPTO, holds the source, the target and whether the
+    /// target looks like an exploitation of the ProFTPD mod_copy vulnerability.
+    SiteCopy,
+    /// An attacker logged in anonymously, holds the username and the password.
+    AnonymousLogin,
+    /// An unknown SITE command was sent, holds the command verbatim.
+    SiteCommand,
+    /// PORT or EPRT pointed to another host, an internal address or a privileged port, holds
+    /// the command, the target host and port, the reason and whether it was sent to the sink.
+    Bounce,
+    /// Another protocol than FTP was sent to the FTP port, holds the protocol, the first bytes
+    /// as hex and details like the path and user agent of HTTP requests.
+    Probe,
+}
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "Events")]
+/// Main model that is used
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i64,
+    pub ip: String,
+    #[sea_orm(column_name = "attackerId")]
+    pub attacker_id: Option<i64>,
+    pub kind: String,
+    #[sea_orm(column_type = "Text")]
+    pub data: String,
+    #[sea_orm(column_type = "Timestamp")]
+    pub created: chrono::DateTime<Local>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+/// Represents the relation to other tables
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::attacker::Entity",
+        from = "Column::AttackerId",
+        to = "super::attacker::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Attacker,
+}
+
+impl Related<super::attacker::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Attacker.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
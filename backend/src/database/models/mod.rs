@@ -8,5 +8,11 @@ pub mod prelude;
 
 pub mod attacker;
 pub mod attacker_to_credentials;
+pub mod captures;
 pub mod credentials;
+pub mod events;
+pub mod public_file_systems;
+pub mod sessions;
+pub mod tls_fingerprints;
+pub mod transcripts;
 pub mod uploaded_files;
@@ -2,5 +2,11 @@
 
 pub use super::attacker::Entity as Attacker;
 pub use super::attacker_to_credentials::Entity as AttackerToCredentials;
+pub use super::captures::Entity as Captures;
 pub use super::credentials::Entity as Credentials;
+pub use super::events::Entity as Events;
+pub use super::public_file_systems::Entity as PublicFileSystems;
+pub use super::sessions::Entity as Sessions;
+pub use super::tls_fingerprints::Entity as TlsFingerprints;
+pub use super::transcripts::Entity as Transcripts;
 pub use super::uploaded_files::Entity as UploadedFiles;
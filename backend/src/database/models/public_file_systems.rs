@@ -0,0 +1,23 @@
+//! Model of the 'PublicFileSystems' table
+
+use sea_orm::entity::prelude::*;
+
+use crate::filesystem::ftp_fs::FtpFileSystem;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "PublicFileSystems")]
+/// Main model that is used
+///
+/// Holds the filesystem shared by all anonymous users, which belongs to no attacker and is never
+/// cleaned up.
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: i64,
+    pub file_system: FtpFileSystem,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+/// Represents the relation to other tables
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
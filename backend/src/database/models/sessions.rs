@@ -0,0 +1,80 @@
+//! Model of the 'Sessions' table
+
+use chrono::Local;
+use sea_orm::entity::prelude::*;
+use strum_macros::Display;
+
+/// The ways a [session][Model] ends.
+#[derive(Clone, Copy, Debug, Display, PartialEq)]
+#[strum(serialize_all = "snake_case")]
+pub enum Outcome {
+    /// The maximum of concurrent users was reached, the client was turned away.
+    Rejected,
+    /// The client ended the session with QUIT.
+    Quit,
+    /// The client closed the connection.
+    Closed,
+    /// The client was idle or too slow sending a command.
+    TimedOut,
+    /// The connection failed or was closed by the honeypot.
+    Disconnected,
+    /// The client spoke another protocol than FTP, which was stored as probe event.
+    Probe,
+}
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "Sessions")]
+/// Main model that is used
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i64,
+    pub ip: String,
+    pub port: i32,
+    pub listener: String,
+    #[sea_orm(column_name = "attackerId")]
+    pub attacker_id: Option<i64>,
+    pub outcome: Option<String>,
+    /// Set if the client logged in anonymously and worked on the shared public filesystem.
+    pub anonymous: bool,
+    #[sea_orm(column_type = "Timestamp")]
+    pub started: chrono::DateTime<Local>,
+    #[sea_orm(column_type = "Timestamp", nullable)]
+    pub ended: Option<chrono::DateTime<Local>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+/// Represents the relation to other tables
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::attacker::Entity",
+        from = "Column::AttackerId",
+        to = "super::attacker::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Attacker,
+    #[sea_orm(has_many = "super::transcripts::Entity")]
+    Transcripts,
+    #[sea_orm(has_many = "super::captures::Entity")]
+    Captures,
+}
+
+impl Related<super::attacker::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Attacker.def()
+    }
+}
+
+impl Related<super::transcripts::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Transcripts.def()
+    }
+}
+
+impl Related<super::captures::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Captures.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,46 @@
+//! Model of the 'TlsFingerprints' table
+
+use chrono::Local;
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "TlsFingerprints")]
+/// Main model that is used
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i64,
+    pub ip: String,
+    pub listener: String,
+    #[sea_orm(column_name = "attackerId")]
+    pub attacker_id: Option<i64>,
+    #[sea_orm(column_type = "Text")]
+    pub ja3: String,
+    #[sea_orm(column_name = "ja3Hash")]
+    pub ja3_hash: String,
+    pub ja4: String,
+    #[sea_orm(column_name = "serverName")]
+    pub server_name: Option<String>,
+    #[sea_orm(column_type = "Timestamp")]
+    pub created: chrono::DateTime<Local>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+/// Represents the relation to other tables
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::attacker::Entity",
+        from = "Column::AttackerId",
+        to = "super::attacker::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Attacker,
+}
+
+impl Related<super::attacker::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Attacker.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
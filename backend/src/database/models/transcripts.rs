@@ -0,0 +1,55 @@
+//! Model of the 'Transcripts' table
+
+use chrono::Local;
+use sea_orm::entity::prelude::*;
+use sea_orm::sea_query::{Alias, SeaRc};
+use strum_macros::{Display, EnumString};
+
+/// The direction of a [transcript line][Model].
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+#[strum(serialize_all = "snake_case")]
+pub enum Direction {
+    /// A command line received from the client.
+    Command,
+    /// A reply sent to the client.
+    Reply,
+    /// A change of the filesystem of the attacker, caused by the preceding command.
+    Filesystem,
+}
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "Transcripts")]
+/// Main model that is used
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i64,
+    #[sea_orm(column_name = "sessionId")]
+    pub session_id: i64,
+    pub sequence: i32,
+    pub direction: String,
+    #[sea_orm(column_type = "Text")]
+    pub line: String,
+    #[sea_orm(column_type = "Custom(SeaRc::new(Alias::new(\"TIMESTAMP(6)\")))")]
+    pub created: chrono::DateTime<Local>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+/// Represents the relation to other tables
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::sessions::Entity",
+        from = "Column::SessionId",
+        to = "super::sessions::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Sessions,
+}
+
+impl Related<super::sessions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Sessions.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
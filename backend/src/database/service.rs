@@ -2,17 +2,22 @@
 
 use async_trait::async_trait;
 use chrono::{Duration, Local};
+use sea_orm::sea_query::OnConflict;
 use sea_orm::{
     ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, DeleteResult,
-    EntityTrait, ExecResult, ModelTrait, QueryFilter, Schema, Statement,
+    EntityTrait, ExecResult, ModelTrait, QueryFilter, QueryOrder, Schema, Statement,
 };
 
 use crate::database::models::attacker_to_credentials::ActiveModel;
 use crate::database::models::prelude::{
-    Attacker, AttackerToCredentials, Credentials, UploadedFiles,
+    Attacker, AttackerToCredentials, Captures, Credentials, Events, PublicFileSystems, Sessions,
+    TlsFingerprints, Transcripts, UploadedFiles,
 };
 use crate::database::models::uploaded_files::Model;
-use crate::database::models::{attacker, attacker_to_credentials, credentials, uploaded_files};
+use crate::database::models::{
+    attacker, attacker_to_credentials, captures, credentials, events, public_file_systems,
+    sessions, tls_fingerprints, transcripts, uploaded_files,
+};
 use crate::database::service_trait::DatabaseTrait;
 
 #[cfg(feature = "testing")]
@@ -27,6 +32,49 @@ pub struct DatabaseImplementation {
 
 const DB_ERROR_MESSAGE: &str = "Error while executing Database statement: ";
 
+impl DatabaseImplementation {
+    /// Adds a column to an existing table, returns [None] if the table has the column already.
+    ///
+    /// * `table`      - The name of the table.
+    /// * `column`     - The name of the column.
+    /// * `definition` - The type and constraints of the column.
+    async fn add_column(
+        &self,
+        table: &str,
+        column: &str,
+        definition: &str,
+    ) -> Result<Option<ExecResult>, DbErr> {
+        let backend = self.db.get_database_backend();
+        let count_statement = Statement::from_sql_and_values(
+            backend,
+            [
+                "SELECT COUNT(*) AS count FROM information_schema.COLUMNS",
+                "WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? AND COLUMN_NAME = ?;",
+            ]
+            .join(" ")
+            .as_str(),
+            [table.into(), column.into()],
+        );
+        let count: i64 = match self.db.query_one(count_statement).await? {
+            Some(row) => row.try_get("", "count")?,
+            None => 0,
+        };
+
+        if count > 0 {
+            return Ok(None);
+        }
+
+        let update_statement = Statement::from_string(
+            backend,
+            format!(
+                "ALTER TABLE `{}` ADD COLUMN {} {};",
+                table, column, definition
+            ),
+        );
+        self.db.execute(update_statement).await.map(Some)
+    }
+}
+
 #[async_trait]
 impl DatabaseTrait for DatabaseImplementation {
     async fn create_attacker_table(&self) -> Result<ExecResult, DbErr> {
@@ -39,7 +87,7 @@ impl DatabaseTrait for DatabaseImplementation {
     async fn update_attacker_table(&self) -> Result<ExecResult, DbErr> {
         let update_statement = Statement::from_string(
             self.db.get_database_backend(),
-            vec![
+            [
                 "ALTER TABLE `Attackers`",
                 "CHANGE  updated",
                 "updated TIMESTAMP NOT NULL",
@@ -51,6 +99,12 @@ impl DatabaseTrait for DatabaseImplementation {
         let update_table_result = self.db.execute(update_statement).await;
         return update_table_result;
     }
+    async fn add_attacker_listener_column(&self) -> Result<Option<ExecResult>, DbErr> {
+        let update_table_result = self
+            .add_column("Attackers", "listener", "VARCHAR(255) NULL")
+            .await;
+        return update_table_result;
+    }
     async fn create_credentials_table(&self) -> Result<ExecResult, DbErr> {
         let builder = self.db.get_database_backend();
         let schema = Schema::new(builder);
@@ -75,6 +129,70 @@ impl DatabaseTrait for DatabaseImplementation {
         return table_create_result;
     }
 
+    async fn create_tls_fingerprints_table(&self) -> Result<ExecResult, DbErr> {
+        let builder = self.db.get_database_backend();
+        let schema = Schema::new(builder);
+        let statement = builder.build(&schema.create_table_from_entity(TlsFingerprints));
+        let table_create_result = self.db.execute(statement).await;
+        return table_create_result;
+    }
+
+    async fn create_events_table(&self) -> Result<ExecResult, DbErr> {
+        let builder = self.db.get_database_backend();
+        let schema = Schema::new(builder);
+        let statement = builder.build(&schema.create_table_from_entity(Events));
+        let table_create_result = self.db.execute(statement).await;
+        return table_create_result;
+    }
+
+    async fn create_sessions_table(&self) -> Result<ExecResult, DbErr> {
+        let builder = self.db.get_database_backend();
+        let schema = Schema::new(builder);
+        let statement = builder.build(&schema.create_table_from_entity(Sessions));
+        let table_create_result = self.db.execute(statement).await;
+        return table_create_result;
+    }
+
+    async fn update_sessions_table(&self) -> Result<Option<ExecResult>, DbErr> {
+        let update_table_result = self
+            .add_column("Sessions", "anonymous", "BOOLEAN NOT NULL DEFAULT FALSE")
+            .await;
+        return update_table_result;
+    }
+
+    async fn create_transcripts_table(&self) -> Result<ExecResult, DbErr> {
+        let builder = self.db.get_database_backend();
+        let schema = Schema::new(builder);
+        let statement = builder.build(&schema.create_table_from_entity(Transcripts));
+        let table_create_result = self.db.execute(statement).await;
+        return table_create_result;
+    }
+
+    async fn create_captures_table(&self) -> Result<ExecResult, DbErr> {
+        let builder = self.db.get_database_backend();
+        let schema = Schema::new(builder);
+        let statement = builder.build(&schema.create_table_from_entity(Captures));
+        let table_create_result = self.db.execute(statement).await;
+        return table_create_result;
+    }
+
+    async fn create_public_file_systems_table(&self) -> Result<ExecResult, DbErr> {
+        let builder = self.db.get_database_backend();
+        let schema = Schema::new(builder);
+        let statement = builder.build(&schema.create_table_from_entity(PublicFileSystems));
+        let table_create_result = self.db.execute(statement).await;
+        return table_create_result;
+    }
+
+    async fn update_captures_table(&self) -> Result<ExecResult, DbErr> {
+        let update_statement = Statement::from_string(
+            self.db.get_database_backend(),
+            "ALTER TABLE `Captures` MODIFY created TIMESTAMP(6) NOT NULL;".to_string(),
+        );
+        let update_table_result = self.db.execute(update_statement).await;
+        return update_table_result;
+    }
+
     // Attacker operations
     async fn get_attacker_by_id(&self, id: i64) -> Option<attacker::Model> {
         let attacker: Option<attacker::Model> = Attacker::find_by_id(id)
@@ -112,22 +230,6 @@ impl DatabaseTrait for DatabaseImplementation {
         return result;
     }
 
-    async fn get_attacker_by_timestamp(&self) -> Vec<attacker::Model> {
-        let time: chrono::DateTime<Local> = Local::now() - Duration::days(7);
-
-        let result = Attacker::find()
-            .filter(attacker::Column::Updated.lt(time))
-            .all(&self.db)
-            .await
-            .unwrap_or_else(|_| {
-                panic!(
-                    "{} Could not get attacker by updated column!",
-                    DB_ERROR_MESSAGE
-                )
-            });
-        return result;
-    }
-
     async fn delete_attacker_if_not_updated_in_one_week(&self) -> DeleteResult {
         let time: chrono::DateTime<Local> = Local::now() - Duration::days(7);
         let result: DeleteResult = attacker::Entity::delete_many()
@@ -153,23 +255,6 @@ impl DatabaseTrait for DatabaseImplementation {
         return attacker;
     }
 
-    async fn delete_attacker(&self, attacker: attacker::ActiveModel) -> DeleteResult {
-        let result: DeleteResult = attacker.delete(&self.db).await.unwrap_or_else(|_| {
-            panic!(
-                "{} Could not delete attacker by Active Model!",
-                DB_ERROR_MESSAGE
-            )
-        });
-        return result;
-    }
-    async fn delete_attacker_by_id(&self, id: i64) -> DeleteResult {
-        let result: DeleteResult = attacker::Entity::delete_by_id(id)
-            .exec(&self.db)
-            .await
-            .unwrap_or_else(|_| panic!("{} Could not delete attacker by id!", DB_ERROR_MESSAGE));
-        return result;
-    }
-
     // Credentials Operations
     async fn get_credentials_by_id(&self, id: i64) -> Option<credentials::Model> {
         let credentials: Option<credentials::Model> = Credentials::find_by_id(id)
@@ -224,15 +309,6 @@ impl DatabaseTrait for DatabaseImplementation {
     }
 
     // FileUpload operations
-    async fn get_file_by_hash(&self, hash: &String) -> Option<Model> {
-        let uploaded_file: Option<Model> = UploadedFiles::find()
-            .filter(uploaded_files::Column::Hash.eq(hash))
-            .one(&self.db)
-            .await
-            .unwrap_or_else(|_| panic!("{} Could not get file by hash!", DB_ERROR_MESSAGE));
-        return uploaded_file;
-    }
-
     async fn get_files_by_attacker_id(&self, attacker_id: i64) -> Vec<Model> {
         let uploaded_files: Vec<Model> = UploadedFiles::find()
             .filter(uploaded_files::Column::AttackerId.eq(attacker_id))
@@ -256,6 +332,15 @@ impl DatabaseTrait for DatabaseImplementation {
         return uploaded_files;
     }
 
+    async fn get_public_files(&self) -> Vec<Model> {
+        let uploaded_files: Vec<Model> = UploadedFiles::find()
+            .filter(uploaded_files::Column::AttackerId.is_null())
+            .all(&self.db)
+            .await
+            .unwrap_or_else(|_| panic!("{} Could not get public files!", DB_ERROR_MESSAGE));
+        return uploaded_files;
+    }
+
     async fn update_file(&self, file: uploaded_files::ActiveModel) -> uploaded_files::ActiveModel {
         let uploaded_file: uploaded_files::ActiveModel =
             file.save(&self.db).await.unwrap_or_else(|_| {
@@ -277,20 +362,6 @@ impl DatabaseTrait for DatabaseImplementation {
         return result;
     }
 
-    async fn delete_files_by_attacker_id(&self, attacker_id: i64) -> DeleteResult {
-        let result: DeleteResult = uploaded_files::Entity::delete_many()
-            .filter(uploaded_files::Column::AttackerId.eq(attacker_id))
-            .exec(&self.db)
-            .await
-            .unwrap_or_else(|_| {
-                panic!(
-                    "{} Could not delete files by attacker id!",
-                    DB_ERROR_MESSAGE
-                )
-            });
-        return result;
-    }
-
     async fn update_attacker_to_credentials(
         &self,
         attacker_to_credentials: ActiveModel,
@@ -298,39 +369,156 @@ impl DatabaseTrait for DatabaseImplementation {
         let attacker_to_credentials = attacker_to_credentials.insert(&self.db).await;
         return attacker_to_credentials;
     }
-    async fn get_credentials_from_attacker(
+    async fn get_credentials_by_id_from_attacker(
         &self,
         attacker: &attacker::Model,
-    ) -> Vec<credentials::Model> {
+        credentials_id: i64,
+    ) -> Option<credentials::Model> {
         let result = attacker
             .find_related(Credentials)
-            .all(&self.db)
+            .filter(credentials::Column::Id.eq(credentials_id))
+            .one(&self.db)
             .await
             .unwrap_or_else(|_| {
                 panic!(
-                    "{} Could not get credentials by Active Attacker Model!",
+                    "{} Could not get credentials by id from attacker!",
                     DB_ERROR_MESSAGE
                 )
             });
         return result;
     }
 
-    async fn get_credentials_by_id_from_attacker(
+    // TLS fingerprint operations
+    async fn update_tls_fingerprint(
         &self,
-        attacker: &attacker::Model,
-        credentials_id: i64,
-    ) -> Option<credentials::Model> {
-        let result = attacker
-            .find_related(Credentials)
-            .filter(credentials::Column::Id.eq(credentials_id))
+        tls_fingerprint: tls_fingerprints::ActiveModel,
+    ) -> tls_fingerprints::ActiveModel {
+        let tls_fingerprint: tls_fingerprints::ActiveModel =
+            tls_fingerprint.save(&self.db).await.unwrap_or_else(|e| {
+                panic!(
+                    "{} Could not update TLS fingerprint by Active Model! {}",
+                    DB_ERROR_MESSAGE, e
+                )
+            });
+        return tls_fingerprint;
+    }
+
+    // Event operations
+    async fn update_event(&self, event: events::ActiveModel) -> events::ActiveModel {
+        let event: events::ActiveModel = event.save(&self.db).await.unwrap_or_else(|e| {
+            panic!(
+                "{} Could not update event by Active Model! {}",
+                DB_ERROR_MESSAGE, e
+            )
+        });
+        return event;
+    }
+
+    // Session operations
+    async fn update_session(&self, session: sessions::ActiveModel) -> sessions::ActiveModel {
+        let session: sessions::ActiveModel = session.save(&self.db).await.unwrap_or_else(|e| {
+            panic!(
+                "{} Could not update session by Active Model! {}",
+                DB_ERROR_MESSAGE, e
+            )
+        });
+        return session;
+    }
+
+    async fn get_session_by_id(&self, id: i64) -> Option<sessions::Model> {
+        let session: Option<sessions::Model> = Sessions::find_by_id(id)
             .one(&self.db)
             .await
+            .unwrap_or_else(|_| panic!("{} Could not get session by id!", DB_ERROR_MESSAGE));
+        return session;
+    }
+
+    async fn get_sessions_by_attacker_id(&self, attacker_id: i64) -> Vec<sessions::Model> {
+        let sessions: Vec<sessions::Model> = Sessions::find()
+            .filter(sessions::Column::AttackerId.eq(attacker_id))
+            .order_by_asc(sessions::Column::Started)
+            .all(&self.db)
+            .await
             .unwrap_or_else(|_| {
                 panic!(
-                    "{} Could not get credentials by id from attacker!",
+                    "{} Could not get sessions by attacker id!",
                     DB_ERROR_MESSAGE
                 )
             });
-        return result;
+        return sessions;
+    }
+
+    async fn insert_transcript_lines(&self, lines: Vec<transcripts::ActiveModel>) {
+        if lines.is_empty() {
+            return;
+        }
+
+        Transcripts::insert_many(lines)
+            .exec(&self.db)
+            .await
+            .unwrap_or_else(|e| {
+                panic!(
+                    "{} Could not insert transcript lines! {}",
+                    DB_ERROR_MESSAGE, e
+                )
+            });
+    }
+
+    async fn get_transcript_by_session_id(&self, session_id: i64) -> Vec<transcripts::Model> {
+        let lines: Vec<transcripts::Model> = Transcripts::find()
+            .filter(transcripts::Column::SessionId.eq(session_id))
+            .order_by_asc(transcripts::Column::Sequence)
+            .all(&self.db)
+            .await
+            .unwrap_or_else(|_| {
+                panic!(
+                    "{} Could not get transcript by session id!",
+                    DB_ERROR_MESSAGE
+                )
+            });
+        return lines;
+    }
+
+    async fn insert_captures(&self, captures: Vec<captures::ActiveModel>) {
+        if captures.is_empty() {
+            return;
+        }
+
+        Captures::insert_many(captures)
+            .exec(&self.db)
+            .await
+            .unwrap_or_else(|e| panic!("{} Could not insert captures! {}", DB_ERROR_MESSAGE, e));
+    }
+
+    async fn get_captures_by_session_id(&self, session_id: i64) -> Vec<captures::Model> {
+        let captures: Vec<captures::Model> = Captures::find()
+            .filter(captures::Column::SessionId.eq(session_id))
+            .order_by_asc(captures::Column::Sequence)
+            .all(&self.db)
+            .await
+            .unwrap_or_else(|_| {
+                panic!("{} Could not get captures by session id!", DB_ERROR_MESSAGE)
+            });
+        return captures;
+    }
+
+    async fn get_public_file_system(&self, id: i64) -> Option<public_file_systems::Model> {
+        let file_system: Option<public_file_systems::Model> = PublicFileSystems::find_by_id(id)
+            .one(&self.db)
+            .await
+            .unwrap_or_else(|_| panic!("{} Could not get public filesystem!", DB_ERROR_MESSAGE));
+        return file_system;
+    }
+
+    async fn update_public_file_system(&self, file_system: public_file_systems::ActiveModel) {
+        PublicFileSystems::insert(file_system)
+            .on_conflict(
+                OnConflict::column(public_file_systems::Column::Id)
+                    .update_column(public_file_systems::Column::FileSystem)
+                    .to_owned(),
+            )
+            .exec(&self.db)
+            .await
+            .unwrap_or_else(|_| panic!("{} Could not update public filesystem!", DB_ERROR_MESSAGE));
     }
 }
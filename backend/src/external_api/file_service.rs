@@ -35,11 +35,10 @@ use crate::database::service_trait::DatabaseTrait;
 /// 1 the vector is empty
 ///
 /// 2 or the virustotal api gives us an 429 code so we make to many requests and we break out of the loop and go out of the function
-
 pub async fn get_virus_total_result_of_files(files: Vec<Model>, db: &DatabaseImplementation) {
     let api_key = get_config().virus_total_token;
     for model in files {
-        let model_id = model.id.clone();
+        let model_id = model.id;
         info!("Start processing of file with file_id: {}", model_id);
         let attacker_id = model.attacker_id.unwrap_or_default();
 
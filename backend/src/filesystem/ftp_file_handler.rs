@@ -21,8 +21,10 @@ use crate::configuration::config::get_config;
 ///
 /// # Example
 /// Creates a 15 Byte sized file with random data in main path named test.txt
-/// ```
-/// create_file(15, "", "test.txt");
+/// ```no_run
+/// use rust_ftp_honeypot::filesystem::ftp_file_handler::create_file;
+///
+/// create_file(15, "test.txt".to_string());
 /// ```
 pub fn create_file(file_size_in_bytes: usize, file_name: String) -> String {
     let mut path = get_config().base_save_path;
@@ -1,15 +1,18 @@
 //! Represents the filesystem.
 
 use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Mutex;
 
 use chrono::{Duration, Local};
 use rand::Rng;
 use sea_orm::ActiveValue::Set;
 use sea_orm::Update;
 use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
 
 use crate::configuration::config::get_config;
-use crate::database::models::{attacker, uploaded_files};
+use crate::database::models::{attacker, public_file_systems, uploaded_files};
 use crate::database::service::DatabaseImplementation;
 use crate::database::service_trait::DatabaseTrait;
 use crate::filesystem::ftp_file_handler::{create_file, generate_files};
@@ -21,8 +24,33 @@ use crate::filesystem::models::ftp_node::{generate_dir, FtpNode};
 pub struct FtpFileSystem {
     pub root: Box<FtpNode>,
     pub path: Vec<String>,
+    /// Set for the shared filesystem of anonymous users, which isn't stored with an attacker.
+    #[serde(skip)]
+    pub public: bool,
 }
 
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+/// The directory layouts of the filesystems of new attackers.
+pub enum Template {
+    /// Pictures and documents of a home directory.
+    Home,
+    /// The document root and logs of a web server.
+    Webserver,
+    /// Backup archives and database dumps.
+    Backup,
+}
+
+/// The filesystem shared by all anonymous users, created upon the first anonymous login.
+static PUBLIC_FS: Mutex<Option<FtpFileSystem>> = Mutex::new(None);
+
+/// Serializes storing the public filesystem, so an older state never overwrites a newer one.
+static PUBLIC_FS_STORE: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// The id of the [public_file_systems] row the filesystem shared by all anonymous users is stored
+/// in, which no attacker owns and the clean up never removes.
+const PUBLIC_FS_ID: i64 = 1;
+
 /// Creates a default filesystem with some directories and files
 #[allow(dead_code)]
 pub fn new_fs() -> FtpFileSystem {
@@ -32,6 +60,7 @@ pub fn new_fs() -> FtpFileSystem {
         files: vec![],
         size: 0,
         timestamp: String::from("Mar 13 19:59"),
+        permissions: None,
     });
 
     let sub_sub_node_1 = Box::new(FtpNode {
@@ -40,6 +69,7 @@ pub fn new_fs() -> FtpFileSystem {
         files: vec![],
         size: 0,
         timestamp: String::from("Mar 18 15:35"),
+        permissions: None,
     });
     let sub_sub_node_2 = Box::new(FtpNode {
         decoration: String::from("private"),
@@ -47,6 +77,7 @@ pub fn new_fs() -> FtpFileSystem {
         files: vec![],
         size: 0,
         timestamp: String::from("Mar 25 17:03"),
+        permissions: None,
     });
     let mut node_1_map = HashMap::new();
     node_1_map.insert(String::from("invoices"), sub_sub_node_1);
@@ -58,6 +89,7 @@ pub fn new_fs() -> FtpFileSystem {
         files: vec![],
         size: 0,
         timestamp: String::from("Mar 17 22:31"),
+        permissions: None,
     });
     let mut root_map = HashMap::new();
     root_map.insert(String::from("pictures"), sub_node_1);
@@ -69,10 +101,12 @@ pub fn new_fs() -> FtpFileSystem {
         files: vec![],
         size: 0,
         timestamp: String::from("Mar 17 18:08"),
+        permissions: None,
     });
     FtpFileSystem {
         root: root_node,
         path: Vec::new(),
+        public: false,
     }
 }
 
@@ -89,7 +123,7 @@ pub fn get_randomized_timestamp() -> String {
     date -= Duration::seconds(-rng.gen_range(0..150));
     date -= Duration::weeks(-rng.gen_range(0..15));
 
-    return date.format("%b %d %H:%M").to_string();
+    date.format("%b %d %H:%M").to_string()
 }
 
 /// Parser to generate valid [FtpFile] objects from the given file parameters
@@ -97,7 +131,7 @@ pub fn get_randomized_timestamp() -> String {
 /// Uses randomization for the timestamp
 ///
 /// * `files: Vec<(String, String, u64)>` - File information in format
-/// ( file path, file name, file size )
+///   ( file path, file name, file size )
 pub fn parse_to_file_vec(files: Vec<(String, String, u64)>) -> Vec<FtpFile> {
     let mut ftp_files = vec![];
     for (file_path, file_name, file_size) in files {
@@ -107,65 +141,164 @@ pub fn parse_to_file_vec(files: Vec<(String, String, u64)>) -> Vec<FtpFile> {
             file_id: None,
             timestamp: get_randomized_timestamp(),
             default_file: Some(file_path),
+            permissions: None,
         };
         ftp_files.push(file)
     }
     ftp_files
 }
 
-/// Creates a default filesystem with some directories and default files
-pub fn new_fs_of_attacker(attacker_id: i64) -> FtpFileSystem {
+/// Creates a directory of a [Template] with a randomized timestamp
+///
+/// * `name`  - The name of the directory.
+/// * `files` - The files of the directory.
+/// * `dirs`  - The sub directories.
+fn template_dir(name: &str, files: Vec<FtpFile>, dirs: Vec<FtpNode>) -> FtpNode {
+    FtpNode {
+        decoration: String::from(name),
+        dirs: dirs
+            .into_iter()
+            .map(|dir| (dir.decoration.to_owned(), Box::new(dir)))
+            .collect(),
+        files,
+        size: 0,
+        timestamp: get_randomized_timestamp(),
+        permissions: None,
+    }
+}
+
+/// Creates the filesystem of a new attacker with the directories of the `template` and default
+/// files
+///
+/// * `attacker_id` - The id of the attacker, whose directory holds the default files.
+/// * `template`    - The [Template] of the listener the attacker came in on.
+pub fn new_fs_of_attacker(attacker_id: i64, template: Template) -> FtpFileSystem {
     let attacker_name = format!("{}", attacker_id);
     let files = generate_files(attacker_name, 15);
     let ftp_files = parse_to_file_vec(files);
+    let files = |range: Range<usize>| ftp_files[range].to_owned();
+
+    let root_node = match template {
+        Template::Home => template_dir(
+            "root",
+            files(13..14),
+            vec![
+                template_dir("pictures", files(0..1), vec![]),
+                template_dir(
+                    "documents",
+                    files(10..13),
+                    vec![
+                        template_dir("invoices", files(2..4), vec![]),
+                        template_dir("private", files(5..9), vec![]),
+                    ],
+                ),
+            ],
+        ),
+        Template::Webserver => template_dir(
+            "root",
+            files(13..15),
+            vec![
+                template_dir(
+                    "www",
+                    vec![],
+                    vec![
+                        template_dir("html", files(0..5), vec![]),
+                        template_dir("logs", files(5..8), vec![]),
+                    ],
+                ),
+                template_dir("backups", files(8..13), vec![]),
+            ],
+        ),
+        Template::Backup => template_dir(
+            "root",
+            files(13..15),
+            vec![
+                template_dir(
+                    "backups",
+                    vec![],
+                    vec![
+                        template_dir("daily", files(0..5), vec![]),
+                        template_dir("weekly", files(5..9), vec![]),
+                    ],
+                ),
+                template_dir("database", files(9..13), vec![]),
+            ],
+        ),
+    };
 
-    let sub_node_1 = Box::new(FtpNode {
-        decoration: String::from("pictures"),
-        dirs: HashMap::new(),
-        files: ftp_files[0..1].to_owned(),
-        size: 0,
-        timestamp: get_randomized_timestamp(),
-    });
+    FtpFileSystem {
+        root: Box::new(root_node),
+        path: Vec::new(),
+        public: false,
+    }
+}
 
-    let sub_sub_node_1 = Box::new(FtpNode {
-        decoration: String::from("invoices"),
-        dirs: HashMap::new(),
-        files: ftp_files[2..4].to_owned(),
-        size: 0,
-        timestamp: get_randomized_timestamp(),
-    });
-    let sub_sub_node_2 = Box::new(FtpNode {
-        decoration: String::from("private"),
-        dirs: HashMap::new(),
-        files: ftp_files[5..9].to_owned(),
-        size: 0,
-        timestamp: get_randomized_timestamp(),
-    });
-    let mut node_1_map = HashMap::new();
-    node_1_map.insert(String::from("invoices"), sub_sub_node_1);
-    node_1_map.insert(String::from("private"), sub_sub_node_2);
+/// Creates the filesystem of anonymous users with a single pub directory of default files
+fn new_pub_fs() -> FtpFileSystem {
+    let files = generate_files(String::from("pub"), 5);
+    let ftp_files = parse_to_file_vec(files);
 
-    let sub_node_2 = Box::new(FtpNode {
-        decoration: String::from("documents"),
-        dirs: node_1_map,
-        files: ftp_files[10..13].to_owned(),
-        size: 0,
+    let pub_node = Box::new(FtpNode {
+        decoration: String::from("pub"),
+        dirs: HashMap::new(),
+        size: ftp_files.iter().map(|file| file.size).sum(),
+        files: ftp_files,
         timestamp: get_randomized_timestamp(),
+        permissions: Some(0o755),
     });
     let mut root_map = HashMap::new();
-    root_map.insert(String::from("pictures"), sub_node_1);
-    root_map.insert(String::from("documents"), sub_node_2);
+    root_map.insert(String::from("pub"), pub_node);
 
     let root_node: Box<FtpNode> = Box::new(FtpNode {
         decoration: String::from("root"),
         dirs: root_map,
-        files: ftp_files[13..14].to_owned(),
+        files: vec![],
         size: 0,
         timestamp: get_randomized_timestamp(),
+        permissions: Some(0o755),
     });
     FtpFileSystem {
         root: root_node,
         path: Vec::new(),
+        public: true,
+    }
+}
+
+/// Loads the filesystem shared by all anonymous users, stored in its own
+/// [table][public_file_systems].
+///
+/// * `db` - The [DatabaseImplementation]
+pub async fn load_public_fs(db: &DatabaseImplementation) {
+    let stored = db.get_public_file_system(PUBLIC_FS_ID).await;
+
+    if let Some(mut file_system) = stored.map(|stored| stored.file_system) {
+        file_system.public = true;
+        *PUBLIC_FS.lock().expect("Public filesystem poisoned!") = Some(file_system);
+    }
+}
+
+/// Returns a copy of the filesystem shared by all anonymous users.
+///
+/// Changes made through the copy are applied to the shared filesystem, see
+/// [apply][FtpFileSystem::apply].
+pub fn get_public_fs() -> FtpFileSystem {
+    let mut public_fs = PUBLIC_FS.lock().expect("Public filesystem poisoned!");
+    let mut file_system = public_fs.get_or_insert_with(new_pub_fs).clone();
+
+    file_system.path = Vec::new();
+    file_system
+}
+
+/// Drops the links to [uploaded files][uploaded_files] of all files of a copied directory.
+///
+/// * `node` - The copied [FtpNode].
+fn forget_uploads(node: &mut FtpNode) {
+    for file in node.files.iter_mut() {
+        file.file_id = None;
+    }
+
+    for dir in node.dirs.values_mut() {
+        forget_uploads(dir);
     }
 }
 
@@ -197,7 +330,7 @@ impl FtpFileSystem {
                             return (can_be_downloaded, file.default_file.to_owned());
                         }
 
-                        if !get_config().can_be_downloaded {
+                        if !get_config().can_be_downloaded || file.file_id.is_none() {
                             return (
                                 can_be_downloaded,
                                 Some(create_file(file.size as usize, file.name.clone())),
@@ -214,7 +347,51 @@ impl FtpFileSystem {
             _ => (can_be_downloaded, None),
         }
     }
+    /// Applies a change to the filesystem and returns its result.
+    ///
+    /// Changes of the [public filesystem][get_public_fs] are applied to the shared filesystem
+    /// under its lock, so concurrent sessions don't undo each other's changes. The session then
+    /// sees the shared filesystem with its own path.
+    ///
+    /// * `change` - The change, called with the filesystem to change.
+    fn apply<R>(&mut self, change: impl FnOnce(&mut FtpFileSystem) -> R) -> R {
+        if !self.public {
+            return change(self);
+        }
+
+        let mut public_fs = PUBLIC_FS.lock().expect("Public filesystem poisoned!");
+        let shared = public_fs.get_or_insert_with(new_pub_fs);
+        shared.path = self.path.clone();
+
+        let result = change(shared);
+        self.root = shared.root.clone();
+        result
+    }
+
+    /// Persists the filesystem with the attacker.
+    ///
+    /// The [public filesystem][get_public_fs] stores the shared filesystem in its own
+    /// [table][public_file_systems] instead.
+    ///
+    /// * `db`          - The [DatabaseImplementation]
+    /// * `attacker_id` - The id of the attacker that owns the filesystem.
     pub async fn update_fs(&mut self, db: &DatabaseImplementation, attacker_id: i64) {
+        if self.public {
+            let _store = PUBLIC_FS_STORE.lock().await;
+            let file_system = PUBLIC_FS
+                .lock()
+                .expect("Public filesystem poisoned!")
+                .get_or_insert_with(new_pub_fs)
+                .clone();
+
+            db.update_public_file_system(public_file_systems::ActiveModel {
+                id: Set(PUBLIC_FS_ID),
+                file_system: Set(file_system),
+            })
+            .await;
+            return;
+        }
+
         Update::one(attacker::ActiveModel {
             id: Set(attacker_id),
             file_system: Set(Some(self.clone())),
@@ -225,12 +402,30 @@ impl FtpFileSystem {
         .expect("Could not update filesystem!");
     }
 
+    /// Creates a directory and persists the filesystem.
+    ///
+    /// Returns the change for the transcript, [None] if the parent doesn't exist or the directory
+    /// exists already.
+    ///
+    /// * `db`                 - The [DatabaseImplementation]
+    /// * `attacker_id`        - The id of the attacker that owns the filesystem.
+    /// * `file_name_and_path` - The path of the new directory.
     pub async fn save_dir(
         &mut self,
         db: &DatabaseImplementation,
         attacker_id: i64,
         file_name_and_path: &str,
-    ) -> bool {
+    ) -> Option<String> {
+        if !self.apply(|file_system| file_system.create_dir_entry(file_name_and_path)) {
+            return None;
+        }
+
+        let change = self.describe_change("mkdir", file_name_and_path);
+        self.update_fs(db, attacker_id).await;
+        Some(change)
+    }
+
+    fn create_dir_entry(&mut self, file_name_and_path: &str) -> bool {
         let mut split_string = file_name_and_path
             .split_terminator('/')
             .map(str::to_string)
@@ -245,22 +440,38 @@ impl FtpFileSystem {
                 if node.dirs.contains_key(&dir_to_add) {
                     false
                 } else {
-                    node.dirs.insert(
-                        String::from(dir_to_add.clone()),
-                        Box::new(generate_dir(&dir_to_add, 0)),
-                    );
-                    self.update_fs(db, attacker_id).await;
+                    node.dirs
+                        .insert(dir_to_add.clone(), Box::new(generate_dir(&dir_to_add, 0)));
                     true
                 }
             }
         }
     }
+
+    /// Removes an empty directory and persists the filesystem.
+    ///
+    /// Returns the change for the transcript, [None] if there is no such directory or it isn't
+    /// empty.
+    ///
+    /// * `db`                 - The [DatabaseImplementation]
+    /// * `attacker_id`        - The id of the attacker that owns the filesystem.
+    /// * `file_name_and_path` - The path of the directory.
     pub async fn rm_dir(
         &mut self,
         db: &DatabaseImplementation,
         attacker_id: i64,
         file_name_and_path: &str,
-    ) -> bool {
+    ) -> Option<String> {
+        if !self.apply(|file_system| file_system.remove_dir_entry(file_name_and_path)) {
+            return None;
+        }
+
+        let change = self.describe_change("rmdir", file_name_and_path);
+        self.update_fs(db, attacker_id).await;
+        Some(change)
+    }
+
+    fn remove_dir_entry(&mut self, file_name_and_path: &str) -> bool {
         let mut split_string = file_name_and_path
             .split_terminator('/')
             .map(str::to_string)
@@ -278,7 +489,6 @@ impl FtpFileSystem {
                     && directory.unwrap().dirs.is_empty()
                 {
                     node.dirs.remove(&dir_to_remove);
-                    self.update_fs(db, attacker_id).await;
                     true
                 } else {
                     false
@@ -286,12 +496,30 @@ impl FtpFileSystem {
             }
         }
     }
+
+    /// Removes a file and persists the filesystem.
+    ///
+    /// Returns the change for the transcript, [None] if there is no such file.
+    ///
+    /// * `db`                 - The [DatabaseImplementation]
+    /// * `attacker_id`        - The id of the attacker that owns the filesystem.
+    /// * `file_name_and_path` - The path of the file.
     pub async fn rm_file(
         &mut self,
         db: &DatabaseImplementation,
         attacker_id: i64,
         file_name_and_path: &str,
-    ) -> bool {
+    ) -> Option<String> {
+        if !self.apply(|file_system| file_system.remove_file_entry(file_name_and_path)) {
+            return None;
+        }
+
+        let change = self.describe_change("delete", file_name_and_path);
+        self.update_fs(db, attacker_id).await;
+        Some(change)
+    }
+
+    fn remove_file_entry(&mut self, file_name_and_path: &str) -> bool {
         let mut split_string = file_name_and_path
             .split_terminator('/')
             .map(str::to_string)
@@ -305,9 +533,8 @@ impl FtpFileSystem {
                 let (node, _) = self.root.traverse_path(path.as_slice());
 
                 let index = node.files.iter().position(|f| f.name.eq(&file_name));
-                if index.is_some() {
-                    node.files.remove(index.unwrap());
-                    self.update_fs(db, attacker_id).await;
+                if let Some(index) = index {
+                    node.files.remove(index);
                     true
                 } else {
                     false
@@ -315,6 +542,287 @@ impl FtpFileSystem {
             }
         }
     }
+
+    /// Renames or moves a file or directory and persists the filesystem.
+    ///
+    /// Moved files keep their `file_id`, so the link to the [uploaded file][uploaded_files]
+    /// stays intact. Returns the change for the transcript, [None] if the source doesn't exist,
+    /// the target already exists or a directory would be moved into itself.
+    ///
+    /// * `db`          - The [DatabaseImplementation]
+    /// * `attacker_id` - The id of the attacker that owns the filesystem.
+    /// * `from`        - The path of the file or directory to rename.
+    /// * `to`          - The new path of the file or directory.
+    pub async fn rename(
+        &mut self,
+        db: &DatabaseImplementation,
+        attacker_id: i64,
+        from: &str,
+        to: &str,
+    ) -> Option<String> {
+        if !self.apply(|file_system| file_system.rename_entry(from, to)) {
+            return None;
+        }
+
+        let change = format!("rename {} ->", self.absolute_path(from));
+        let change = self.describe_change(&change, to);
+        self.update_fs(db, attacker_id).await;
+        Some(change)
+    }
+
+    fn rename_entry(&mut self, from: &str, to: &str) -> bool {
+        let (from_path, from_name) = match self.resolve_parent(from) {
+            Some(parent) => parent,
+            None => return false,
+        };
+        let (to_path, to_name) = match self.resolve_parent(to) {
+            Some(parent) => parent,
+            None => return false,
+        };
+
+        let (target, _) = self.root.traverse_path(to_path.as_slice());
+        if target.dirs.contains_key(&to_name) || target.files.iter().any(|f| f.name.eq(&to_name)) {
+            return false;
+        }
+
+        let mut source_path = from_path.clone();
+        source_path.push(from_name.clone());
+
+        let (source, _) = self.root.traverse_path(from_path.as_slice());
+        if source.dirs.contains_key(&from_name) {
+            if to_path.starts_with(&source_path) {
+                return false;
+            }
+
+            let mut dir = source.dirs.remove(&from_name).unwrap();
+            dir.decoration = to_name.clone();
+
+            let (target, _) = self.root.traverse_path(to_path.as_slice());
+            target.dirs.insert(to_name, dir);
+            return true;
+        }
+
+        match source.files.iter().position(|f| f.name.eq(&from_name)) {
+            None => false,
+            Some(index) => {
+                let mut file = source.files.remove(index);
+                source.size -= file.size;
+                file.name = to_name;
+
+                let (target, _) = self.root.traverse_path(to_path.as_slice());
+                target.size += file.size;
+                target.files.push(file);
+                true
+            }
+        }
+    }
+
+    /// Copies a file or directory and persists the filesystem.
+    ///
+    /// Copies don't refer to the [uploaded file][uploaded_files] of the source, they are served
+    /// with generated content of the same size like files without an upload. A file replaces a
+    /// file with the same name at the target. Returns the change for the transcript, [None] if the
+    /// source doesn't exist, a directory is in the way or a directory would be copied into itself.
+    ///
+    /// * `db`          - The [DatabaseImplementation]
+    /// * `attacker_id` - The id of the attacker that owns the filesystem.
+    /// * `from`        - The path of the file or directory to copy.
+    /// * `to`          - The path of the copy.
+    pub async fn copy(
+        &mut self,
+        db: &DatabaseImplementation,
+        attacker_id: i64,
+        from: &str,
+        to: &str,
+    ) -> Option<String> {
+        if !self.apply(|file_system| file_system.copy_entry(from, to)) {
+            return None;
+        }
+
+        let change = format!("copy {} ->", self.absolute_path(from));
+        let change = self.describe_change(&change, to);
+        self.update_fs(db, attacker_id).await;
+        Some(change)
+    }
+
+    fn copy_entry(&mut self, from: &str, to: &str) -> bool {
+        let (from_path, from_name) = match self.resolve_parent(from) {
+            Some(parent) => parent,
+            None => return false,
+        };
+        let (to_path, to_name) = match self.resolve_parent(to) {
+            Some(parent) => parent,
+            None => return false,
+        };
+        let timestamp = Local::now().format("%b %d %H:%M").to_string();
+
+        let (target, _) = self.root.traverse_path(to_path.as_slice());
+        if target.dirs.contains_key(&to_name) {
+            return false;
+        }
+        let target_has_file = target.files.iter().any(|f| f.name.eq(&to_name));
+
+        let mut source_path = from_path.clone();
+        source_path.push(from_name.clone());
+
+        let (source, _) = self.root.traverse_path(from_path.as_slice());
+        if let Some(dir) = source.dirs.get(&from_name) {
+            if target_has_file || to_path.starts_with(&source_path) {
+                return false;
+            }
+
+            let mut dir = dir.clone();
+            dir.decoration = to_name.clone();
+            dir.timestamp = timestamp;
+            forget_uploads(&mut dir);
+
+            let (target, _) = self.root.traverse_path(to_path.as_slice());
+            target.dirs.insert(to_name, dir);
+            return true;
+        }
+
+        match source.files.iter().find(|f| f.name.eq(&from_name)) {
+            None => false,
+            Some(file) => {
+                let mut file = file.clone();
+                file.name = to_name;
+                file.timestamp = timestamp;
+                file.file_id = None;
+
+                self.put_file(to_path.as_slice(), file);
+                true
+            }
+        }
+    }
+
+    /// Sets the mode of a file or directory and persists the filesystem.
+    ///
+    /// Returns the change for the transcript, [None] if there is no such file or directory.
+    ///
+    /// * `db`                 - The [DatabaseImplementation]
+    /// * `attacker_id`        - The id of the attacker that owns the filesystem.
+    /// * `file_name_and_path` - The path of the file or directory.
+    /// * `mode`               - The new permission bits, like `0o755`.
+    pub async fn chmod(
+        &mut self,
+        db: &DatabaseImplementation,
+        attacker_id: i64,
+        file_name_and_path: &str,
+        mode: u32,
+    ) -> Option<String> {
+        if !self.apply(|file_system| file_system.set_permissions(file_name_and_path, mode)) {
+            return None;
+        }
+
+        let change = self.describe_change(&format!("chmod {:o}", mode), file_name_and_path);
+        self.update_fs(db, attacker_id).await;
+        Some(change)
+    }
+
+    fn set_permissions(&mut self, file_name_and_path: &str, mode: u32) -> bool {
+        let (path, name) = match self.resolve_parent(file_name_and_path) {
+            Some(parent) => parent,
+            None => return false,
+        };
+
+        let (node, _) = self.root.traverse_path(path.as_slice());
+        if let Some(dir) = node.dirs.get_mut(&name) {
+            dir.permissions = Some(mode);
+            return true;
+        }
+
+        match node.files.iter_mut().find(|f| f.name.eq(&name)) {
+            None => false,
+            Some(file) => {
+                file.permissions = Some(mode);
+                true
+            }
+        }
+    }
+
+    /// Returns whether a file or directory exists at the given path.
+    ///
+    /// * `file_name_and_path` - The path of the file or directory.
+    pub fn exists(&mut self, file_name_and_path: &str) -> bool {
+        match self.resolve_parent(file_name_and_path) {
+            None => false,
+            Some((path, name)) => {
+                let (node, _) = self.root.traverse_path(path.as_slice());
+                node.dirs.contains_key(&name) || node.files.iter().any(|f| f.name.eq(&name))
+            }
+        }
+    }
+
+    /// Splits a path into its resolved parent directory and the name of the last entry.
+    ///
+    /// Returns [None] if the parent doesn't exist or the name is empty, `.` or `..`.
+    fn resolve_parent(&mut self, file_name_and_path: &str) -> Option<(Vec<String>, String)> {
+        let mut split_string = file_name_and_path
+            .split_terminator('/')
+            .map(str::to_string)
+            .collect::<Vec<String>>();
+        let name = split_string.pop()?;
+
+        if name.is_empty() || name.eq(".") || name.eq("..") {
+            return None;
+        }
+
+        let path = self.resolve_path_as_vec(split_string)?;
+        Some((path, name))
+    }
+
+    /// Returns the absolute form of the path, or the path as given if its parent doesn't exist.
+    fn absolute_path(&mut self, file_name_and_path: &str) -> String {
+        match self.resolve_parent(file_name_and_path) {
+            Some((mut path, name)) => {
+                path.push(name);
+                format!("/{}", path.join("/"))
+            }
+            None => file_name_and_path.to_string(),
+        }
+    }
+
+    /// Returns the description of a change of the filesystem, which is added to the transcript.
+    ///
+    /// * `change`             - The change, like `mkdir`.
+    /// * `file_name_and_path` - The path of the changed file or directory.
+    fn describe_change(&mut self, change: &str, file_name_and_path: &str) -> String {
+        format!("{} {}", change, self.absolute_path(file_name_and_path))
+    }
+
+    /// Returns the file at the given path.
+    ///
+    /// Returns [None] if there is no such file, which includes directories.
+    pub fn get_file(&mut self, file_name_and_path: &str) -> Option<&FtpFile> {
+        let (path, name) = self.resolve_parent(file_name_and_path)?;
+        let (node, _) = self.root.traverse_path(path.as_slice());
+        node.files.iter().find(|f| f.name.eq(&name))
+    }
+
+    /// Returns the id of the uploaded file at the given path.
+    ///
+    /// Returns [None] if there is no such file or it is one of the default files.
+    pub fn get_uploaded_file_id(&mut self, file_name_and_path: &str) -> Option<i64> {
+        let (path, name) = self.resolve_parent(file_name_and_path)?;
+        let (node, _) = self.root.traverse_path(path.as_slice());
+        let file = node.files.iter().find(|f| f.name.eq(&name))?;
+
+        if file.default_file.is_some() {
+            return None;
+        }
+
+        file.file_id
+    }
+
+    /// Stores an uploaded file and adds it to the filesystem, which is persisted.
+    ///
+    /// Files uploaded to the [public filesystem][get_public_fs] belong to no attacker, as they
+    /// outlive the uploader.
+    ///
+    /// Returns the change for the transcript, [None] if the parent directory doesn't exist.
+    ///
+    /// * `path_to_physical_file` - The path of the file on the system.
+    /// * `file_name_and_path`    - The path of the file in the filesystem.
     pub async fn save_file(
         &mut self,
         db: &DatabaseImplementation,
@@ -323,40 +831,103 @@ impl FtpFileSystem {
         file_name_and_path: &str,
         file_size: i64,
         file_hash: String,
-    ) {
+    ) -> Option<String> {
         let mut split_string = file_name_and_path
             .split_terminator('/')
             .map(str::to_string)
             .collect::<Vec<String>>();
         let file_name = split_string.pop().unwrap();
-        let is_path_valid = self.resolve_path_as_vec(split_string);
-        if let Some(path) = is_path_valid {
-            let mut file = uploaded_files::ActiveModel {
-                filename: Set(file_name.clone()),
-                attacker_id: Set(Some(attacker_id)),
-                size: Set(file_size),
-                hash: Set(Some(file_hash)),
-                ..Default::default()
-            };
+        let path = self.resolve_path_as_vec(split_string)?;
+        let mut file = uploaded_files::ActiveModel {
+            filename: Set(file_name.clone()),
+            attacker_id: Set((!self.public).then_some(attacker_id)),
+            size: Set(file_size),
+            hash: Set(Some(file_hash)),
+            ..Default::default()
+        };
 
-            if get_config().file_upload_real {
-                file.location = Set(Some(path_to_physical_file.to_string()))
-            }
+        if get_config().file_upload_real {
+            file.location = Set(Some(path_to_physical_file.to_string()))
+        }
 
-            let db_file = db.update_file(file).await;
-            let (node, _) = self.root.traverse_path(path.as_slice());
-            let date = Local::now();
-            node.size += file_size;
-            node.files.push(FtpFile {
-                name: file_name,
-                size: file_size,
-                file_id: Some(db_file.id.unwrap()),
-                timestamp: date.format("%b %d %H:%M").to_string(),
-                default_file: None,
-            });
-            self.update_fs(db, attacker_id).await
+        let db_file = db.update_file(file).await;
+        let date = Local::now();
+        let file = FtpFile {
+            name: file_name,
+            size: file_size,
+            file_id: Some(db_file.id.unwrap()),
+            timestamp: date.format("%b %d %H:%M").to_string(),
+            default_file: None,
+            permissions: None,
+        };
+        self.apply(|file_system| file_system.put_file(path.as_slice(), file));
+        let path = self.absolute_path(file_name_and_path);
+        self.update_fs(db, attacker_id).await;
+        Some(format!("upload {} ({} bytes)", path, file_size))
+    }
+
+    /// Stores the new size and hash of an uploaded file, which was appended to or resumed.
+    ///
+    /// Returns the change for the transcript, [None] if the file isn't in the filesystem anymore.
+    ///
+    /// * `uploaded_file`      - The [uploaded_files::Model] of the file.
+    /// * `file_name_and_path` - The path of the file in the filesystem.
+    pub async fn update_uploaded_file(
+        &mut self,
+        db: &DatabaseImplementation,
+        attacker_id: i64,
+        uploaded_file: uploaded_files::Model,
+        file_name_and_path: &str,
+        file_size: i64,
+        file_hash: String,
+    ) -> Option<String> {
+        let mut file: uploaded_files::ActiveModel = uploaded_file.into();
+        file.size = Set(file_size);
+        file.hash = Set(Some(file_hash));
+        db.update_file(file).await;
+
+        if !self.apply(|file_system| file_system.resize_file(file_name_and_path, file_size)) {
+            return None;
         }
+
+        let path = self.absolute_path(file_name_and_path);
+        self.update_fs(db, attacker_id).await;
+        Some(format!("resize {} ({} bytes)", path, file_size))
     }
+
+    /// Adds the file to the node at `path`, replacing a file with the same name.
+    fn put_file(&mut self, path: &[String], file: FtpFile) {
+        let (node, _) = self.root.traverse_path(path);
+
+        if let Some(index) = node.files.iter().position(|f| f.name.eq(&file.name)) {
+            node.size -= node.files.remove(index).size;
+        }
+
+        node.size += file.size;
+        node.files.push(file);
+    }
+
+    /// Sets the size and timestamp of the file at the given path.
+    ///
+    /// Returns false if there is no such file.
+    fn resize_file(&mut self, file_name_and_path: &str, file_size: i64) -> bool {
+        let (path, name) = match self.resolve_parent(file_name_and_path) {
+            Some(parent) => parent,
+            None => return false,
+        };
+
+        let (node, _) = self.root.traverse_path(path.as_slice());
+        match node.files.iter_mut().find(|f| f.name.eq(&name)) {
+            None => false,
+            Some(file) => {
+                node.size += file_size - file.size;
+                file.size = file_size;
+                file.timestamp = Local::now().format("%b %d %H:%M").to_string();
+                true
+            }
+        }
+    }
+
     pub fn traverse_path<'p>(&mut self, path: &'p [String]) -> (&mut FtpNode, &'p [String]) {
         self.root.traverse_path(path)
     }
@@ -371,22 +942,22 @@ impl FtpFileSystem {
 
     pub fn real_cd(&mut self, mut path_to_move: Vec<String>) -> bool {
         let mut tmp_path = self.path.clone();
-        if !path_to_move.is_empty() && path_to_move.get(0).unwrap().eq("") {
+        if !path_to_move.is_empty() && path_to_move[0].is_empty() {
             tmp_path = vec![];
             path_to_move.remove(0);
         }
 
-        if !path_to_move.is_empty() && path_to_move.get(0).unwrap().eq(".") {
+        if !path_to_move.is_empty() && path_to_move[0].eq(".") {
             path_to_move.remove(0);
         }
 
-        return match self.real_cd_rec(path_to_move.as_slice(), &mut tmp_path) {
+        match self.real_cd_rec(path_to_move.as_slice(), &mut tmp_path) {
             None => false,
             Some(new_path) => {
                 self.path = new_path.clone();
                 true
             }
-        };
+        }
     }
 
     fn real_cd_rec<'p>(
@@ -406,7 +977,7 @@ impl FtpFileSystem {
             let new_path = cloned_path.as_slice();
             if self.root.cd(new_path) {
                 tmp_path.push(path_to_move[0].clone());
-                return self.real_cd_rec(&path_to_move[1..], tmp_path);
+                self.real_cd_rec(&path_to_move[1..], tmp_path)
             } else {
                 None
             }
@@ -439,6 +1010,60 @@ impl FtpFileSystem {
         }
     }
 
+    /// Returns the listing of the current directory, or of the given path, in the MS-DOS format
+    /// of the Microsoft FTP Service.
+    pub fn ls_msdos_with_str(&mut self, path_to_move: &str) -> Option<String> {
+        let is_path_valid = self.resolve_path(path_to_move);
+        is_path_valid.map(|path| self.root.ls_path_msdos(&path))
+    }
+
+    pub fn mlsd(&mut self, attacker_id: i64) -> String {
+        self.root.mlsd_path(&self.path, attacker_id)
+    }
+
+    pub fn mlsd_with_str(&mut self, attacker_id: i64, path_to_move: &str) -> Option<String> {
+        let is_path_valid = self.resolve_path(path_to_move);
+        is_path_valid.map(|path| self.root.mlsd_path(&path, attacker_id))
+    }
+
+    /// Returns the RFC 3659 facts of a single file or directory for MLST.
+    ///
+    /// An empty path returns the facts of the current directory.
+    ///
+    /// * `attacker_id`     - The id of the attacker that owns the filesystem.
+    /// * `path_to_move`    - The path of the file or directory.
+    pub fn mlst_with_str(&mut self, attacker_id: i64, path_to_move: &str) -> Option<String> {
+        let name = if path_to_move.is_empty() {
+            format!("/{}", self.path.join("/"))
+        } else {
+            path_to_move.to_string()
+        };
+
+        if let Some(path) = self.resolve_path(path_to_move) {
+            let kind = if path_to_move.is_empty() {
+                "cdir"
+            } else {
+                "dir"
+            };
+            let (node, _) = self.root.traverse_path(path.as_slice());
+            return Some(node.mlsx_facts(kind, &name, attacker_id));
+        }
+
+        let mut path_as_vec = path_to_move
+            .split_terminator('/')
+            .map(str::to_string)
+            .collect::<Vec<String>>();
+        let file_name = path_as_vec.pop()?;
+        let path = self.resolve_path_as_vec(path_as_vec)?;
+
+        let (node, _) = self.root.traverse_path(path.as_slice());
+        let file = node.files.iter().find(|file| file.name.eq(&file_name))?;
+        let facts = file.mlsx_facts(attacker_id);
+        let (facts, _) = facts.rsplit_once(' ')?;
+
+        Some(format!("{} {}", facts, name))
+    }
+
     pub fn ls_path(&mut self, path_to_move: &str) -> Option<String> {
         let is_path_valid = self.resolve_path(path_to_move);
         match is_path_valid {
@@ -457,12 +1082,12 @@ impl FtpFileSystem {
     fn resolve_path_as_vec(&mut self, mut add_path: Vec<String>) -> Option<Vec<String>> {
         let mut tmp_path = self.path.clone();
 
-        if !add_path.is_empty() && add_path.get(0).unwrap().eq("") {
+        if !add_path.is_empty() && add_path[0].is_empty() {
             tmp_path = vec![];
             add_path.remove(0);
         }
 
-        if !add_path.is_empty() && add_path.get(0).unwrap().eq(".") {
+        if !add_path.is_empty() && add_path[0].eq(".") {
             add_path.remove(0);
         }
 
@@ -475,8 +1100,13 @@ impl FtpFileSystem {
 mod tests {
     use std::collections::HashMap;
 
-    use crate::filesystem::ftp_fs::{FtpFileSystem, FtpNode};
+    use std::str::FromStr;
+
+    use crate::filesystem::ftp_fs::{
+        get_public_fs, new_fs_of_attacker, FtpFileSystem, FtpNode, Template,
+    };
     use crate::filesystem::models::ftp_file::FtpFile;
+    use crate::filesystem::models::ftp_node::{mlsx_timestamp, mlsx_unique, msdos_timestamp};
 
     fn ini_tree() -> FtpFileSystem {
         let sub_node_1 = Box::new(FtpNode {
@@ -485,6 +1115,7 @@ mod tests {
             files: vec![],
             size: 0,
             timestamp: String::from("Mar 13 19:59"),
+            permissions: None,
         });
 
         let sub_sub_node_1 = Box::new(FtpNode {
@@ -493,6 +1124,7 @@ mod tests {
             files: vec![],
             size: 0,
             timestamp: String::from("Mar 13 19:59"),
+            permissions: None,
         });
         let sub_sub_node_2 = Box::new(FtpNode {
             decoration: String::from("sub_node_2"),
@@ -500,6 +1132,7 @@ mod tests {
             files: vec![],
             size: 0,
             timestamp: String::from("Mar 13 19:59"),
+            permissions: None,
         });
         let mut node_1_map = HashMap::new();
         node_1_map.insert(String::from("sub_node_1"), sub_sub_node_1);
@@ -511,6 +1144,7 @@ mod tests {
             files: vec![],
             size: 0,
             timestamp: String::from("Mar 13 19:59"),
+            permissions: None,
         });
         let mut root_map = HashMap::new();
         root_map.insert(String::from("node1"), sub_node_1);
@@ -522,13 +1156,38 @@ mod tests {
             files: vec![],
             size: 0,
             timestamp: String::from("Mar 13 19:59"),
+            permissions: None,
         });
         FtpFileSystem {
             root: root_node,
             path: Vec::new(),
+            public: false,
         }
     }
 
+    #[test]
+    fn attacker_fs_templates() {
+        assert_eq!(
+            Template::from_str("WebServer").unwrap(),
+            Template::Webserver
+        );
+        assert!(Template::from_str("unknown").is_err());
+
+        let mut fs = new_fs_of_attacker(0, Template::Home);
+        assert!(fs.cd_as_str("/documents/invoices"));
+
+        let mut fs = new_fs_of_attacker(0, Template::Webserver);
+        assert!(fs.cd_as_str("/www/html"));
+        assert_eq!(fs.root.dirs["www"].dirs["html"].files.len(), 5);
+        assert!(fs.cd_as_str("/backups"));
+        assert!(!fs.cd_as_str("/documents"));
+
+        let mut fs = new_fs_of_attacker(0, Template::Backup);
+        assert!(fs.cd_as_str("/backups/weekly"));
+        assert!(fs.cd_as_str("/database"));
+        assert_eq!(fs.root.files.len(), 2);
+    }
+
     #[test]
     fn cd_to_child() {
         let mut tree = ini_tree();
@@ -635,6 +1294,7 @@ mod tests {
             file_id: None,
             timestamp: String::from("Mar 13 19:59"),
             default_file: None,
+            permissions: None,
         };
         let file2 = FtpFile {
             name: "ztesting.yaml".to_string(),
@@ -642,6 +1302,7 @@ mod tests {
             file_id: None,
             timestamp: String::from("Mar 13 19:59"),
             default_file: None,
+            permissions: None,
         };
 
         tree.root.files.push(file1);
@@ -693,6 +1354,7 @@ mod tests {
             file_id: None,
             timestamp: String::from("Mar 13 19:59"),
             default_file: None,
+            permissions: None,
         };
         let file2 = FtpFile {
             name: "ztesting.yaml".to_string(),
@@ -700,6 +1362,7 @@ mod tests {
             file_id: None,
             timestamp: String::from("Mar 13 19:59"),
             default_file: None,
+            permissions: None,
         };
 
         tree.root.files.push(file1);
@@ -733,4 +1396,319 @@ mod tests {
             "drwxr-sr-x	1 1001	1001		0 Mar 13 19:59 sub_node_1\r\ndrwxr-sr-x	1 1001	1001		0 Mar 13 19:59 sub_node_2";
         assert_eq!(cmp, res.unwrap());
     }
+
+    #[test]
+    fn ls_path_to_sub_node() {
+        let mut tree = ini_tree();
+        let res = tree.ls_path("/node2");
+        assert_eq!("sub_node_1\r\nsub_node_2", res.unwrap());
+        assert!(tree.ls_path("invalid_dir").is_none());
+    }
+
+    #[test]
+    fn mlsd_with_files() {
+        let mut tree = ini_tree();
+        tree.root.files.push(FtpFile {
+            name: "atestfile.txt".to_string(),
+            size: 42,
+            file_id: None,
+            timestamp: String::from("Mar 13 19:59"),
+            default_file: None,
+            permissions: None,
+        });
+
+        let res = tree.mlsd(1);
+        let lines: Vec<&str> = res.split("\r\n").collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("type=dir;size=0;modify="));
+        assert!(lines[0].ends_with("; node1"));
+        assert!(lines[2].starts_with("type=file;size=42;modify="));
+        assert!(lines[2].contains("0313195900;perm=adfrw;unique=fd01U"));
+        assert!(lines[2].ends_with("; atestfile.txt"));
+    }
+
+    #[test]
+    fn mlsd_to_sub_node() {
+        let mut tree = ini_tree();
+        let res = tree.mlsd_with_str(1, "node2").unwrap();
+
+        assert!(res.contains("; sub_node_1\r\ntype=dir;"));
+        assert!(tree.mlsd_with_str(1, "invalid_dir").is_none());
+    }
+
+    #[test]
+    fn mlst_file_and_dir() {
+        let mut tree = ini_tree();
+        tree.cd_as_str("node1");
+        tree.traverse_path(&["node1".to_string()])
+            .0
+            .files
+            .push(FtpFile {
+                name: "shell.php".to_string(),
+                size: 7,
+                file_id: Some(3),
+                timestamp: String::from("Mar 13 19:59"),
+                default_file: None,
+                permissions: None,
+            });
+
+        let cdir = tree.mlst_with_str(1, "").unwrap();
+        assert!(cdir.starts_with("type=cdir;"));
+        assert!(cdir.ends_with("; /node1"));
+
+        let dir = tree.mlst_with_str(1, "/node2").unwrap();
+        assert!(dir.starts_with("type=dir;"));
+        assert!(dir.ends_with("; /node2"));
+
+        let file = tree.mlst_with_str(1, "/node1/shell.php").unwrap();
+        assert!(file.starts_with("type=file;size=7;"));
+        assert!(file.ends_with("; /node1/shell.php"));
+
+        assert!(tree.mlst_with_str(1, "missing.php").is_none());
+    }
+
+    #[test]
+    fn mlsx_timestamp_and_unique() {
+        let timestamp = mlsx_timestamp("Mar 13 19:59");
+        assert_eq!(timestamp.len(), 14);
+        assert!(timestamp.ends_with("0313195900"));
+        assert_eq!(mlsx_timestamp("invalid").len(), 14);
+
+        assert_eq!(
+            mlsx_unique(1, "node1", "Mar 13 19:59"),
+            mlsx_unique(1, "node1", "Mar 13 19:59")
+        );
+        assert_ne!(
+            mlsx_unique(1, "node1", "Mar 13 19:59"),
+            mlsx_unique(2, "node1", "Mar 13 19:59")
+        );
+    }
+
+    #[test]
+    fn rename_file_keeps_file_id() {
+        let mut tree = ini_tree();
+        tree.root.size = 7;
+        tree.root.files.push(FtpFile {
+            name: "x.tmp".to_string(),
+            size: 7,
+            file_id: Some(3),
+            timestamp: String::from("Mar 13 19:59"),
+            default_file: None,
+            permissions: None,
+        });
+
+        assert!(tree.rename_entry("x.tmp", "node2/sub_node_1/x.php"));
+
+        let (node, _) = tree.traverse_path(&["node2".to_string(), "sub_node_1".to_string()]);
+        assert_eq!(node.files[0].name, "x.php");
+        assert_eq!(node.files[0].file_id, Some(3));
+        assert_eq!(node.size, 7);
+        assert!(tree.root.files.is_empty());
+        assert_eq!(tree.root.size, 0);
+    }
+
+    #[test]
+    fn put_file_replaces_same_name() {
+        let mut tree = ini_tree();
+        let file = |size, file_id| FtpFile {
+            name: "x.sh".to_string(),
+            size,
+            file_id: Some(file_id),
+            timestamp: String::from("Mar 13 19:59"),
+            default_file: None,
+            permissions: None,
+        };
+
+        tree.put_file(&[], file(7, 1));
+        tree.put_file(&[], file(3, 2));
+
+        assert_eq!(tree.root.files.len(), 1);
+        assert_eq!(tree.root.files[0].file_id, Some(2));
+        assert_eq!(tree.root.size, 3);
+        assert_eq!(tree.get_uploaded_file_id("/x.sh"), Some(2));
+    }
+
+    #[test]
+    fn resize_uploaded_file() {
+        let mut tree = ini_tree();
+        tree.root.size = 7;
+        tree.root.files.push(FtpFile {
+            name: "x.sh".to_string(),
+            size: 7,
+            file_id: Some(3),
+            timestamp: String::from("Mar 13 19:59"),
+            default_file: None,
+            permissions: None,
+        });
+
+        assert!(tree.resize_file("x.sh", 12));
+        assert!(!tree.resize_file("y.sh", 12));
+        assert_eq!(tree.root.files[0].size, 12);
+        assert_eq!(tree.root.size, 12);
+        assert_ne!(tree.root.files[0].timestamp, "Mar 13 19:59");
+    }
+
+    #[test]
+    fn get_file_in_sub_node() {
+        let mut tree = ini_tree();
+        let (node, _) = tree.traverse_path(&["node2".to_string(), "sub_node_1".to_string()]);
+        node.files.push(FtpFile {
+            name: "x.sh".to_string(),
+            size: 7,
+            file_id: Some(3),
+            timestamp: String::from("Mar 13 19:59"),
+            default_file: None,
+            permissions: None,
+        });
+
+        assert_eq!(tree.get_file("/node2/sub_node_1/x.sh").unwrap().size, 7);
+        assert!(tree.get_file("node2/sub_node_1").is_none());
+        assert!(tree.get_file("node2/x.sh").is_none());
+    }
+
+    #[test]
+    fn ls_msdos_with_files() {
+        let mut tree = ini_tree();
+        tree.root.files.push(FtpFile {
+            name: "web.config".to_string(),
+            size: 689,
+            file_id: None,
+            timestamp: String::from("Mar 13 19:59"),
+            default_file: None,
+            permissions: None,
+        });
+
+        let listing = tree.ls_msdos_with_str("").unwrap();
+        let lines = listing.split("\r\n").collect::<Vec<&str>>();
+        let date = msdos_timestamp("Mar 13 19:59");
+
+        assert!(date.starts_with("03-13-"));
+        assert!(date.ends_with("07:59PM"));
+        assert_eq!(lines[0], format!("{}       <DIR>          node1", date));
+        assert_eq!(
+            lines[2],
+            format!("{}                  689 web.config", date)
+        );
+        assert!(tree.ls_msdos_with_str("node3").is_none());
+    }
+
+    #[test]
+    fn default_file_is_not_uploaded() {
+        let mut tree = ini_tree();
+        tree.root.files.push(FtpFile {
+            name: "readme.txt".to_string(),
+            size: 7,
+            file_id: None,
+            timestamp: String::from("Mar 13 19:59"),
+            default_file: Some("default/readme.txt".to_string()),
+            permissions: None,
+        });
+
+        assert_eq!(tree.get_uploaded_file_id("readme.txt"), None);
+        assert_eq!(tree.get_uploaded_file_id("missing.txt"), None);
+    }
+
+    #[test]
+    fn rename_dir() {
+        let mut tree = ini_tree();
+
+        assert!(tree.rename_entry("/node2", "node3"));
+        assert!(tree.exists("node3/sub_node_1"));
+        assert!(!tree.exists("node2"));
+        assert_eq!(tree.root.dirs["node3"].decoration, "node3");
+    }
+
+    #[test]
+    fn rename_invalid() {
+        let mut tree = ini_tree();
+
+        assert!(!tree.rename_entry("missing", "node3"));
+        assert!(!tree.rename_entry("node1", "node2"));
+        assert!(!tree.rename_entry("node2", "node2/sub_node_1/node2"));
+        assert!(!tree.rename_entry("node1", "missing/node1"));
+        assert!(!tree.rename_entry("node1", ".."));
+        assert!(tree.exists("node2/sub_node_1"));
+    }
+
+    #[test]
+    fn copy_file_drops_file_id() {
+        let mut tree = ini_tree();
+        tree.root.size = 7;
+        tree.root.files.push(FtpFile {
+            name: "passwd".to_string(),
+            size: 7,
+            file_id: Some(3),
+            timestamp: String::from("Mar 13 19:59"),
+            default_file: None,
+            permissions: None,
+        });
+
+        assert!(tree.copy_entry("/passwd", "node2/sub_node_1/x.php"));
+
+        let (node, _) = tree.traverse_path(&["node2".to_string(), "sub_node_1".to_string()]);
+        assert_eq!(node.files[0].name, "x.php");
+        assert_eq!(node.files[0].file_id, None);
+        assert_eq!(node.size, 7);
+        assert_eq!(tree.root.files.len(), 1);
+        assert_eq!(tree.root.files[0].file_id, Some(3));
+        assert_eq!(tree.root.size, 7);
+    }
+
+    #[test]
+    fn public_fs_changes_are_shared() {
+        let mut first = get_public_fs();
+        let mut second = get_public_fs();
+
+        assert!(first.apply(|file_system| file_system.copy_entry("pub", "first")));
+        assert!(second.apply(|file_system| file_system.copy_entry("pub", "second")));
+        assert!(second.exists("first"));
+
+        let mut shared = get_public_fs();
+        assert!(shared.exists("first"));
+        assert!(shared.exists("second"));
+    }
+
+    #[test]
+    fn copy_dir() {
+        let mut tree = ini_tree();
+
+        assert!(tree.copy_entry("node2", "node1/node3"));
+        assert!(tree.exists("node1/node3/sub_node_1"));
+        assert!(tree.exists("node2/sub_node_1"));
+        assert_eq!(tree.root.dirs["node1"].dirs["node3"].decoration, "node3");
+    }
+
+    #[test]
+    fn copy_invalid() {
+        let mut tree = ini_tree();
+
+        assert!(!tree.copy_entry("missing", "node3"));
+        assert!(!tree.copy_entry("node1", "node2"));
+        assert!(!tree.copy_entry("node2", "node2/sub_node_1/node2"));
+        assert!(!tree.copy_entry("node1", "missing/node1"));
+        assert!(!tree.exists("node3"));
+    }
+
+    #[test]
+    fn chmod_changes_listing() {
+        let mut tree = ini_tree();
+        tree.root.files.push(FtpFile {
+            name: "x.sh".to_string(),
+            size: 7,
+            file_id: None,
+            timestamp: String::from("Mar 13 19:59"),
+            default_file: None,
+            permissions: None,
+        });
+
+        assert!(tree.set_permissions("x.sh", 0o4755));
+        assert!(tree.set_permissions("/node1", 0o1777));
+        assert!(!tree.set_permissions("missing", 0o777));
+
+        let listing = tree.ls_extended_information(1);
+        assert!(listing.starts_with("drwxrwxrwt\t1 1001"));
+        assert!(listing.contains("\r\ndrwxr-sr-x\t1 1001"));
+        assert!(listing.contains("\r\n-rwsr-xr-x\t1 1001"));
+    }
 }
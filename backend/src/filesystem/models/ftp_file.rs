@@ -2,6 +2,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::filesystem::models::ftp_node::{mlsx_timestamp, mlsx_unique};
+
 #[derive(
     Serialize,
     Deserialize,
@@ -21,4 +23,22 @@ pub struct FtpFile {
     pub file_id: Option<i64>,
     pub timestamp: String,
     pub default_file: Option<String>,
+    /// The mode set with SITE CHMOD, [None] for the default `644`.
+    #[serde(default)]
+    pub permissions: Option<u32>,
+}
+
+impl FtpFile {
+    /// Returns the RFC 3659 facts of the file, followed by its name.
+    ///
+    /// * `attacker_id` - The id of the attacker that owns the filesystem.
+    pub fn mlsx_facts(&self, attacker_id: i64) -> String {
+        format!(
+            "type=file;size={};modify={};perm=adfrw;unique={}; {}",
+            self.size,
+            mlsx_timestamp(&self.timestamp),
+            mlsx_unique(attacker_id, &self.name, &self.timestamp),
+            self.name
+        )
+    }
 }
@@ -1,8 +1,10 @@
 //! Represents a directory of the fake filesystem.
 
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 
-use chrono::Local;
+use chrono::{Datelike, Local, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 
 use crate::filesystem::models::ftp_file::FtpFile;
@@ -14,8 +16,17 @@ pub struct FtpNode {
     pub files: Vec<FtpFile>,
     pub dirs: HashMap<String, Box<FtpNode>>,
     pub timestamp: String,
+    /// The mode set with SITE CHMOD, [None] for the default `2755`.
+    #[serde(default)]
+    pub permissions: Option<u32>,
 }
 
+/// The mode of directories without permissions set with SITE CHMOD.
+pub const DEFAULT_DIR_MODE: u32 = 0o2755;
+
+/// The mode of files without permissions set with SITE CHMOD.
+pub const DEFAULT_FILE_MODE: u32 = 0o644;
+
 /// Generates a new [Directory][crate:: FtpNode] with the current timestamp.
 ///
 /// # Functions that will be executed:
@@ -32,9 +43,88 @@ pub fn generate_dir(dir_name: &str, size: i64) -> FtpNode {
         files: vec![],
         dirs: Default::default(),
         timestamp: date.format("%b %d %H:%M").to_string(),
+        permissions: None,
     }
 }
 
+/// Parses a listing timestamp like `Mar 13 19:59`.
+///
+/// The listing timestamps have no year, the last year in which the date isn't in the future is
+/// used. Invalid timestamps are replaced with the current time.
+///
+/// * `timestamp` - The timestamp of a [FtpNode] or [FtpFile].
+fn parse_timestamp(timestamp: &str) -> NaiveDateTime {
+    let now = Local::now().naive_local();
+
+    let parse = |year: i32| {
+        NaiveDateTime::parse_from_str(&format!("{} {}", year, timestamp), "%Y %b %d %H:%M").ok()
+    };
+
+    match parse(now.year()) {
+        Some(date) if date > now => parse(now.year() - 1).unwrap_or(date),
+        Some(date) => date,
+        None => now,
+    }
+}
+
+/// Converts a mode like `0o2755` to the `ls -l` format `drwxr-sr-x`.
+///
+/// * `mode`      - The permission bits, including setuid, setgid and sticky bit.
+/// * `directory` - Whether the entry is a directory.
+pub fn permission_string(mode: u32, directory: bool) -> String {
+    let mut result = String::from(if directory { "d" } else { "-" });
+
+    for (shift, special, set, unset) in [
+        (6, 0o4000, 's', 'S'),
+        (3, 0o2000, 's', 'S'),
+        (0, 0o1000, 't', 'T'),
+    ] {
+        let bits = (mode >> shift) & 0o7;
+        result.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        result.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        result.push(match (bits & 0o1 != 0, mode & special != 0) {
+            (true, true) => set,
+            (false, true) => unset,
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+
+    result
+}
+
+/// Converts a listing timestamp like `Mar 13 19:59` to the RFC 3659 format `20230313195900`.
+///
+/// * `timestamp` - The timestamp of a [FtpNode] or [FtpFile].
+pub fn mlsx_timestamp(timestamp: &str) -> String {
+    parse_timestamp(timestamp)
+        .format("%Y%m%d%H%M%S")
+        .to_string()
+}
+
+/// Converts a listing timestamp like `Mar 13 19:59` to the MS-DOS format `03-13-23  07:59PM`.
+///
+/// * `timestamp` - The timestamp of a [FtpNode] or [FtpFile].
+pub fn msdos_timestamp(timestamp: &str) -> String {
+    parse_timestamp(timestamp)
+        .format("%m-%d-%y  %I:%M%p")
+        .to_string()
+}
+
+/// Returns a unique fact in the `<device>U<inode>` form of real FTP servers.
+///
+/// The fact is stable for the same entry, so crawlers can detect already visited entries.
+///
+/// * `attacker_id` - The id of the attacker that owns the filesystem.
+/// * `name`        - The name of the entry.
+/// * `timestamp`   - The timestamp of the entry.
+pub fn mlsx_unique(attacker_id: i64, name: &str, timestamp: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    (attacker_id, name, timestamp).hash(&mut hasher);
+
+    format!("fd01U{:x}", hasher.finish() as u32)
+}
+
 impl FtpNode {
     pub fn cd(&mut self, path: &[String]) -> bool {
         if path.is_empty() {
@@ -79,8 +169,9 @@ impl FtpNode {
 
         for child_node in child_node_values {
             result = format!(
-                "{}\r\ndrwxr-sr-x\t1 {}\t{}\t\t{} {} {}",
+                "{}\r\n{}\t1 {}\t{}\t\t{} {} {}",
                 result,
+                permission_string(child_node.permissions.unwrap_or(DEFAULT_DIR_MODE), true),
                 ftp_user_id,
                 ftp_user_id,
                 child_node.size,
@@ -93,8 +184,14 @@ impl FtpNode {
 
         for file in self.files.iter() {
             result = format!(
-                "{}\r\n-rw-r--r--\t1 {}\t{}\t\t{} {} {}",
-                result, ftp_user_id, ftp_user_id, file.size, file.timestamp, file.name
+                "{}\r\n{}\t1 {}\t{}\t\t{} {} {}",
+                result,
+                permission_string(file.permissions.unwrap_or(DEFAULT_FILE_MODE), false),
+                ftp_user_id,
+                ftp_user_id,
+                file.size,
+                file.timestamp,
+                file.name
             );
         }
 
@@ -106,6 +203,79 @@ impl FtpNode {
         result
     }
 
+    fn ls_msdos(&mut self) -> String {
+        let mut child_node_values: Vec<_> = self.dirs.values().collect();
+        child_node_values.sort_by(|a, b| a.decoration.cmp(&b.decoration));
+
+        let mut result: Vec<String> = child_node_values
+            .iter()
+            .map(|child_node| {
+                format!(
+                    "{}       <DIR>          {}",
+                    msdos_timestamp(&child_node.timestamp),
+                    child_node.decoration
+                )
+            })
+            .collect();
+
+        self.files.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for file in self.files.iter() {
+            result.push(format!(
+                "{}{:>21} {}",
+                msdos_timestamp(&file.timestamp),
+                file.size,
+                file.name
+            ));
+        }
+
+        result.join("\r\n")
+    }
+
+    /// Returns the RFC 3659 facts of the directory, followed by its name.
+    ///
+    /// * `kind`        - The type fact, `dir` or `cdir` for the listed directory itself.
+    /// * `name`        - The name to list the directory with.
+    /// * `attacker_id` - The id of the attacker that owns the filesystem.
+    pub fn mlsx_facts(&self, kind: &str, name: &str, attacker_id: i64) -> String {
+        format!(
+            "type={};size={};modify={};perm=flcdmpe;unique={}; {}",
+            kind,
+            self.size,
+            mlsx_timestamp(&self.timestamp),
+            mlsx_unique(attacker_id, &self.decoration, &self.timestamp),
+            name
+        )
+    }
+
+    fn mlsd(&mut self, attacker_id: i64) -> String {
+        let mut child_node_values: Vec<_> = self.dirs.values().collect();
+        child_node_values.sort_by(|a, b| a.decoration.cmp(&b.decoration));
+
+        let mut result: Vec<String> = child_node_values
+            .iter()
+            .map(|child_node| child_node.mlsx_facts("dir", &child_node.decoration, attacker_id))
+            .collect();
+
+        self.files.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for file in self.files.iter() {
+            result.push(file.mlsx_facts(attacker_id));
+        }
+
+        result.join("\r\n")
+    }
+
+    pub fn mlsd_path(&mut self, path: &[String], attacker_id: i64) -> String {
+        let (node, _) = self.traverse_path(path);
+        node.mlsd(attacker_id)
+    }
+
+    pub fn ls_path_msdos(&mut self, path: &[String]) -> String {
+        let (node, _) = self.traverse_path(path);
+        node.ls_msdos()
+    }
+
     pub fn ls_path(&mut self, path: &[String]) -> String {
         let (node, _) = self.traverse_path(path);
         node.ls()
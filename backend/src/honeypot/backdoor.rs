@@ -0,0 +1,334 @@
+//! Emulation of the vsftpd 2.3.4 backdoor (CVE-2011-2523)
+//!
+//! A USER ending in `:)` makes the backdoored server open a root shell on port 6200. The shell is
+//! faked here, every command is recorded as event and common recon commands are answered with
+//! the output of a Metasploitable 2 host. A single shell listener accepts the IPs that triggered
+//! the backdoor shortly before.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::{error, info};
+use serde_json::json;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::timeout;
+
+use crate::configuration::config::get_config;
+use crate::database::models::events::EventKind;
+use crate::database::service::DatabaseImplementation;
+use crate::database::service_trait::DatabaseTrait;
+use crate::honeypot::client::Client;
+use crate::honeypot::ftp_event::save_event_for_ip;
+use crate::honeypot::proxy;
+use crate::honeypot::proxy::Addresses;
+
+/// The hostname of the emulated server.
+const HOSTNAME: &str = "metasploitable";
+
+/// The directories listed by `ls /`.
+const ROOT_DIRECTORIES: &str = "bin\nboot\ncdrom\ndev\netc\nhome\ninitrd\ninitrd.img\nlib\n\
+lost+found\nmedia\nmnt\nnohup.out\nopt\nproc\nroot\nsbin\nsrv\nsys\ntmp\nusr\nvar\nvmlinuz\n";
+
+/// The content of `/etc/passwd`.
+const PASSWD: &str = "root:x:0:0:root:/root:/bin/bash\n\
+daemon:x:1:1:daemon:/usr/sbin:/bin/sh\n\
+bin:x:2:2:bin:/bin:/bin/sh\n\
+sys:x:3:3:sys:/dev:/bin/sh\n\
+sync:x:4:65534:sync:/bin:/bin/sync\n\
+games:x:5:60:games:/usr/games:/bin/sh\n\
+man:x:6:12:man:/var/cache/man:/bin/sh\n\
+lp:x:7:7:lp:/var/spool/lpd:/bin/sh\n\
+mail:x:8:8:mail:/var/mail:/bin/sh\n\
+news:x:9:9:news:/var/spool/news:/bin/sh\n\
+www-data:x:33:33:www-data:/var/www:/bin/sh\n\
+nobody:x:65534:65534:nobody:/nonexistent:/bin/sh\n\
+sshd:x:104:65534::/var/run/sshd:/usr/sbin/nologin\n\
+msfadmin:x:1000:1000:msfadmin,,,:/home/msfadmin:/bin/bash\n\
+postgres:x:108:117:PostgreSQL administrator,,,:/var/lib/postgresql:/bin/bash\n\
+mysql:x:109:118:MySQL Server,,,:/var/lib/mysql:/bin/false\n\
+ftp:x:107:65534::/home/ftp:/bin/false\n";
+
+/// Returns whether the `username` triggers the backdoor.
+///
+/// * `username` - The argument of the USER packet.
+pub fn is_trigger(username: &str) -> bool {
+    username.trim_end().ends_with(":)")
+}
+
+/// Returns the output of a single shell command, or [None] if the shell is exited.
+///
+/// * `command` - The command without arguments separator like `;`.
+fn run_command(command: &str) -> Option<String> {
+    let command = command.trim();
+    let mut words = command.split_whitespace();
+    let program = match words.next() {
+        Some(program) => program,
+        None => return Some(String::new()),
+    };
+    let arguments = words.collect::<Vec<&str>>();
+
+    let output = match (program, arguments.as_slice()) {
+        ("exit", _) | ("logout", _) => return None,
+        ("id", _) => "uid=0(root) gid=0(root)\n".to_string(),
+        ("whoami", _) => "root\n".to_string(),
+        ("hostname", _) => format!("{}\n", HOSTNAME),
+        ("pwd", _) => "/\n".to_string(),
+        ("uname", ["-a"]) => format!(
+            "Linux {} 2.6.24-16-server #1 SMP Thu Apr 10 13:58:00 UTC 2008 i686 GNU/Linux\n",
+            HOSTNAME
+        ),
+        ("uname", ["-r"]) => "2.6.24-16-server\n".to_string(),
+        ("uname", ["-m"]) => "i686\n".to_string(),
+        ("uname", _) => "Linux\n".to_string(),
+        ("ls", arguments) if arguments.iter().all(|a| a.starts_with('-') || *a == "/") => {
+            ROOT_DIRECTORIES.to_string()
+        }
+        ("ls", [path, ..]) => format!("ls: {}: No such file or directory\n", path),
+        ("cat", ["/etc/passwd"]) => PASSWD.to_string(),
+        ("cat", ["/etc/hostname"]) => format!("{}\n", HOSTNAME),
+        ("cat", [path, ..]) => format!("cat: {}: No such file or directory\n", path),
+        ("echo", arguments) => format!("{}\n", arguments.join(" ")),
+        _ => format!("sh: {}: not found\n", program),
+    };
+
+    Some(output)
+}
+
+/// Returns the output of a command line, or [None] if the shell is exited.
+///
+/// Commands separated by `;` are run one after another.
+///
+/// * `line` - The command line sent to the shell.
+fn run_line(line: &str) -> Option<String> {
+    let mut output = String::new();
+
+    for command in line.split(';') {
+        output.push_str(&run_command(command)?);
+    }
+
+    Some(output)
+}
+
+/// A triggered backdoor, waiting for the shell connection of the attacker.
+struct Trigger {
+    /// The IP of the attacker.
+    ip: String,
+    /// The id of the attacker, if known.
+    attacker_id: Option<i64>,
+    /// When the backdoor closes again if the attacker didn't connect.
+    expires: Instant,
+}
+
+/// The triggered backdoors the [shell listeners][listen] accept connections for.
+static TRIGGERS: Mutex<Vec<Trigger>> = Mutex::new(Vec::new());
+
+/// Removes and returns the pending [Trigger] of the `ip`, expired triggers are dropped.
+///
+/// * `ip` - The IP the shell connection comes from.
+fn take_trigger(ip: &str) -> Option<Trigger> {
+    let mut triggers = TRIGGERS.lock().unwrap();
+    let now = Instant::now();
+    triggers.retain(|trigger| trigger.expires > now);
+
+    let index = triggers.iter().position(|trigger| trigger.ip == ip)?;
+    Some(triggers.swap_remove(index))
+}
+
+/// Accepts the connections to the fake root shell on the configured backdoor port.
+///
+/// The listener runs as long as the honeypot. Only IPs that [triggered][open_shell] the backdoor
+/// within the backdoor timeout may connect, other connections are closed right away. The IP is
+/// [resolved][proxy::resolve] like the one of the control connection, so connections forwarded by
+/// a trusted load balancer are matched by the IP of the attacker. Each shell is served in a new
+/// task.
+///
+/// * `listener` - The [TcpListener] bound to the backdoor port.
+/// * `db`       - The [DatabaseImplementation]
+pub async fn listen(listener: TcpListener, db: DatabaseImplementation) {
+    let wait = Duration::from_secs(get_config().backdoor_timeout);
+    let db = Arc::new(db);
+
+    loop {
+        let (mut shell, client) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                error!("Could not accept backdoor connection: {}", e);
+                continue;
+            }
+        };
+
+        let db = Arc::clone(&db);
+        tokio::spawn(async move {
+            let server = match shell.local_addr() {
+                Ok(server) => server,
+                Err(e) => {
+                    error!("Backdoor connection error: {}", e);
+                    return;
+                }
+            };
+            let socket = Addresses { client, server };
+            let addresses = match proxy::resolve(&mut shell, socket, false).await {
+                Ok(addresses) => addresses,
+                Err(e) => {
+                    error!("Invalid PROXY protocol header from {}: {}", client, e);
+                    return;
+                }
+            };
+
+            let trigger = match take_trigger(&addresses.client.ip().to_canonical().to_string()) {
+                Some(trigger) => trigger,
+                None => {
+                    info!("Rejected backdoor connection from: {}", addresses.client);
+                    return;
+                }
+            };
+
+            serve_shell(shell, &trigger.ip, trigger.attacker_id, &db, wait).await;
+        });
+    }
+}
+
+/// Opens the fake root shell after the backdoor was triggered.
+///
+/// Like the backdoored server, the PASS packet isn't answered. Instead the IP of the client is
+/// allowed to connect to the [shell listener][listen] until the timeout is reached. The session
+/// goes on with the next command meanwhile.
+///
+/// * `client`   - The current [Client] struct.
+/// * `db`       - The [DatabaseImplementation]
+pub async fn open_shell(client: &Client, db: &DatabaseImplementation) -> bool {
+    let config = get_config();
+    let attacker_id = match &client.model {
+        Some(model) => Some(model.id),
+        None => db
+            .get_attacker_by_ip(&client.ip)
+            .await
+            .map(|model| model.id),
+    };
+
+    save_event_for_ip(
+        db,
+        &client.ip,
+        attacker_id,
+        EventKind::BackdoorTrigger,
+        json!({"username": client.username, "password": client.password}),
+    )
+    .await;
+
+    let mut triggers = TRIGGERS.lock().unwrap();
+    triggers.retain(|trigger| trigger.ip != client.ip);
+    triggers.push(Trigger {
+        ip: client.ip.to_owned(),
+        attacker_id,
+        expires: Instant::now() + Duration::from_secs(config.backdoor_timeout),
+    });
+
+    info!(
+        "Attacker: {} triggered the backdoor, shell listening on port: {}",
+        client.ip, config.backdoor_port
+    );
+
+    true
+}
+
+/// Answers the command lines of the attacker until the shell is exited or idle for too long.
+///
+/// * `shell`        - The [TcpStream] of the shell.
+/// * `ip`           - The IP of the attacker.
+/// * `attacker_id`  - The id of the attacker, if known.
+/// * `db`           - The [DatabaseImplementation]
+/// * `wait`         - How long to wait for each command line.
+async fn serve_shell(
+    shell: TcpStream,
+    ip: &str,
+    attacker_id: Option<i64>,
+    db: &DatabaseImplementation,
+    wait: Duration,
+) {
+    let (reader, mut writer) = shell.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Ok(Ok(Some(line))) = timeout(wait, lines.next_line()).await {
+        save_event_for_ip(
+            db,
+            ip,
+            attacker_id,
+            EventKind::BackdoorCommand,
+            json!({ "command": line }),
+        )
+        .await;
+
+        let output = match run_line(&line) {
+            Some(output) => output,
+            None => break,
+        };
+
+        if let Err(e) = writer.write_all(output.as_bytes()).await {
+            error!("Backdoor connection error: {}", e);
+            break;
+        }
+    }
+
+    info!("Attacker: {} left the backdoor shell", ip);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::honeypot::backdoor::{is_trigger, run_line, take_trigger, Trigger, TRIGGERS};
+
+    #[test]
+    fn backdoor_trigger() {
+        assert!(is_trigger("root:)"));
+        assert!(is_trigger(":)"));
+        assert!(!is_trigger("root"));
+        assert!(!is_trigger("root:("));
+    }
+
+    #[test]
+    fn recon_commands() {
+        assert_eq!(run_line("id").unwrap(), "uid=0(root) gid=0(root)\n");
+        assert!(run_line("uname -a")
+            .unwrap()
+            .starts_with("Linux metasploitable 2.6.24"));
+        assert!(run_line("ls -la /").unwrap().contains("\nvmlinuz\n"));
+        assert!(run_line("cat /etc/passwd")
+            .unwrap()
+            .starts_with("root:x:0:0:root:/root:/bin/bash\n"));
+        assert_eq!(
+            run_line("id; whoami").unwrap(),
+            "uid=0(root) gid=0(root)\nroot\n"
+        );
+        assert_eq!(run_line("wget").unwrap(), "sh: wget: not found\n");
+        assert_eq!(run_line("").unwrap(), "");
+    }
+
+    #[test]
+    fn exit_closes_shell() {
+        assert!(run_line("exit").is_none());
+        assert!(run_line("id; exit").is_none());
+    }
+
+    #[test]
+    fn shell_accepts_triggered_ips() {
+        let mut triggers = TRIGGERS.lock().unwrap();
+        triggers.push(Trigger {
+            ip: "10.0.0.7".to_string(),
+            attacker_id: Some(7),
+            expires: Instant::now() + Duration::from_secs(60),
+        });
+        triggers.push(Trigger {
+            ip: "10.0.0.8".to_string(),
+            attacker_id: None,
+            expires: Instant::now() - Duration::from_secs(1),
+        });
+        drop(triggers);
+
+        assert_eq!(take_trigger("10.0.0.7").unwrap().attacker_id, Some(7));
+        assert!(take_trigger("10.0.0.7").is_none());
+        assert!(take_trigger("10.0.0.8").is_none());
+        assert!(take_trigger("10.0.0.9").is_none());
+    }
+}
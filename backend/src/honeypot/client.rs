@@ -1,8 +1,21 @@
 //! Holds information about the current Client
 
-use tokio::net::TcpSocket;
+use std::net::SocketAddr;
 
-use crate::database::models::attacker;
+use tokio::net::{TcpListener, TcpSocket};
+use tokio_rustls::TlsAcceptor;
+
+use crate::configuration::config::{get_config, ListenerConfig};
+use crate::database::models::{attacker, tls_fingerprints};
+use crate::honeypot::tarpit::TarpitSlot;
+
+/// The data connection the client negotiated for the next transfer.
+pub enum DataConnection {
+    /// Set by PORT or EPRT, the server connects to the address the client listens on.
+    Active(TcpSocket, SocketAddr),
+    /// Set by PASV or EPSV, the client connects to the listener the server opened.
+    Passive(TcpListener),
+}
 
 /// Holds information about the current [Client], the current Attacker
 pub struct Client {
@@ -10,6 +23,38 @@ pub struct Client {
     pub password: String,
     pub model: Option<attacker::Model>,
     pub ip: String,
-    pub data_socket: Option<TcpSocket>,
-    pub data_addr: Option<String>,
+    /// The address the client connected to, taken from the PROXY protocol header if forwarded.
+    pub server_addr: SocketAddr,
+    /// The listener the session came in on.
+    pub listener: ListenerConfig,
+    pub data_connection: Option<DataConnection>,
+    /// The acceptor used for AUTH TLS, [None] if FTPS is disabled.
+    pub tls_acceptor: Option<TlsAcceptor>,
+    /// The TLS fingerprint of the control connection, linked to the attacker upon login.
+    pub tls_fingerprint: Option<tls_fingerprints::Model>,
+    /// Set by PBSZ, which has to precede PROT.
+    pub protection_buffer_set: bool,
+    /// Set by PROT P, data connections are upgraded to TLS.
+    pub private_data: bool,
+    /// The path given with RNFR, which is renamed by the following RNTO.
+    pub rename_from: Option<String>,
+    /// The path given with SITE CPFR, which is copied by the following SITE CPTO.
+    pub copy_from: Option<String>,
+    /// The offset given with REST, the following RETR or STOR starts from.
+    pub restart_offset: u64,
+    /// Set by a USER ending in `:)`, the following PASS opens the fake backdoor shell.
+    pub backdoor_triggered: bool,
+    /// Set upon an anonymous login, the attacker works on the shared public filesystem.
+    pub anonymous: bool,
+    /// The login attempts of the IP, counted up with every failed login of the session.
+    pub login_attempts: i32,
+    /// Set while the session is tarpitted, failed logins are answered with a growing delay.
+    pub tarpit: Option<TarpitSlot>,
+}
+
+impl Client {
+    /// Returns whether the attacker is logged in anonymously without write access.
+    pub fn read_only(&self) -> bool {
+        self.anonymous && !get_config().anonymous_writable
+    }
 }
@@ -0,0 +1,310 @@
+//! Reads CRLF-framed FTP commands from the control connection
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use log::{error, info};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::time::{timeout_at, Instant};
+
+use crate::database::models::captures::Sender;
+use crate::honeypot::recorder::capture_control;
+
+/// Telnet "Interpret As Command" escape byte.
+const IAC: u8 = 255;
+/// Telnet subnegotiation begin.
+const SB: u8 = 250;
+/// Telnet subnegotiation end.
+const SE: u8 = 240;
+/// Telnet WILL, WONT, DO and DONT, which are followed by a single option byte.
+const WILL: u8 = 251;
+const DONT: u8 = 254;
+
+/// The result of reading from the control connection.
+#[derive(Debug, PartialEq)]
+pub enum Packet {
+    /// A complete command line without its line terminator.
+    Line(String),
+    /// The command line exceeded the configured maximum length and was discarded.
+    TooLong,
+    /// The client didn't complete a started command line in time.
+    TimedOut,
+    /// The client closed the connection or the connection failed.
+    Closed,
+}
+
+/// The state of the Telnet parser between two received bytes.
+#[derive(Clone, Copy, PartialEq)]
+enum TelnetState {
+    Data,
+    Iac,
+    Option,
+    Sub,
+    SubIac,
+}
+
+/// The [CommandReader] buffers the control connection and splits it into command lines.
+///
+/// Several commands arriving in a single TCP segment as well as commands split over several
+/// segments are handled. Lines are terminated by CRLF, a bare LF is accepted as well since many
+/// bots send it. Telnet IAC sequences are stripped from the data stream.
+/// A started line has to be completed within the command timeout, so clients can't hold the
+/// session by trickling in a command byte by byte.
+pub struct CommandReader {
+    max_length: usize,
+    command_timeout: Duration,
+    line: Vec<u8>,
+    /// When the first byte of the current line was received.
+    line_started: Option<Instant>,
+    lines: VecDeque<Packet>,
+    state: TelnetState,
+    discarding: bool,
+}
+
+impl CommandReader {
+    /// Constructs a new [CommandReader].
+    ///
+    /// * `max_length`      - The maximum length of a single command line in bytes.
+    /// * `command_timeout` - The time a started command line has to be completed in.
+    pub fn new(max_length: usize, command_timeout: Duration) -> Self {
+        CommandReader {
+            max_length,
+            command_timeout,
+            line: Vec::new(),
+            line_started: None,
+            lines: VecDeque::new(),
+            state: TelnetState::Data,
+            discarding: false,
+        }
+    }
+
+    /// Reads the next command from the `stream`.
+    ///
+    /// Upon successful reading of a command information about the packet is logged.
+    /// Upon EOF or an error [Packet::Closed] is returned, so the session can be ended.
+    /// If a started line isn't completed within the command timeout [Packet::TimedOut] is returned.
+    ///
+    /// * `stream`   - The stream used to communicate with the client.
+    pub async fn read_command<S: AsyncRead + Unpin>(&mut self, stream: &mut S) -> Packet {
+        let mut buf = [0; 1024];
+
+        loop {
+            if let Some(packet) = self.lines.pop_front() {
+                if let Packet::Line(line) = &packet {
+                    info!("Read packet: {:?}", line);
+                }
+
+                return packet;
+            }
+
+            let read = match self.line_started {
+                None => stream.read(&mut buf).await,
+                Some(started) => {
+                    match timeout_at(started + self.command_timeout, stream.read(&mut buf)).await {
+                        Ok(read) => read,
+                        Err(_) => {
+                            info!(
+                                "Discarding packet not completed in time: {:?}",
+                                String::from_utf8_lossy(&self.line)
+                            );
+
+                            self.line.clear();
+                            self.line_started = None;
+                            return Packet::TimedOut;
+                        }
+                    }
+                }
+            };
+
+            match read {
+                Ok(0) => {
+                    if !self.line.is_empty() {
+                        info!(
+                            "Discarding unterminated packet: {:?}",
+                            String::from_utf8_lossy(&self.line)
+                        );
+                    }
+
+                    return Packet::Closed;
+                }
+                Ok(n) => {
+                    capture_control(Sender::Client, &buf[..n]);
+                    self.feed(&buf[..n]);
+
+                    if !self.line.is_empty() && self.line_started.is_none() {
+                        self.line_started = Some(Instant::now());
+                    }
+                }
+                Err(e) => {
+                    error!("Connection error: {}", e);
+                    return Packet::Closed;
+                }
+            }
+        }
+    }
+
+    /// Feeds received bytes into the reader and queues every completed line.
+    ///
+    /// * `bytes` - The bytes read from the control connection.
+    fn feed(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state = match self.state {
+                TelnetState::Data if byte == IAC => TelnetState::Iac,
+                TelnetState::Data => {
+                    self.push(byte);
+                    TelnetState::Data
+                }
+                TelnetState::Iac if byte == IAC => {
+                    // An escaped 0xFF is a literal data byte.
+                    self.push(byte);
+                    TelnetState::Data
+                }
+                TelnetState::Iac if byte == SB => TelnetState::Sub,
+                TelnetState::Iac if (WILL..=DONT).contains(&byte) => TelnetState::Option,
+                TelnetState::Iac | TelnetState::Option => TelnetState::Data,
+                TelnetState::Sub if byte == IAC => TelnetState::SubIac,
+                TelnetState::Sub => TelnetState::Sub,
+                TelnetState::SubIac if byte == SE => TelnetState::Data,
+                TelnetState::SubIac => TelnetState::Sub,
+            };
+        }
+    }
+
+    /// Pushes a single data byte to the current line.
+    fn push(&mut self, byte: u8) {
+        if byte == b'\n' {
+            if self.discarding {
+                self.discarding = false;
+            } else {
+                if self.line.last() == Some(&b'\r') {
+                    self.line.pop();
+                }
+
+                let line = String::from_utf8_lossy(&self.line).to_string();
+                self.lines.push_back(Packet::Line(line));
+            }
+
+            self.line.clear();
+            self.line_started = None;
+            return;
+        }
+
+        if self.discarding {
+            return;
+        }
+
+        if self.line.len() >= self.max_length {
+            info!(
+                "Discarding packet exceeding {} bytes: {:?}",
+                self.max_length,
+                String::from_utf8_lossy(&self.line)
+            );
+
+            self.line.clear();
+            self.discarding = true;
+            self.lines.push_back(Packet::TooLong);
+            return;
+        }
+
+        self.line.push(byte);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::AsyncWriteExt;
+
+    use crate::honeypot::command_reader::{CommandReader, Packet};
+
+    #[actix_rt::test]
+    async fn pipelined_commands() {
+        let mut reader = CommandReader::new(64, Duration::from_secs(60));
+        let mut stream: &[u8] = b"USER anonymous\r\nPASS a@b.c\r\nPWD\r\n";
+
+        assert_eq!(
+            reader.read_command(&mut stream).await,
+            Packet::Line("USER anonymous".to_string())
+        );
+        assert_eq!(
+            reader.read_command(&mut stream).await,
+            Packet::Line("PASS a@b.c".to_string())
+        );
+        assert_eq!(
+            reader.read_command(&mut stream).await,
+            Packet::Line("PWD".to_string())
+        );
+        assert_eq!(reader.read_command(&mut stream).await, Packet::Closed);
+    }
+
+    #[actix_rt::test]
+    async fn split_command() {
+        let mut reader = CommandReader::new(64, Duration::from_secs(60));
+
+        reader.feed(b"STOR my ");
+        reader.feed(b"file.exe\r");
+        reader.feed(b"\n");
+
+        let mut stream: &[u8] = b"";
+        assert_eq!(
+            reader.read_command(&mut stream).await,
+            Packet::Line("STOR my file.exe".to_string())
+        );
+    }
+
+    #[actix_rt::test]
+    async fn long_command() {
+        let mut reader = CommandReader::new(8, Duration::from_secs(60));
+        let mut stream: &[u8] = b"CWD /a/very/long/path\r\nNOOP\r\n";
+
+        assert_eq!(reader.read_command(&mut stream).await, Packet::TooLong);
+        assert_eq!(
+            reader.read_command(&mut stream).await,
+            Packet::Line("NOOP".to_string())
+        );
+    }
+
+    #[actix_rt::test]
+    async fn strips_telnet_sequences() {
+        let mut reader = CommandReader::new(64, Duration::from_secs(60));
+        // IAC IP, IAC DM, IAC DO 1, IAC SB 24 0 IAC SE and an escaped IAC IAC
+        let mut stream: &[u8] = &[
+            255, 244, 255, 242, 65, 66, 255, 253, 1, 79, 82, 255, 250, 24, 0, 255, 240, 84, 255,
+            255, 13, 10,
+        ];
+
+        assert_eq!(
+            reader.read_command(&mut stream).await,
+            Packet::Line("ABORT\u{FFFD}".to_string())
+        );
+    }
+
+    #[actix_rt::test]
+    async fn unterminated_command_at_eof() {
+        let mut reader = CommandReader::new(64, Duration::from_secs(60));
+        let mut stream: &[u8] = b"QUI";
+
+        assert_eq!(reader.read_command(&mut stream).await, Packet::Closed);
+    }
+
+    #[actix_rt::test]
+    async fn incomplete_command_times_out() {
+        let mut reader = CommandReader::new(64, Duration::from_millis(50));
+        let (mut client, mut server) = tokio::io::duplex(64);
+
+        client.write_all(b"NOOP\r\nPW").await.unwrap();
+
+        assert_eq!(
+            reader.read_command(&mut server).await,
+            Packet::Line("NOOP".to_string())
+        );
+        assert_eq!(reader.read_command(&mut server).await, Packet::TimedOut);
+
+        client.write_all(b"D\r\n").await.unwrap();
+        assert_eq!(
+            reader.read_command(&mut server).await,
+            Packet::Line("D".to_string())
+        );
+    }
+}
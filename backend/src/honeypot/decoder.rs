@@ -1,63 +1,119 @@
 //! Decodes the FTP commands & arguments
 
-use std::borrow::Cow;
+use std::fmt;
 use std::str::FromStr;
 
-use crate::honeypot::ftp::{Command, Request};
+use crate::honeypot::ftp::{Command, Request, StatusCode};
+
+/// The errors the [Decoder] returns for command lines that can't be handled.
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    /// The line doesn't start with a valid command verb.
+    InvalidCommand(String),
+    /// The command requires an argument, but none was given.
+    MissingArgument(String),
+}
+
+impl DecodeError {
+    /// Returns the [StatusCode] and the message the client is answered with.
+    pub fn reply(&self) -> (StatusCode, &'static str) {
+        match self {
+            DecodeError::InvalidCommand(_) => (
+                StatusCode::SyntaxError,
+                "Syntax error, command unrecognized.",
+            ),
+            DecodeError::MissingArgument(_) => (
+                StatusCode::SyntaxErrorInParameters,
+                "Syntax error in parameters or arguments.",
+            ),
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::InvalidCommand(line) => write!(f, "Invalid command: {:?}", line),
+            DecodeError::MissingArgument(line) => write!(f, "Missing argument: {:?}", line),
+        }
+    }
+}
 
 /// The [Decoder] is used to decode FTP packets to a usable state.
 pub struct Decoder {}
 
 impl Decoder {
-    /// Decodes a FTP packet to the [Request] which holds the [Command] and message [String].
+    /// Decodes a FTP command line to the [Request] which holds the [Command] and argument [String].
     ///
-    /// Invalid packets kill the connection of the client.
+    /// Verbs are matched case-insensitively and the whole remainder of the line after the first
+    /// space is kept as the argument, including further spaces.
+    /// Invalid lines and missing arguments return a [DecodeError].
     /// Not supported packets will return the CommandNotImplemented status code later on.
     ///
-    /// * `packet` - The FTP packet the client sent.
-    pub fn decode(packet: Cow<str>) -> Result<Request, String> {
-        let altered_string = packet.replace("\r\n", " ");
-        let vector_string: Vec<&str> = altered_string.split(' ').collect();
+    /// * `line` - The command line the client sent, without the line terminator.
+    pub fn decode(line: &str) -> Result<Request, DecodeError> {
+        let (verb, argument) = line.split_once(' ').unwrap_or((line, ""));
 
-        if vector_string.len() <= 1 {
-            return Err("Got invalid packet! Goodbye!".to_string());
+        if verb.is_empty() || !verb.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(DecodeError::InvalidCommand(line.to_string()));
         }
 
-        let command_string = vector_string[0];
-        let command_res = Command::from_str(command_string);
-        let command = command_res.unwrap_or(Command::NOT_SUPPORTED);
+        let command = Command::from_str(verb).unwrap_or(Command::NOT_SUPPORTED);
 
-        let argument = vector_string[1].to_string();
+        if argument.is_empty() && command.requires_argument() {
+            return Err(DecodeError::MissingArgument(line.to_string()));
+        }
 
-        Ok(Request { command, argument })
+        Ok(Request {
+            command,
+            argument: argument.to_string(),
+            raw: line.to_string(),
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::honeypot::decoder::Decoder;
-    use crate::honeypot::ftp::Command::USER;
+    use crate::honeypot::decoder::{DecodeError, Decoder};
+    use crate::honeypot::ftp::Command::{NOT_SUPPORTED, PWD, STOR, USER};
 
     #[test]
     fn valid_decode() {
-        // "USER c\r\n\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0"
-        let packet = [
-            85, 83, 69, 82, 32, 99, 13, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0,
-        ];
-
-        let res = Decoder::decode(String::from_utf8_lossy(&packet[..]));
+        let res = Decoder::decode("USER c");
 
         assert_eq!(res.as_ref().unwrap().argument, "c");
+        assert_eq!(res.as_ref().unwrap().raw, "USER c");
         assert_eq!(res.unwrap().command, USER);
     }
 
+    #[test]
+    fn decode_keeps_spaces_in_argument() {
+        let res = Decoder::decode("STOR my file.exe").unwrap();
+
+        assert_eq!(res.command, STOR);
+        assert_eq!(res.argument, "my file.exe");
+    }
+
+    #[test]
+    fn decode_case_insensitive() {
+        assert_eq!(Decoder::decode("user anonymous").unwrap().command, USER);
+        assert_eq!(Decoder::decode("pWd").unwrap().command, PWD);
+        assert_eq!(Decoder::decode("XYZW").unwrap().command, NOT_SUPPORTED);
+    }
+
+    #[test]
+    fn decode_missing_argument() {
+        let res = Decoder::decode("STOR");
+
+        assert_eq!(res.err(), Some(DecodeError::MissingArgument("STOR".into())));
+    }
+
     #[test]
     #[should_panic]
     fn invalid_decode() {
         // random garbage, we can't do anything with broken / invalid packets so we panic
         let packet = [44, 33, 22, 11, 10, 66, 33, 99];
 
-        Decoder::decode(String::from_utf8_lossy(&packet[..])).unwrap();
+        Decoder::decode(&String::from_utf8_lossy(&packet[..])).unwrap();
     }
 }
@@ -11,17 +11,50 @@ impl Encoder {
     /// Encodes a FTP packet to a bytes so it can be sent over the TCP connection
     /// and understood by the client.
     ///
+    /// Messages with more than one line are framed as RFC 959 multi-line replies: the first line
+    /// is sent as `code-text`, the last line as `code text`. Lines in between are sent as they
+    /// are, unless they would be read as the last line, then they are indented by a space.
+    ///
     /// * `reply` - The FTP reply struct that holds the status code and the message.
     pub fn encode(reply: &Reply) -> Result<Vec<u8>, std::io::Error> {
         let mut vec = Vec::new();
+        let code = reply.code as u32;
+
+        let lines = match &reply.msg {
+            ReplyMessage::Is(message) => message
+                .split('\n')
+                .map(|line| line.trim_end_matches('\r'))
+                .collect::<Vec<&str>>(),
+            ReplyMessage::Lines(lines) => lines.iter().map(String::as_str).collect(),
+        };
+
+        match lines.as_slice() {
+            [] => write!(vec, "{}\r\n", code)?,
+            [message] => write!(vec, "{} {}\r\n", code, message)?,
+            [first, middle @ .., last] => {
+                write!(vec, "{}-{}\r\n", code, first)?;
+
+                for line in middle {
+                    if Encoder::is_final_line(line) {
+                        write!(vec, " {}\r\n", line)?;
+                    } else {
+                        write!(vec, "{}\r\n", line)?;
+                    }
+                }
 
-        match &reply.msg {
-            ReplyMessage::Is(message) => write!(vec, "{} {}\r\n", reply.code as u32, message)?,
-            _ => write!(vec, "{}\r\n", reply.code as u32)?, // Just in case, but this wasn't necessary yet.
+                write!(vec, "{} {}\r\n", code, last)?;
+            }
         }
 
         Ok(vec)
     }
+
+    /// Returns whether a client reads the line as the last line of a multi-line reply, which is
+    /// the case if it starts with three digits followed by a space.
+    fn is_final_line(line: &str) -> bool {
+        let bytes = line.as_bytes();
+        bytes.len() >= 4 && bytes[..3].iter().all(u8::is_ascii_digit) && bytes[3] == b' '
+    }
 }
 
 #[cfg(test)]
@@ -38,4 +71,62 @@ mod tests {
         // "200 Test\r\n"
         assert_eq!(res, [50, 48, 48, 32, 84, 101, 115, 116, 13, 10]);
     }
+
+    #[test]
+    fn encode_multi_line_message() {
+        let reply = Reply::new(
+            StatusCode::ServiceReadyForNewUser,
+            ReplyMessage::Is(String::from("Welcome\r\nto the\nFTP Server")),
+        );
+
+        let res = Encoder::encode(&reply).unwrap();
+
+        assert_eq!(res, b"220-Welcome\r\nto the\r\n220 FTP Server\r\n");
+    }
+
+    #[test]
+    fn encode_lines() {
+        let reply = Reply::new(
+            StatusCode::SystemStatus,
+            ReplyMessage::Lines(vec![
+                String::from("Features:"),
+                String::from(" SIZE"),
+                String::from(" UTF8"),
+                String::from("End"),
+            ]),
+        );
+
+        let res = Encoder::encode(&reply).unwrap();
+
+        assert_eq!(res, b"211-Features:\r\n SIZE\r\n UTF8\r\n211 End\r\n");
+    }
+
+    #[test]
+    fn encode_indents_lines_read_as_last_line() {
+        let reply = Reply::new(
+            StatusCode::HelpMessage,
+            ReplyMessage::Lines(vec![
+                String::from("Help"),
+                String::from("214 not the end"),
+                String::from("214-still not the end"),
+                String::from("End"),
+            ]),
+        );
+
+        let res = Encoder::encode(&reply).unwrap();
+
+        assert_eq!(
+            res,
+            b"214-Help\r\n 214 not the end\r\n214-still not the end\r\n214 End\r\n"
+        );
+    }
+
+    #[test]
+    fn encode_without_message() {
+        let reply = Reply::new(StatusCode::Okay, ReplyMessage::Lines(vec![]));
+
+        let res = Encoder::encode(&reply).unwrap();
+
+        assert_eq!(res, b"200\r\n");
+    }
 }
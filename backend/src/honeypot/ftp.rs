@@ -1,16 +1,21 @@
 //! FTP related helper structs & enums
 
+use strum::IntoEnumIterator;
 use strum_macros::Display;
+use strum_macros::EnumIter;
 use strum_macros::EnumString;
 
 #[derive(Clone, Copy, Display)]
 /// The FTP StatusCodes used to communicate with the client.
 pub enum StatusCode {
     ServiceReadyForNewUser = 220,
+    EnteringPassiveMode = 227,
+    EnteringExtendedPassiveMode = 229,
     UserNameOkayNeedPassword = 331,
     UserLoggedInProceed = 230,
     NameSystemType = 215,
     RequestedFileActionOkayCompleted = 250,
+    RequestedFileActionPendingFurtherInformation = 350,
     CommandNotImplemented = 502,
     NotLoggedIn = 530,
     Okay = 200,
@@ -22,13 +27,28 @@ pub enum StatusCode {
     ClosingDataConnection = 226,
     CommandNotImplementedForParameter = 504,
     ServiceNotAvailable = 421,
+    CantOpenDataConnection = 425,
+    ConnectionClosedTransferAborted = 426,
+    SyntaxError = 500,
+    SyntaxErrorInParameters = 501,
+    NetworkProtocolNotSupported = 522,
+    SecurityExchangeOkay = 234,
+    BadSequenceOfCommands = 503,
+    ProtectionLevelNotSupported = 536,
+    SystemStatus = 211,
+    HelpMessage = 214,
+    FileStatus = 213,
+    ExceededStorageAllocation = 552,
+    LocalErrorInProcessing = 451,
+    RequestedActionNotTaken = 554,
 }
 
-#[allow(dead_code)]
 /// The ReplyMessage which holds the message [String].
 pub enum ReplyMessage {
-    None,
+    /// A message, which is sent as multi-line reply if it contains line breaks.
     Is(String),
+    /// The lines of a multi-line reply. The first and the last line are prefixed with the code.
+    Lines(Vec<String>),
 }
 
 /// The Reply struct which hold [StatusCode] and the [ReplyMessage].
@@ -46,7 +66,8 @@ impl Reply {
 
 #[allow(dead_code)]
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Debug, PartialEq, EnumString)]
+#[derive(Debug, PartialEq, EnumString, EnumIter)]
+#[strum(ascii_case_insensitive)]
 /// The FTP [Command] enum which holds all handled commands.
 pub enum Command {
     USER,
@@ -71,20 +92,91 @@ pub enum Command {
     ALLO,
     RMD,
     STAT,
+    PASV,
+    EPSV,
+    EPRT,
+    AUTH,
+    PBSZ,
+    PROT,
+    NLST,
+    MLSD,
+    MLST,
+    RNFR,
+    RNTO,
+    STOU,
+    APPE,
+    REST,
+    SIZE,
+    MDTM,
+    FEAT,
+    OPTS,
+    SITE,
     #[allow(non_camel_case_types)]
-    // RNTO,
-    // NLST,
-    // STOU,
-    // PASV,
-    // APPE,
-    // REST,
-    // RNFR,
     NOT_SUPPORTED,
 }
 
+impl Command {
+    /// Returns whether the [Command] can't be used without an argument.
+    pub fn requires_argument(&self) -> bool {
+        matches!(
+            self,
+            Command::USER
+                | Command::ACCT
+                | Command::PORT
+                | Command::EPRT
+                | Command::AUTH
+                | Command::PBSZ
+                | Command::PROT
+                | Command::TYPE
+                | Command::MODE
+                | Command::STRU
+                | Command::RETR
+                | Command::STOR
+                | Command::APPE
+                | Command::REST
+                | Command::CWD
+                | Command::MKD
+                | Command::DELE
+                | Command::RMD
+                | Command::RNFR
+                | Command::RNTO
+                | Command::SIZE
+                | Command::MDTM
+                | Command::OPTS
+                | Command::SITE
+        )
+    }
+
+    /// Returns whether the [Command] changes the filesystem, which read-only users can't do.
+    pub fn modifies_filesystem(&self) -> bool {
+        matches!(
+            self,
+            Command::STOR
+                | Command::STOU
+                | Command::APPE
+                | Command::MKD
+                | Command::DELE
+                | Command::RMD
+                | Command::RNFR
+                | Command::RNTO
+        )
+    }
+
+    /// Returns the names of all handled commands in alphabetical order, as listed by HELP.
+    pub fn recognized() -> Vec<String> {
+        let mut commands = Command::iter()
+            .filter(|command| *command != Command::NOT_SUPPORTED)
+            .map(|command| format!("{:?}", command))
+            .collect::<Vec<String>>();
+        commands.sort();
+        commands
+    }
+}
+
 /// The [Request] struct which holds the [Command] and the argument [String] used to communicate with
-/// the client.
+/// the client. The raw line is kept for logging.
 pub struct Request {
     pub command: Command,
     pub argument: String,
+    pub raw: String,
 }
@@ -1,45 +1,66 @@
 //! FTP related access handling functions
 
-use tokio::net::TcpStream;
+use serde_json::json;
+use tokio::time::sleep;
 
+use crate::configuration::config::{get_config, ListenerConfig};
 use crate::database::models::attacker::Model;
+use crate::database::models::events::EventKind;
 use crate::database::service::DatabaseImplementation;
 use crate::external_api::file_service::create_json_and_send_request;
 #[allow(unused_imports)]
 use crate::filesystem::ftp_fs::FtpFileSystem;
+use crate::honeypot::backdoor::{is_trigger, open_shell};
 use crate::honeypot::client::Client;
 use crate::honeypot::ftp::{Request, StatusCode};
+use crate::honeypot::ftp_event::save_event;
 use crate::honeypot::ftp_helper::send_response;
+use crate::honeypot::ftp_security::link_tls_fingerprint;
+use crate::honeypot::recorder;
+use crate::honeypot::stream::FtpStream;
+use crate::honeypot::tarpit;
 use crate::login::login_service;
 
 /// Handles the FTP login logic
 ///
 /// Sends each login attempt to the frontend and checks for valid logins.
+/// Anonymous logins are always valid.
 ///
-/// * `username` - The attackers username
-/// * `password` - The attackers password
-/// * `ip`       - The attackers IP
-/// * `db`       - The [DatabaseImplementation]
+/// * `username`  - The attackers username
+/// * `password`  - The attackers password
+/// * `ip`        - The attackers IP
+/// * `listener`  - The listener the attacker came in on
+/// * `db`        - The [DatabaseImplementation]
+/// * `anonymous` - Whether the attacker logs in anonymously.
 async fn login(
     username: &str,
     password: &str,
     ip: &str,
+    listener: &ListenerConfig,
     db: &DatabaseImplementation,
+    anonymous: bool,
 ) -> Option<Model> {
     create_json_and_send_request(ip, username, password).await;
-    login_service::is_login_valid(username, password, ip, db).await
+
+    if anonymous {
+        return Some(login_service::anonymous_login(username, password, ip, listener, db).await);
+    }
+
+    login_service::is_login_valid(username, password, ip, listener, db).await
 }
 
 /// Handles the FTP USER packet.
 ///
 /// Sends each login attempt to the frontend and checks for valid logins.
-/// Currently all usernames are valid.
+/// Currently all usernames are valid. If enabled, usernames ending in `:)` trigger the emulated
+/// vsftpd 2.3.4 backdoor.
 ///
-/// * `stream`   - The TCP stream used to communicate with the client.
+/// * `stream`   - The [FtpStream] used to communicate with the client.
 /// * `client`   - The current [Client] struct.
 /// * `request`  - The [Request] used to handle the argument.
-pub async fn user(stream: &mut TcpStream, client: &mut Client, request: &Request) -> bool {
+pub async fn user(stream: &mut FtpStream, client: &mut Client, request: &Request) -> bool {
     client.username = request.argument.to_string();
+    client.backdoor_triggered = get_config().backdoor_enabled && is_trigger(&client.username);
 
     if !send_response(
         stream,
@@ -57,7 +78,7 @@ pub async fn user(stream: &mut TcpStream, client: &mut Client, request: &Request
 /// Handles the FTP ACCT packet.
 ///
 /// ACCT is always Rejected.
-pub async fn acct(stream: &mut TcpStream) -> bool {
+pub async fn acct(stream: &mut FtpStream) -> bool {
     if !send_response(stream, StatusCode::NotLoggedIn, "Rejected").await {
         return false;
     }
@@ -70,34 +91,78 @@ pub async fn acct(stream: &mut TcpStream) -> bool {
 /// Upon a valid login a [Model] is created and the attacker is greeted with a successful
 /// login.
 /// The [FtpFileSystem] path is set back to it's default state upon a successful login.
+/// A TLS fingerprint taken before the attacker existed is linked to the attacker.
+/// If enabled, anonymous logins are accepted right away, stored as event and the session is
+/// marked as anonymous.
+/// If the backdoor was triggered, the fake shell is [opened][open_shell] instead of logging in
+/// and replying.
+/// Failed logins of [tarpitted][tarpit] sessions are answered with a growing delay.
 ///
-/// * `stream`   - The TCP stream used to communicate with the client.
+/// * `stream`   - The [FtpStream] used to communicate with the client.
 /// * `client`   - The current [Client] struct.
 /// * `request`  - The [Request] used to handle the argument.
 /// * `db`       - The [DatabaseImplementation]
 pub async fn pass(
-    stream: &mut TcpStream,
+    stream: &mut FtpStream,
     client: &mut Client,
     request: &Request,
     db: &DatabaseImplementation,
 ) -> bool {
     client.password = request.argument.to_string();
-    client.model = login(&client.username, &client.password, &client.ip, db).await;
-    if client.model.is_some() {
+
+    if client.backdoor_triggered {
+        return open_shell(client, db).await;
+    }
+
+    client.anonymous = get_config().anonymous_enabled
+        && login_service::is_anonymous(&client.username, &client.password);
+    client.model = login(
+        &client.username,
+        &client.password,
+        &client.ip,
+        &client.listener,
+        db,
+        client.anonymous,
+    )
+    .await;
+    link_tls_fingerprint(client, db).await;
+
+    if client.anonymous {
+        recorder::mark_anonymous();
+        save_event(
+            client,
+            db,
+            EventKind::AnonymousLogin,
+            json!({ "username": client.username, "password": client.password }),
+        )
+        .await;
+    }
+
+    if let Some(model) = client.model.as_mut() {
         if !send_response(stream, StatusCode::UserLoggedInProceed, "Login successful.").await {
             return false;
         }
 
-        let model = client.model.as_mut().unwrap();
-
         model
             .file_system
             .as_mut()
             .expect("Filesystem not set!")
             .clear_path(db, model.id)
             .await;
-    } else if !send_response(stream, StatusCode::NotLoggedIn, "Login incorrect.").await {
-        return false;
+    } else {
+        client.login_attempts += 1;
+
+        if client.tarpit.is_none() {
+            client.tarpit = tarpit::enter(client.login_attempts);
+        }
+
+        if client.tarpit.is_some() {
+            sleep(tarpit::login_delay(client.login_attempts)).await;
+        }
+
+        if !send_response(stream, StatusCode::NotLoggedIn, "Login incorrect.").await {
+            return false;
+        }
     }
 
     true
@@ -106,7 +171,7 @@ pub async fn pass(
 /// Handles the FTP QUIT packet.
 ///
 /// Bye.
-pub async fn quit(stream: &mut TcpStream) -> bool {
+pub async fn quit(stream: &mut FtpStream) -> bool {
     if !send_response(stream, StatusCode::UserSuccessfulLogout, "Bye.").await {
         return false;
     }
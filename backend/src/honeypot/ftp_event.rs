@@ -0,0 +1,62 @@
+//! Records notable attacker actions as events
+
+use chrono::Local;
+use log::info;
+use sea_orm::ActiveValue::Set;
+use sea_orm::NotSet;
+use serde_json::Value;
+
+use crate::database::models::events;
+use crate::database::models::events::EventKind;
+use crate::database::service::DatabaseImplementation;
+use crate::database::service_trait::DatabaseTrait;
+use crate::honeypot::client::Client;
+
+/// Stores a new event for the attacker of the `client`.
+///
+/// * `client`   - The current [Client] struct.
+/// * `db`       - The [DatabaseImplementation]
+/// * `kind`     - The [EventKind] of the event.
+/// * `data`     - The details of the event, stored as JSON.
+pub async fn save_event(
+    client: &Client,
+    db: &DatabaseImplementation,
+    kind: EventKind,
+    data: Value,
+) {
+    save_event_for_ip(
+        db,
+        &client.ip,
+        client.model.as_ref().map(|model| model.id),
+        kind,
+        data,
+    )
+    .await;
+}
+
+/// Stores a new event for the attacker with the `ip`, used outside of the FTP session.
+///
+/// * `db`           - The [DatabaseImplementation]
+/// * `ip`           - The IP of the attacker.
+/// * `attacker_id`  - The id of the attacker, if known.
+/// * `kind`         - The [EventKind] of the event.
+/// * `data`         - The details of the event, stored as JSON.
+pub async fn save_event_for_ip(
+    db: &DatabaseImplementation,
+    ip: &str,
+    attacker_id: Option<i64>,
+    kind: EventKind,
+    data: Value,
+) {
+    info!("Attacker: {} triggered event {}: {}", ip, kind, data);
+
+    let event = events::ActiveModel {
+        id: NotSet,
+        ip: Set(ip.to_string()),
+        attacker_id: Set(attacker_id),
+        kind: Set(kind.to_string()),
+        data: Set(data.to_string()),
+        created: Set(Local::now()),
+    };
+    db.update_event(event).await;
+}
@@ -1,39 +1,217 @@
 //! FTP related file system handling functions
 
+use std::io;
+use std::io::SeekFrom;
 use std::ops::Add;
 use std::path::Path;
+use std::time::Duration;
 
-use log::info;
+use log::{error, info};
 use rand::distributions::Alphanumeric;
 use rand::Rng;
 use regex::Regex;
+use serde_json::json;
+use sha2::{Digest, Sha256};
 use tokio::fs;
-use tokio::fs::File;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::time::timeout;
 
 use crate::configuration::config::get_config;
 #[allow(unused_imports)]
 use crate::configuration::config::Config;
 use crate::database::models::attacker::Model;
+use crate::database::models::captures::Sender;
+use crate::database::models::events::EventKind;
 use crate::database::service::DatabaseImplementation;
+use crate::database::service_trait::DatabaseTrait;
 #[allow(unused_imports)]
 use crate::filesystem::ftp_fs::FtpFileSystem;
-use crate::honeypot::client::Client;
-use crate::honeypot::ftp::{Request, StatusCode};
-use crate::honeypot::ftp_helper::{deny_access, send_response};
+use crate::filesystem::models::ftp_node::mlsx_timestamp;
+use crate::honeypot::client::{Client, DataConnection};
+use crate::honeypot::ftp::{Reply, ReplyMessage, Request, StatusCode};
+use crate::honeypot::ftp_event::save_event;
+use crate::honeypot::ftp_helper::{
+    deny_access, send_multiline_response, send_response, write_packet,
+};
+use crate::honeypot::personality::{get_personality, ListingFormat};
+use crate::honeypot::proxy;
+use crate::honeypot::proxy::Addresses;
+use crate::honeypot::recorder::{record_change, DataCapture};
+use crate::honeypot::stream::FtpStream;
+
+/// Returns the data stream used to exchange data between the client and the server with its
+/// [Addresses].
+///
+/// Depending on the [DataConnection] the client negotiated, the server either connects to the
+/// address given with PORT or EPRT or accepts the client on the listener opened with PASV or EPSV.
+/// Passive data connections of trusted load balancers start with a PROXY protocol header if
+/// configured, which is stripped and tells the addresses. A passive data connection from another
+/// IP than the one of the client is refused.
+/// If the client requested a private data channel with PROT P, the data stream is upgraded to TLS.
+/// If no data connection was negotiated or it can't be established within the data connect timeout
+/// [None] is returned.
+///
+/// * `client`   - The current [Client] struct.
+async fn get_data_tcp(client: &mut Client) -> Option<(FtpStream, Addresses)> {
+    let connect_timeout = Duration::from_secs(get_config().timeout_data_connect);
+    let data_connection = client.data_connection.take()?;
+    let client_ip = client.ip.as_str();
+
+    let result = timeout(connect_timeout, async {
+        match data_connection {
+            DataConnection::Active(socket, client) => {
+                let tcp = socket.connect(client).await?;
+                let server = tcp.local_addr()?;
+                Ok((tcp, Addresses { client, server }))
+            }
+            DataConnection::Passive(listener) => {
+                let (mut tcp, client) = listener.accept().await?;
+                let server = tcp.local_addr()?;
+                let addresses =
+                    proxy::resolve(&mut tcp, Addresses { client, server }, true).await?;
+
+                let ip = addresses.client.ip().to_canonical().to_string();
+                if ip != client_ip {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        format!("refused passive data connection from: {}", ip),
+                    ));
+                }
+                Ok((tcp, addresses))
+            }
+        }
+    })
+    .await;
+
+    let (mut data_stream, addresses) = match result {
+        Ok(Ok((tcp, addresses))) => (FtpStream::Plain(tcp), addresses),
+        Ok(Err(e)) => {
+            error!("Data connection error: {}", e);
+            return None;
+        }
+        Err(_) => {
+            error!("Data connection of: {} timed out", client.ip);
+            return None;
+        }
+    };
+
+    if client.private_data {
+        let acceptor = client.tls_acceptor.as_ref()?;
+
+        match timeout(connect_timeout, data_stream.upgrade(acceptor)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => {
+                error!("TLS handshake on data connection failed: {}", e);
+                return None;
+            }
+            Err(_) => {
+                error!(
+                    "TLS handshake on data connection of: {} timed out",
+                    client.ip
+                );
+                return None;
+            }
+        }
+    }
+
+    Some((data_stream, addresses))
+}
+
+/// Informs the client that no data connection could be established.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+async fn deny_data_connection(stream: &mut FtpStream) -> bool {
+    send_response(
+        stream,
+        StatusCode::CantOpenDataConnection,
+        "Can't open data connection.",
+    )
+    .await
+}
+
+/// Informs the client that the transfer failed or didn't finish in time.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+async fn abort_transfer(stream: &mut FtpStream) -> bool {
+    send_response(
+        stream,
+        StatusCode::ConnectionClosedTransferAborted,
+        "Connection closed; transfer aborted.",
+    )
+    .await
+}
 
-/// Returns the TCP data stream used to exchange data between the client and the server.
+/// Returns whether the `transfer` finished within the data transfer timeout, errors are logged.
 ///
+/// * `transfer` - The transfer over the data connection.
+async fn finish_transfer<F>(transfer: F) -> bool
+where
+    F: std::future::Future<Output = std::io::Result<()>>,
+{
+    let transfer_timeout = Duration::from_secs(get_config().timeout_data_transfer);
+
+    match timeout(transfer_timeout, transfer).await {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            error!("Data connection error: {}", e);
+            false
+        }
+        Err(_) => {
+            error!("Data transfer timed out");
+            false
+        }
+    }
+}
+
+/// Sends a directory listing over a new data connection.
+///
+/// The client is informed about the transfer, the listing is written to the data connection and
+/// the data connection is closed again. If the transfer fails or times out, it is aborted.
+/// The listing is [captured][DataCapture].
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
 /// * `client`   - The current [Client] struct.
-async fn get_data_tcp(client: &mut Client) -> TcpStream {
-    client
-        .data_socket
-        .take()
-        .unwrap()
-        .connect(client.data_addr.take().unwrap().parse().unwrap())
-        .await
-        .unwrap()
+/// * `listing`  - The listing with lines separated by CRLF.
+async fn send_listing(stream: &mut FtpStream, client: &mut Client, listing: String) -> bool {
+    if !send_response(
+        stream,
+        StatusCode::FileStatusOkay,
+        "Here comes the directory listing.",
+    )
+    .await
+    {
+        return false;
+    }
+
+    let (mut tcp, addresses) = match get_data_tcp(client).await {
+        Some(data) => data,
+        None => return deny_data_connection(stream).await,
+    };
+
+    let mut capture = DataCapture::new(addresses);
+    let completed = finish_transfer(async {
+        if !listing.is_empty() {
+            let listing = format!("{}\r\n", listing);
+            tcp.write_all(listing.as_bytes()).await?;
+            capture.capture(Sender::Server, listing.as_bytes());
+        }
+
+        tcp.shutdown().await
+    })
+    .await;
+    capture.finish();
+
+    if !completed {
+        return abort_transfer(stream).await;
+    }
+
+    send_response(
+        stream,
+        StatusCode::ClosingDataConnection,
+        "Directory send OK.",
+    )
+    .await
 }
 
 /// Takes the current base path from the [Config] and appends the id of the current [Model] along
@@ -53,53 +231,341 @@ fn get_real_file_path(model: &mut Model, append: String) -> String {
 /// If the [FtpFileSystem] knows the path to switch to the current path is updated and the client
 /// gets a successful message. Otherwise an error is sent to the client.
 ///
-/// * `stream`   - The TCP stream used to communicate with the client.
+/// * `stream`   - The [FtpStream] used to communicate with the client.
 /// * `client`   - The current [Client] struct.
 /// * `request`  - The [Request] used to handle the argument.
-pub async fn cwd(stream: &mut TcpStream, client: &mut Client, request: &Request) -> bool {
-    if client.model.is_none() {
-        if !deny_access(stream).await {
-            return false;
+pub async fn cwd(stream: &mut FtpStream, client: &mut Client, request: &Request) -> bool {
+    match client.model.as_mut() {
+        None => {
+            if !deny_access(stream).await {
+                return false;
+            }
         }
-    } else {
-        let model = client.model.as_mut().unwrap();
-        let can_cwd = model
-            .file_system
-            .as_mut()
-            .expect("Filesystem not set!")
-            .cd_as_str(request.argument.as_str());
+        Some(model) => {
+            let can_cwd = model
+                .file_system
+                .as_mut()
+                .expect("Filesystem not set!")
+                .cd_as_str(request.argument.as_str());
 
-        info!(
-            "Attacker: {}, attempted to change directory to: {}",
-            client.username,
-            request.argument.as_str()
-        );
+            info!(
+                "Attacker: {}, attempted to change directory to: {}",
+                client.username,
+                request.argument.as_str()
+            );
 
-        return if !can_cwd {
-            if !send_response(
+            return if !can_cwd {
+                if !send_response(
+                    stream,
+                    StatusCode::DirectoryCreationFailed,
+                    "Failed to change directory.",
+                )
+                .await
+                {
+                    return false;
+                }
+
+                true
+            } else {
+                if !send_response(
+                    stream,
+                    StatusCode::RequestedFileActionOkayCompleted,
+                    "Directory successfully changed.",
+                )
+                .await
+                {
+                    return false;
+                }
+
+                true
+            };
+        }
+    }
+
+    true
+}
+
+/// The way the data of an upload is written to the physical file.
+#[derive(Clone, Copy, PartialEq)]
+enum UploadMode {
+    /// A new file is created, used by STOR and STOU.
+    Store,
+    /// The data is appended to the existing file, used by APPE.
+    Append,
+    /// The existing file is overwritten from the offset given with REST on, used by STOR.
+    Resume(u64),
+}
+
+/// Returns whether the owner of the filesystem reached the configured upload limits.
+///
+/// Once the uploads of the owner add up to the size limit no more data is accepted, once
+/// the amount of uploads reaches the file limit no new files are.
+///
+/// * `db`       - The [DatabaseImplementation]
+/// * `owner`    - The id of the attacker, [None] for the public filesystem.
+/// * `new_file` - Whether the upload creates a new file.
+async fn upload_limit_reached(
+    db: &DatabaseImplementation,
+    owner: Option<i64>,
+    new_file: bool,
+) -> bool {
+    let config = get_config();
+    let files = match owner {
+        Some(attacker_id) => db.get_files_by_attacker_id(attacker_id).await,
+        None => db.get_public_files().await,
+    };
+    let size: i64 = files.iter().map(|file| file.size).sum();
+
+    (new_file && files.len() as i64 >= i64::from(config.file_upload_limit))
+        || size >= i64::from(config.file_size_limit_in_gb) * 1024 * 1024 * 1024
+}
+
+/// Opens the physical file an upload is written to and returns it with a hasher, which already
+/// digested the data kept from the existing file.
+///
+/// * `path`     - The path of the physical file.
+/// * `existing` - Whether the physical file belongs to the uploaded file.
+/// * `mode`     - The [UploadMode].
+async fn open_upload(path: &Path, existing: bool, mode: UploadMode) -> io::Result<(File, Sha256)> {
+    let kept = match (existing, mode) {
+        (true, UploadMode::Append) => fs::metadata(path).await?.len(),
+        (true, UploadMode::Resume(offset)) => offset,
+        _ => return Ok((File::create(path).await?, Sha256::new())),
+    };
+
+    let mut hasher = Sha256::new();
+    let mut prefix = File::open(path).await?.take(kept);
+    let mut buffer = [0; 8192];
+    loop {
+        let bytes_read = prefix.read(&mut buffer).await?;
+        if bytes_read == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..bytes_read]);
+    }
+
+    let file = match mode {
+        UploadMode::Append => OpenOptions::new().append(true).open(path).await?,
+        _ => {
+            let mut file = OpenOptions::new().write(true).open(path).await?;
+            file.set_len(kept).await?;
+            file.seek(SeekFrom::Start(kept)).await?;
+            file
+        }
+    };
+
+    Ok((file, hasher))
+}
+
+/// Tells the client that the requested action was aborted because of a local error.
+///
+/// * `stream` - The [FtpStream] used to communicate with the client.
+async fn local_error(stream: &mut FtpStream) -> bool {
+    send_response(
+        stream,
+        StatusCode::LocalErrorInProcessing,
+        "Requested action aborted: local error in processing.",
+    )
+    .await
+}
+
+/// Receives an upload over a new data connection and stores it.
+///
+/// Uploads of attackers that reached the [upload limits][upload_limit_reached] are refused.
+/// For [UploadMode::Store] or if the fake file has no physical file of its own, a random filename
+/// is stored on the systems filesystem. Otherwise the physical file of the uploaded file is
+/// appended to or overwritten from the restart offset on, a restart offset beyond its end is
+/// refused. Then another TCP connection is established to transfer the data over the new data
+/// stream. The client is informed about that and the upload can begin. The data from the TCP
+/// stream is saved to the system and hashed along the way and the TCP stream is then terminated.
+/// After that the hash and size of the physical file are stored to the [FtpFileSystem] and if
+/// configured the file is deleted again from the system. Finally the client is told about the
+/// termination of the TCP connection. Errors writing the physical file abort the upload with a
+/// local error.
+/// If the transfer fails or times out, the data received so far is kept and the transfer is
+/// aborted. The upload is [captured][DataCapture] up to the configured maximum payload.
+///
+/// * `stream`          - The [FtpStream] used to communicate with the client.
+/// * `client`          - The current [Client] struct.
+/// * `db`              - The [DatabaseImplementation]
+/// * `fake_path`       - The path of the file in the [FtpFileSystem].
+/// * `mode`            - The [UploadMode].
+/// * `ready_message`   - The message the client is told the upload can begin with.
+async fn receive_file(
+    stream: &mut FtpStream,
+    client: &mut Client,
+    db: &DatabaseImplementation,
+    fake_path: &str,
+    mode: UploadMode,
+    ready_message: &str,
+) -> bool {
+    let model = client.model.as_ref().unwrap();
+    let public = model.file_system.as_ref().is_some_and(|fs| fs.public);
+    let owner = (!public).then_some(model.id);
+    if upload_limit_reached(db, owner, mode == UploadMode::Store).await {
+        info!("Attacker: {} reached the upload limit", client.username);
+        client.data_connection = None;
+
+        return send_response(
+            stream,
+            StatusCode::ExceededStorageAllocation,
+            "Exceeded storage allocation.",
+        )
+        .await;
+    }
+
+    let model = client.model.as_mut().unwrap();
+    let file_system = model.file_system.as_mut().expect("Filesystem not set!");
+
+    let uploaded_file = match mode {
+        UploadMode::Store => None,
+        _ => match file_system.get_uploaded_file_id(fake_path) {
+            Some(file_id) => db.get_file_by_id(file_id).await.filter(|file| {
+                file.location
+                    .as_ref()
+                    .is_some_and(|l| Path::new(l).exists())
+            }),
+            None => None,
+        },
+    };
+
+    let real_path = match uploaded_file
+        .as_ref()
+        .and_then(|file| file.location.clone())
+    {
+        Some(location) => location,
+        None => {
+            let random_filename: String = rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(7)
+                .map(char::from)
+                .collect();
+
+            get_real_file_path(model, random_filename)
+        }
+    };
+
+    let file_path_real = Path::new(real_path.as_str());
+
+    if let UploadMode::Resume(offset) = mode {
+        let size = match uploaded_file {
+            Some(_) => file_path_real
+                .metadata()
+                .map_or(0, |metadata| metadata.len()),
+            None => 0,
+        };
+
+        if offset > size {
+            client.data_connection = None;
+
+            return send_response(
                 stream,
-                StatusCode::DirectoryCreationFailed,
-                "Failed to change directory.",
+                StatusCode::RequestedActionNotTaken,
+                "Requested action not taken: invalid REST parameter.",
             )
-            .await
-            {
-                return false;
+            .await;
+        }
+    }
+
+    let (mut file, mut hasher) =
+        match open_upload(file_path_real, uploaded_file.is_some(), mode).await {
+            Ok(opened) => opened,
+            Err(e) => {
+                error!("Failed opening the file {}: {}", real_path, e);
+                client.data_connection = None;
+                return local_error(stream).await;
             }
+        };
 
-            true
-        } else {
-            if !send_response(
-                stream,
-                StatusCode::RequestedFileActionOkayCompleted,
-                "Directory successfully changed.",
-            )
-            .await
-            {
-                return false;
+    let (mut tcp, addresses) = match get_data_tcp(client).await {
+        Some(data) => data,
+        None => return deny_data_connection(stream).await,
+    };
+
+    if !send_response(stream, StatusCode::FileStatusOkay, ready_message).await {
+        return false;
+    }
+
+    let mut capture = DataCapture::new(addresses);
+    let completed = finish_transfer(async {
+        let mut buffer = [0; 1024];
+        loop {
+            let bytes_read = tcp.read(&mut buffer).await?;
+            if bytes_read == 0 {
+                break;
             }
 
-            true
-        };
+            capture.capture(Sender::Client, &buffer[..bytes_read]);
+            file.write_all(&buffer[..bytes_read]).await?;
+            hasher.update(&buffer[..bytes_read]);
+        }
+
+        Ok(())
+    })
+    .await;
+    capture.finish();
+
+    if let Err(e) = tcp.shutdown().await {
+        error!("Data connection error: {}", e);
+    }
+
+    let size = match file.flush().await.and(file.metadata().await) {
+        Ok(metadata) => metadata.len() as i64,
+        Err(e) => {
+            error!("Failed writing the file {}: {}", real_path, e);
+            return local_error(stream).await;
+        }
+    };
+    let hash = hex::encode(hasher.finalize());
+
+    let model = client.model.as_mut().unwrap();
+    let file_system = model.file_system.as_mut().expect("Filesystem not set!");
+
+    let change = match uploaded_file {
+        Some(uploaded_file) => {
+            file_system
+                .update_uploaded_file(db, model.id, uploaded_file, fake_path, size, hash)
+                .await
+        }
+        None => {
+            file_system
+                .save_file(
+                    db,
+                    model.id,
+                    file_path_real.to_str().unwrap(),
+                    fake_path,
+                    size,
+                    hash,
+                )
+                .await
+        }
+    };
+    record_change(change);
+
+    info!(
+        "Attacker: {} uploaded File: {:?}",
+        client.username, fake_path
+    );
+
+    if !get_config().file_upload_real {
+        if let Err(e) = fs::remove_file(file_path_real).await {
+            error!("Failed removing the file {}: {}", real_path, e);
+        }
+    }
+
+    if !completed {
+        return abort_transfer(stream).await;
+    }
+
+    if !send_response(
+        stream,
+        StatusCode::ClosingDataConnection,
+        "Transfer complete.",
+    )
+    .await
+    {
+        return false;
     }
 
     true
@@ -107,19 +573,15 @@ pub async fn cwd(stream: &mut TcpStream, client: &mut Client, request: &Request)
 
 /// Handles the FTP STOR packet.
 ///
-/// First another TCP connection is established to transfer the data over the new data stream.
-/// The client is informed about that and the upload can begin.
-/// A random filename is stored on the systems filesystem and the data from the TCP stream is saved
-/// to the system. The TCP stream is then terminated. After that the file's metadata is also stored
-/// to the [FtpFileSystem] and if configured the file is deleted again from the system. Finally
-/// the client is told about the termination of the TCP connection.
+/// The upload is [received][receive_file] as a new file. If a restart offset was set with REST,
+/// the physical file of the existing upload is overwritten from the offset on.
 ///
-/// * `stream`   - The [TcpStream] used to communicate with the client.
+/// * `stream`   - The [FtpStream] used to communicate with the client.
 /// * `client`   - The current [Client] struct.
 /// * `db`       - The [DatabaseImplementation]
 /// * `request`  - The [Request] used to handle the argument.
 pub async fn stor(
-    stream: &mut TcpStream,
+    stream: &mut FtpStream,
     client: &mut Client,
     db: &DatabaseImplementation,
     request: &Request,
@@ -129,79 +591,161 @@ pub async fn stor(
             return false;
         }
     } else {
-        let mut tcp = get_data_tcp(client).await;
-
-        if !send_response(stream, StatusCode::FileStatusOkay, "Ready to receive data").await {
-            return false;
-        }
-
-        let model = client.model.as_mut().unwrap();
-
-        let fake_path = Path::new(request.argument.as_str());
+        let mode = match std::mem::take(&mut client.restart_offset) {
+            0 => UploadMode::Store,
+            offset => UploadMode::Resume(offset),
+        };
 
-        let random_filename: String = rand::thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(7)
-            .map(char::from)
-            .collect();
+        return receive_file(
+            stream,
+            client,
+            db,
+            request.argument.as_str(),
+            mode,
+            "Ready to receive data",
+        )
+        .await;
+    }
 
-        let real_path = get_real_file_path(model, random_filename);
+    true
+}
 
-        let file_path_real = Path::new(real_path.as_str());
+/// Handles the FTP APPE packet.
+///
+/// The upload is [received][receive_file] and appended to the physical file of the existing
+/// upload. If the file doesn't exist yet, it is created.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `db`       - The [DatabaseImplementation]
+/// * `request`  - The [Request] used to handle the argument.
+pub async fn appe(
+    stream: &mut FtpStream,
+    client: &mut Client,
+    db: &DatabaseImplementation,
+    request: &Request,
+) -> bool {
+    if client.model.is_none() {
+        if !deny_access(stream).await {
+            return false;
+        }
+    } else {
+        client.restart_offset = 0;
 
-        let mut file = File::create(&file_path_real).await.unwrap();
+        return receive_file(
+            stream,
+            client,
+            db,
+            request.argument.as_str(),
+            UploadMode::Append,
+            "Ready to receive data",
+        )
+        .await;
+    }
 
-        let mut buffer = [0; 1024];
-        loop {
-            let bytes_read = tcp.read(&mut buffer).await.unwrap();
-            if bytes_read == 0 {
-                break;
-            }
+    true
+}
 
-            file.write_all(&buffer[..bytes_read]).await.unwrap();
+/// Handles the FTP STOU packet.
+///
+/// A unique filename is generated in the current directory, based on the argument if one is
+/// given. The client is told the filename and the upload is [received][receive_file] as a new file.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `db`       - The [DatabaseImplementation]
+/// * `request`  - The [Request] used to handle the argument.
+pub async fn stou(
+    stream: &mut FtpStream,
+    client: &mut Client,
+    db: &DatabaseImplementation,
+    request: &Request,
+) -> bool {
+    if client.model.is_none() {
+        if !deny_access(stream).await {
+            return false;
         }
+    } else {
+        client.restart_offset = 0;
 
-        let contents = fs::read(&real_path).await;
-
-        let hash = sha256::digest(contents.unwrap().as_slice());
-
-        tcp.shutdown().await.unwrap();
+        let base_name = match request.argument.rsplit('/').next() {
+            Some(base_name) if !base_name.is_empty() => base_name.to_string(),
+            _ => "file".to_string(),
+        };
 
-        model
+        let file_system = client
+            .model
+            .as_mut()
+            .unwrap()
             .file_system
             .as_mut()
-            .expect("Filesystem not set!")
-            .save_file(
-                db,
-                model.id,
-                file_path_real.to_str().unwrap(),
-                fake_path.to_str().unwrap(),
-                file_path_real.metadata().unwrap().len() as i64,
-                hash,
-            )
-            .await;
+            .expect("Filesystem not set!");
 
-        info!(
-            "Attacker: {} uploaded File: {:?}",
-            client.username,
-            fake_path.to_str()
-        );
+        let mut unique_name = base_name.clone();
+        while file_system.exists(unique_name.as_str()) {
+            let suffix: String = rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(6)
+                .map(char::from)
+                .collect();
 
-        if !get_config().file_upload_real {
-            fs::remove_file(file_path_real)
-                .await
-                .expect("Failed removing the file!");
+            unique_name = format!("{}.{}", base_name, suffix);
         }
 
-        if !send_response(
+        return receive_file(
             stream,
-            StatusCode::ClosingDataConnection,
-            "Transfer complete.",
+            client,
+            db,
+            unique_name.as_str(),
+            UploadMode::Store,
+            format!("FILE: {}", unique_name).as_str(),
         )
-        .await
-        {
+        .await;
+    }
+
+    true
+}
+
+/// Handles the FTP REST packet.
+///
+/// The restart offset is stored for the next RETR or STOR, so interrupted transfers can be
+/// resumed. Invalid offsets are rejected.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `request`  - The [Request] used to handle the argument.
+pub async fn rest(stream: &mut FtpStream, client: &mut Client, request: &Request) -> bool {
+    if client.model.is_none() {
+        if !deny_access(stream).await {
             return false;
         }
+    } else {
+        match request.argument.trim().parse::<u64>() {
+            Ok(offset) => {
+                client.restart_offset = offset;
+
+                if !send_response(
+                    stream,
+                    StatusCode::RequestedFileActionPendingFurtherInformation,
+                    format!("Restart position accepted ({}).", offset).as_str(),
+                )
+                .await
+                {
+                    return false;
+                }
+            }
+            Err(_) => {
+                if !send_response(
+                    stream,
+                    StatusCode::SyntaxErrorInParameters,
+                    "Invalid restart position.",
+                )
+                .await
+                {
+                    return false;
+                }
+            }
+        }
     }
 
     true
@@ -214,188 +758,452 @@ pub async fn stor(
 /// to the client. If configured the directory is also created on the system itself and not only
 /// on the [FtpFileSystem].
 ///
-/// * `stream`   - The [TcpStream] used to communicate with the client.
+/// * `stream`   - The [FtpStream] used to communicate with the client.
 /// * `client`   - The current [Client] struct.
 /// * `db`       - The [DatabaseImplementation]
 /// * `request`  - The [Request] used to handle the argument.
 pub async fn mkd(
-    stream: &mut TcpStream,
+    stream: &mut FtpStream,
     client: &mut Client,
     db: &DatabaseImplementation,
     request: &Request,
 ) -> bool {
-    if client.model.is_none() {
-        if !deny_access(stream).await {
-            return false;
+    match client.model.as_mut() {
+        None => {
+            if !deny_access(stream).await {
+                return false;
+            }
         }
-    } else {
-        let mut new_dir = request.argument.to_string();
+        Some(model) => {
+            let mut new_dir = request.argument.to_string();
+
+            let success = record_change(
+                model
+                    .file_system
+                    .as_mut()
+                    .expect("Filesystem not set!")
+                    .save_dir(db, model.id, new_dir.as_str())
+                    .await,
+            );
 
-        let model = client.model.as_mut().unwrap();
-        let success = model
-            .file_system
-            .as_mut()
-            .expect("Filesystem not set!")
-            .save_dir(db, model.id, new_dir.as_str())
-            .await;
+            if success {
+                new_dir = get_real_file_path(model, new_dir);
 
-        if success {
-            new_dir = get_real_file_path(model, new_dir);
+                info!(
+                    "Attacker: {} created a new dir: {}",
+                    client.username, new_dir
+                );
 
-            info!(
-                "Attacker: {} created a new dir: {}",
-                client.username, new_dir
-            );
+                if get_config().file_upload_real {
+                    let path = Path::new(&new_dir);
 
-            if get_config().file_upload_real {
-                let path = Path::new(&new_dir);
+                    if !path.exists() {
+                        fs::create_dir_all(path)
+                            .await
+                            .expect("Could not create directory");
+                    }
+                }
 
-                if !path.exists() {
-                    fs::create_dir_all(path)
-                        .await
-                        .expect("Could not create directory");
+                if !send_response(
+                    stream,
+                    StatusCode::PathnameAvailable,
+                    "Create directory operation successful.",
+                )
+                .await
+                {
+                    return false;
                 }
+            } else if !send_response(
+                stream,
+                StatusCode::DirectoryCreationFailed,
+                "Create directory operation failed.",
+            )
+            .await
+            {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Handles the FTP PWD packet.
+///
+/// The [FtpFileSystem] returns the current path, which is then sent to the client.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+pub async fn pwd(stream: &mut FtpStream, client: &mut Client) -> bool {
+    match client.model.as_mut() {
+        None => {
+            if !deny_access(stream).await {
+                return false;
             }
+        }
+        Some(model) => {
+            let current_path = format!(
+                "/{}",
+                model
+                    .file_system
+                    .as_mut()
+                    .expect("Filesystem not set!")
+                    .path
+                    .join("/")
+            );
+
+            info!("Attacker: {} used PWD", client.username);
 
             if !send_response(
                 stream,
                 StatusCode::PathnameAvailable,
-                "Create directory operation successful.",
+                format!("\"{}\" is the current directory", current_path).as_str(),
             )
             .await
             {
                 return false;
             }
-        } else if !send_response(
-            stream,
-            StatusCode::DirectoryCreationFailed,
-            "Create directory operation failed.",
-        )
-        .await
-        {
-            return false;
-        }
+        }
+    }
+
+    true
+}
+
+/// Handles the FTP LIST packet.
+///
+/// The [FtpFileSystem] returns the list of files currently listed within the current directory.
+/// If a argument to a valid path is given the [FtpFileSystem] returns the files within the
+/// given directory. The `-a` argument is also handled and returns all files when the client requests
+/// them. The listing is in the format of the
+/// [Personality][crate::honeypot::personality::Personality].
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `request`  - The [Request] used to handle the argument.
+pub async fn list(stream: &mut FtpStream, client: &mut Client, request: &Request) -> bool {
+    if client.model.is_none() {
+        if !deny_access(stream).await {
+            return false;
+        }
+    } else {
+        let model = client.model.as_mut().unwrap();
+        let file_system = model.file_system.as_mut().expect("Filesystem not set!");
+
+        let dir_to_ls_into = request.argument.to_string();
+
+        let re = Regex::new(r"-.*a.*").unwrap();
+        let dir_list = if get_personality().listing == ListingFormat::Msdos {
+            // The Microsoft FTP Service ignores options like -a.
+            let path = if dir_to_ls_into.starts_with('-') {
+                ""
+            } else {
+                dir_to_ls_into.as_str()
+            };
+
+            file_system.ls_msdos_with_str(path).unwrap_or_default()
+        } else if re.is_match(dir_to_ls_into.as_str()) {
+            file_system.ls_minus_a_extended_information(model.id)
+        } else if !dir_to_ls_into.is_empty() {
+            file_system
+                .ls_extended_information_with_str(model.id, dir_to_ls_into.as_str())
+                .unwrap_or("".to_string())
+        } else {
+            file_system.ls_extended_information(model.id)
+        };
+
+        info!(
+            "Attacker: {} used ls with the following argument: {}",
+            client.username, dir_to_ls_into
+        );
+
+        return send_listing(stream, client, dir_list).await;
+    }
+
+    true
+}
+
+/// Handles the FTP STAT packet.
+///
+/// Without argument the status of the server and the session is sent to the client, as the
+/// server of the [Personality][crate::honeypot::personality::Personality] would. With a path as
+/// argument, the [FtpFileSystem] returns the long listing of the path, which is sent over the
+/// control connection instead of a data connection.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `request`  - The [Request] used to handle the argument.
+pub async fn stat(stream: &mut FtpStream, client: &mut Client, request: &Request) -> bool {
+    match client.model.as_mut() {
+        None => {
+            if !deny_access(stream).await {
+                return false;
+            }
+        }
+        Some(_) if request.argument.is_empty() => {
+            let status = get_personality().status(
+                &client.ip,
+                &client.username,
+                stream.is_tls(),
+                client.private_data,
+            );
+
+            return write_packet(
+                stream,
+                &Reply::new(StatusCode::SystemStatus, ReplyMessage::Lines(status)),
+            )
+            .await;
+        }
+        Some(model) => {
+            let file_system = model.file_system.as_mut().expect("Filesystem not set!");
+            let dir_list = match get_personality().listing {
+                ListingFormat::Unix => file_system
+                    .ls_extended_information_with_str(model.id, request.argument.as_str()),
+                ListingFormat::Msdos => file_system.ls_msdos_with_str(request.argument.as_str()),
+            }
+            .unwrap_or_default();
+
+            info!(
+                "Attacker: {} used stat with the following argument: {}",
+                client.username, request.argument
+            );
+
+            let mut lines = vec!["Status follows:".to_string()];
+            lines.extend(dir_list.lines().map(str::to_string));
+            lines.push("End of status".to_string());
+
+            return write_packet(
+                stream,
+                &Reply::new(StatusCode::FileStatus, ReplyMessage::Lines(lines)),
+            )
+            .await;
+        }
+    }
+
+    true
+}
+
+/// Handles the FTP NLST packet.
+///
+/// The [FtpFileSystem] returns the names of the files and directories within the current
+/// directory, or within the directory given as argument. Arguments starting with `-` are treated
+/// as options and list the current directory. A path that is no directory returns an error.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `request`  - The [Request] used to handle the argument.
+pub async fn nlst(stream: &mut FtpStream, client: &mut Client, request: &Request) -> bool {
+    if client.model.is_none() {
+        if !deny_access(stream).await {
+            return false;
+        }
+    } else {
+        let model = client.model.as_mut().unwrap();
+        let file_system = model.file_system.as_mut().expect("Filesystem not set!");
+
+        let dir_to_ls_into = request.argument.to_string();
+
+        let name_list = if dir_to_ls_into.is_empty() || dir_to_ls_into.starts_with('-') {
+            Some(file_system.ls())
+        } else {
+            file_system.ls_path(dir_to_ls_into.as_str())
+        };
+
+        info!(
+            "Attacker: {} used nlst with the following argument: {}",
+            client.username, dir_to_ls_into
+        );
+
+        return match name_list {
+            Some(name_list) => send_listing(stream, client, name_list).await,
+            None => {
+                send_response(
+                    stream,
+                    StatusCode::DirectoryCreationFailed,
+                    "No such directory.",
+                )
+                .await
+            }
+        };
     }
+
     true
 }
 
-/// Handles the FTP PWD packet.
+/// Handles the FTP MLSD packet.
 ///
-/// The [FtpFileSystem] returns the current path, which is then sent to the client.
+/// The [FtpFileSystem] returns the RFC 3659 facts of the files and directories within the
+/// current directory, or within the directory given as argument. A path that is no directory
+/// returns an error.
 ///
-/// * `stream`   - The [TcpStream] used to communicate with the client.
+/// * `stream`   - The [FtpStream] used to communicate with the client.
 /// * `client`   - The current [Client] struct.
-pub async fn pwd(stream: &mut TcpStream, client: &mut Client) -> bool {
+/// * `request`  - The [Request] used to handle the argument.
+pub async fn mlsd(stream: &mut FtpStream, client: &mut Client, request: &Request) -> bool {
     if client.model.is_none() {
         if !deny_access(stream).await {
             return false;
         }
     } else {
         let model = client.model.as_mut().unwrap();
-        let current_path = format!(
-            "/{}",
-            model
-                .file_system
-                .as_mut()
-                .expect("Filesystem not set!")
-                .path
-                .join("/")
-        );
+        let file_system = model.file_system.as_mut().expect("Filesystem not set!");
+
+        let dir_to_ls_into = request.argument.to_string();
 
-        info!("Attacker: {} used PWD", client.username);
+        let fact_list = if dir_to_ls_into.is_empty() {
+            Some(file_system.mlsd(model.id))
+        } else {
+            file_system.mlsd_with_str(model.id, dir_to_ls_into.as_str())
+        };
 
-        if !send_response(
-            stream,
-            StatusCode::PathnameAvailable,
-            format!("\"{}\" is the current directory", current_path).as_str(),
-        )
-        .await
-        {
-            return false;
-        }
+        info!(
+            "Attacker: {} used mlsd with the following argument: {}",
+            client.username, dir_to_ls_into
+        );
+
+        return match fact_list {
+            Some(fact_list) => send_listing(stream, client, fact_list).await,
+            None => {
+                send_response(
+                    stream,
+                    StatusCode::DirectoryCreationFailed,
+                    "No such directory.",
+                )
+                .await
+            }
+        };
     }
 
     true
 }
 
-/// Handles the FTP LIST packet.
+/// Handles the FTP SIZE packet.
 ///
-/// The [FtpFileSystem] returns the list of files currently listed within the current directory.
-/// If a argument to a valid path is given the [FtpFileSystem] returns the files within the
-/// given directory. The `-a` argument is also handled and returns all files when the client requests
-/// them.
+/// The size of the file given as argument is looked up in the [FtpFileSystem] and sent to the
+/// client. Directories have no size.
 ///
-/// * `stream`   - The [TcpStream] used to communicate with the client.
+/// * `stream`   - The [FtpStream] used to communicate with the client.
 /// * `client`   - The current [Client] struct.
 /// * `request`  - The [Request] used to handle the argument.
-pub async fn list(stream: &mut TcpStream, client: &mut Client, request: &Request) -> bool {
-    if client.model.is_none() {
-        if !deny_access(stream).await {
-            return false;
+pub async fn size(stream: &mut FtpStream, client: &mut Client, request: &Request) -> bool {
+    match client.model.as_mut() {
+        None => {
+            if !deny_access(stream).await {
+                return false;
+            }
         }
-    } else {
-        let model = client.model.as_mut().unwrap();
-        let mut dir_list = model
-            .file_system
-            .as_mut()
-            .expect("Filesystem not set!")
-            .ls_extended_information(model.id);
-
-        let dir_to_ls_into = request.argument.to_string();
-
-        if dir_to_ls_into != "\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0" {
-            dir_list = model
+        Some(model) => {
+            let size = model
                 .file_system
                 .as_mut()
                 .expect("Filesystem not set!")
-                .ls_extended_information_with_str(model.id, dir_to_ls_into.as_str())
-                .unwrap_or("".to_string());
+                .get_file(request.argument.as_str())
+                .map(|file| file.size);
+
+            return match size {
+                Some(size) => {
+                    send_response(stream, StatusCode::FileStatus, &size.to_string()).await
+                }
+                None => {
+                    send_response(
+                        stream,
+                        StatusCode::DirectoryCreationFailed,
+                        "Could not get file size.",
+                    )
+                    .await
+                }
+            };
         }
+    }
 
-        let re = Regex::new(r"-.*a.*").unwrap();
-        if re.is_match(dir_to_ls_into.as_str()) {
-            dir_list = model
+    true
+}
+
+/// Handles the FTP MDTM packet.
+///
+/// The modification time of the file given as argument is looked up in the [FtpFileSystem] and
+/// sent to the client in the form YYYYMMDDHHMMSS.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `request`  - The [Request] used to handle the argument.
+pub async fn mdtm(stream: &mut FtpStream, client: &mut Client, request: &Request) -> bool {
+    match client.model.as_mut() {
+        None => {
+            if !deny_access(stream).await {
+                return false;
+            }
+        }
+        Some(model) => {
+            let modified = model
                 .file_system
                 .as_mut()
                 .expect("Filesystem not set!")
-                .ls_minus_a_extended_information(model.id);
-        }
-
-        if !send_response(
-            stream,
-            StatusCode::FileStatusOkay,
-            "Here comes the directory listing.",
-        )
-        .await
-        {
-            return false;
+                .get_file(request.argument.as_str())
+                .map(|file| mlsx_timestamp(&file.timestamp));
+
+            return match modified {
+                Some(modified) => send_response(stream, StatusCode::FileStatus, &modified).await,
+                None => {
+                    send_response(
+                        stream,
+                        StatusCode::DirectoryCreationFailed,
+                        "Could not get file modification time.",
+                    )
+                    .await
+                }
+            };
         }
+    }
 
-        let mut tcp = get_data_tcp(client).await;
+    true
+}
 
-        if dir_list.is_empty() {
-            tcp.write_all("".as_bytes())
-                .await
-                .expect("Panicked during write_all!!");
-        } else {
-            tcp.write_all(format!("{}\r\n", dir_list).as_bytes())
-                .await
-                .expect("Panicked during write_all!!");
+/// Handles the FTP MLST packet.
+///
+/// The [FtpFileSystem] returns the RFC 3659 facts of the file or directory given as argument, or
+/// of the current directory. The facts are sent over the control connection.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `request`  - The [Request] used to handle the argument.
+pub async fn mlst(stream: &mut FtpStream, client: &mut Client, request: &Request) -> bool {
+    match client.model.as_mut() {
+        None => {
+            if !deny_access(stream).await {
+                return false;
+            }
         }
+        Some(model) => {
+            let facts = model
+                .file_system
+                .as_mut()
+                .expect("Filesystem not set!")
+                .mlst_with_str(model.id, request.argument.as_str());
 
-        info!(
-            "Attacker: {} used ls with the following argument: {}",
-            client.username, dir_to_ls_into
-        );
+            info!(
+                "Attacker: {} used mlst with the following argument: {}",
+                client.username, request.argument
+            );
 
-        if !send_response(
-            stream,
-            StatusCode::ClosingDataConnection,
-            "Directory send OK.",
-        )
-        .await
-        {
-            return false;
+            return match facts {
+                Some(facts) => {
+                    send_multiline_response(
+                        stream,
+                        StatusCode::RequestedFileActionOkayCompleted,
+                        format!("Listing {}", request.argument).trim_end(),
+                        &[facts],
+                        "End",
+                    )
+                    .await
+                }
+                None => {
+                    send_response(
+                        stream,
+                        StatusCode::DirectoryCreationFailed,
+                        "No such file or directory.",
+                    )
+                    .await
+                }
+            };
         }
     }
 
@@ -408,88 +1216,250 @@ pub async fn list(stream: &mut TcpStream, client: &mut Client, request: &Request
 /// the file. If the file exists it also gets removed from the actual system. Otherwise an error
 /// is sent to the client.
 ///
-/// * `stream`   - The [TcpStream] used to communicate with the client.
+/// * `stream`   - The [FtpStream] used to communicate with the client.
 /// * `client`   - The current [Client] struct.
 /// * `db`       - The [DatabaseImplementation]
 /// * `request`  - The [Request] used to handle the argument.
 pub async fn dele(
-    stream: &mut TcpStream,
+    stream: &mut FtpStream,
     client: &mut Client,
     db: &DatabaseImplementation,
     request: &Request,
 ) -> bool {
-    if client.model.is_none() {
-        if !deny_access(stream).await {
-            return false;
+    match client.model.as_mut() {
+        None => {
+            if !deny_access(stream).await {
+                return false;
+            }
         }
-    } else {
-        let file_to_delete = request.argument.to_string();
+        Some(model) => {
+            let file_to_delete = request.argument.to_string();
 
-        let model = client.model.as_mut().unwrap();
+            let real_file_to_delete = model
+                .file_system
+                .as_mut()
+                .expect("Filesystem not set!")
+                .get_physical_file_path(db, file_to_delete.as_str())
+                .await;
+
+            let success = record_change(
+                model
+                    .file_system
+                    .as_mut()
+                    .expect("Filesystem not set!")
+                    .rm_file(db, model.id, file_to_delete.as_str())
+                    .await,
+            );
 
-        let real_file_to_delete = model
-            .file_system
-            .as_mut()
-            .expect("Filesystem not set!")
-            .get_physical_file_path(db, file_to_delete.as_str())
-            .await;
+            info!(
+                "Attacker: {} attempted to delete: {}",
+                client.username, file_to_delete
+            );
 
-        let success = model
-            .file_system
-            .as_mut()
-            .expect("Filesystem not set!")
-            .rm_file(db, model.id, file_to_delete.as_str())
-            .await;
+            if success {
+                if get_config().file_upload_real {
+                    fs::remove_file(real_file_to_delete.1.unwrap())
+                        .await
+                        .expect("Could not delete file");
+                }
 
-        info!(
-            "Attacker: {} attempted to delete: {}",
-            client.username, file_to_delete
-        );
+                if !send_response(
+                    stream,
+                    StatusCode::RequestedFileActionOkayCompleted,
+                    "File removed.",
+                )
+                .await
+                {
+                    return false;
+                }
+            } else if !send_response(
+                stream,
+                StatusCode::DirectoryCreationFailed,
+                "File not removed.",
+            )
+            .await
+            {
+                return false;
+            }
+        }
+    }
+    true
+}
 
-        if success {
-            if get_config().file_upload_real {
-                fs::remove_file(real_file_to_delete.1.unwrap())
-                    .await
-                    .expect("Could not delete file");
+/// Handles the FTP RNFR packet.
+///
+/// If the [FtpFileSystem] knows the file or directory, it is remembered for the following RNTO and
+/// the client is asked for the new name. Otherwise an error is sent to the client.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `request`  - The [Request] used to handle the argument.
+pub async fn rnfr(stream: &mut FtpStream, client: &mut Client, request: &Request) -> bool {
+    match client.model.as_mut() {
+        None => {
+            if !deny_access(stream).await {
+                return false;
+            }
+        }
+        Some(model) => {
+            let exists = model
+                .file_system
+                .as_mut()
+                .expect("Filesystem not set!")
+                .exists(request.argument.as_str());
+
+            if !exists {
+                client.rename_from = None;
+
+                return send_response(
+                    stream,
+                    StatusCode::DirectoryCreationFailed,
+                    "RNFR command failed.",
+                )
+                .await;
             }
 
+            client.rename_from = Some(request.argument.to_string());
+
             if !send_response(
                 stream,
-                StatusCode::RequestedFileActionOkayCompleted,
-                "File removed.",
+                StatusCode::RequestedFileActionPendingFurtherInformation,
+                "Ready for RNTO.",
+            )
+            .await
+            {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+/// Handles the FTP RNTO packet.
+///
+/// The file or directory given with the previous RNFR is renamed or moved by the [FtpFileSystem].
+/// The old and new name are stored as an event, since rename targets show the attackers intent.
+/// Without a previous RNFR an error is sent to the client.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `db`       - The [DatabaseImplementation]
+/// * `request`  - The [Request] used to handle the argument.
+pub async fn rnto(
+    stream: &mut FtpStream,
+    client: &mut Client,
+    db: &DatabaseImplementation,
+    request: &Request,
+) -> bool {
+    match client.model.as_mut() {
+        None => {
+            if !deny_access(stream).await {
+                return false;
+            }
+        }
+        Some(model) => {
+            let rename_from = match client.rename_from.take() {
+                Some(rename_from) => rename_from,
+                None => {
+                    return send_response(
+                        stream,
+                        StatusCode::BadSequenceOfCommands,
+                        "RNFR required first.",
+                    )
+                    .await;
+                }
+            };
+
+            let success = record_change(
+                model
+                    .file_system
+                    .as_mut()
+                    .expect("Filesystem not set!")
+                    .rename(
+                        db,
+                        model.id,
+                        rename_from.as_str(),
+                        request.argument.as_str(),
+                    )
+                    .await,
+            );
+
+            info!(
+                "Attacker: {} attempted to rename: {} to: {}",
+                client.username, rename_from, request.argument
+            );
+
+            if success {
+                save_event(
+                    client,
+                    db,
+                    EventKind::Rename,
+                    json!({ "from": rename_from, "to": request.argument }),
+                )
+                .await;
+
+                if !send_response(
+                    stream,
+                    StatusCode::RequestedFileActionOkayCompleted,
+                    "Rename successful.",
+                )
+                .await
+                {
+                    return false;
+                }
+            } else if !send_response(
+                stream,
+                StatusCode::DirectoryCreationFailed,
+                "Rename failed.",
             )
             .await
             {
                 return false;
             }
-        } else if !send_response(
-            stream,
-            StatusCode::DirectoryCreationFailed,
-            "File not removed.",
-        )
-        .await
-        {
-            return false;
         }
     }
+
     true
 }
 
+/// Opens a file to download from the restart `offset` on.
+///
+/// Returns [None] if the offset lies beyond the end of the file.
+///
+/// * `path`   - The path of the physical file.
+/// * `offset` - The restart offset set with REST.
+async fn open_download(path: &str, offset: u64) -> io::Result<Option<File>> {
+    let mut file = File::open(path).await?;
+
+    if offset > file.metadata().await?.len() {
+        return Ok(None);
+    }
+
+    if offset > 0 {
+        file.seek(SeekFrom::Start(offset)).await?;
+    }
+
+    Ok(Some(file))
+}
+
 /// Handles the FTP RETR packet.
 ///
 /// First a new TCP data connection is created. The [FtpFileSystem] returns the actual path of the
 /// requested file from the systems file system. If the file doesn't exist an error is sent to the
-/// client. Otherwise the client is informed about the new TCP data connection. Finally the file
+/// client, as is a restart offset beyond the end of the file or an error opening it. Otherwise the
+/// client is informed about the new TCP data connection. Finally the file
 /// is sent to the client on the newly established TCP connection. Additionally if configured the
 /// server can also sent a fake file to the client. For this random bytes with the size of the selected
-/// file are sent to the client over the new TCP data connection.
+/// file are sent to the client over the new TCP data connection. The download is
+/// [captured][DataCapture] up to the configured maximum payload.
 ///
-/// * `stream`   - The [TcpStream] used to communicate with the client.
+/// * `stream`   - The [FtpStream] used to communicate with the client.
 /// * `client`   - The current [Client] struct.
 /// * `db`       - The [DatabaseImplementation]
 /// * `request`  - The [Request] used to handle the argument.
 pub async fn retr(
-    stream: &mut TcpStream,
+    stream: &mut FtpStream,
     client: &mut Client,
     db: &DatabaseImplementation,
     request: &Request,
@@ -499,15 +1469,12 @@ pub async fn retr(
             return false;
         }
     } else {
-        let model = client.model.as_mut().unwrap();
+        let (mut tcp, addresses) = match get_data_tcp(client).await {
+            Some(data) => data,
+            None => return deny_data_connection(stream).await,
+        };
 
-        let mut tcp = client
-            .data_socket
-            .take()
-            .unwrap()
-            .connect(client.data_addr.take().unwrap().parse().unwrap())
-            .await
-            .unwrap();
+        let model = client.model.as_mut().unwrap();
 
         let physical_path = model
             .file_system
@@ -524,27 +1491,52 @@ pub async fn retr(
             return true;
         }
 
+        let offset = std::mem::take(&mut client.restart_offset);
+        let mut file = match open_download(physical_path.1.as_ref().unwrap(), offset).await {
+            Ok(Some(file)) => file,
+            opened => {
+                if !physical_path.0 {
+                    let _ = fs::remove_file(physical_path.1.as_ref().unwrap()).await;
+                }
+
+                return match opened {
+                    Err(e) => {
+                        error!("Failed reading the file: {}", e);
+                        local_error(stream).await
+                    }
+                    _ => {
+                        send_response(
+                            stream,
+                            StatusCode::RequestedActionNotTaken,
+                            "Requested action not taken: invalid REST parameter.",
+                        )
+                        .await
+                    }
+                };
+            }
+        };
+
         if !send_response(stream, StatusCode::FileStatusOkay, "Sending data").await {
             return false;
         }
 
-        let mut file = File::open(physical_path.1.as_ref().unwrap())
-            .await
-            .expect("Failed to open File!");
-
-        let mut buf = vec![0; 1024];
-        loop {
-            let n = file.read(&mut buf).await.unwrap();
-            if n > 0 {
-                tcp.write_all(&buf[..n])
-                    .await
-                    .expect("Failed writing all to file!");
-            } else {
-                break;
+        let mut capture = DataCapture::new(addresses);
+        let completed = finish_transfer(async {
+            let mut buf = vec![0; 1024];
+            loop {
+                let n = file.read(&mut buf).await?;
+                if n > 0 {
+                    tcp.write_all(&buf[..n]).await?;
+                    capture.capture(Sender::Server, &buf[..n]);
+                } else {
+                    break;
+                }
             }
-        }
 
-        tcp.shutdown().await.unwrap();
+            tcp.shutdown().await
+        })
+        .await;
+        capture.finish();
 
         info!(
             "Attacker: {} downloaded File: {:?}",
@@ -558,6 +1550,10 @@ pub async fn retr(
                 .expect("Couldn't remove file!");
         }
 
+        if !completed {
+            return abort_transfer(stream).await;
+        }
+
         if !send_response(
             stream,
             StatusCode::ClosingDataConnection,
@@ -577,54 +1573,58 @@ pub async fn retr(
 /// The [FtpFileSystem] attempts to delete the directory requested by the client. Upon success the
 /// client is informed about the successful action. Upon failure an error is sent to the client.
 ///
-/// * `stream`   - The [TcpStream] used to communicate with the client.
+/// * `stream`   - The [FtpStream] used to communicate with the client.
 /// * `client`   - The current [Client] struct.
 /// * `db`       - The [DatabaseImplementation]
 /// * `request`  - The [Request] used to handle the argument.
 pub async fn rmd(
-    stream: &mut TcpStream,
+    stream: &mut FtpStream,
     client: &mut Client,
     db: &DatabaseImplementation,
     request: &Request,
 ) -> bool {
-    if client.model.is_none() {
-        if !deny_access(stream).await {
-            return false;
+    match client.model.as_mut() {
+        None => {
+            if !deny_access(stream).await {
+                return false;
+            }
         }
-    } else {
-        let dir_to_delete = request.argument.to_string();
-
-        let model = client.model.as_mut().unwrap();
-        let success = model
-            .file_system
-            .as_mut()
-            .expect("Filesystem not set!")
-            .rm_dir(db, model.id, dir_to_delete.as_str())
-            .await;
+        Some(model) => {
+            let dir_to_delete = request.argument.to_string();
+
+            let success = record_change(
+                model
+                    .file_system
+                    .as_mut()
+                    .expect("Filesystem not set!")
+                    .rm_dir(db, model.id, dir_to_delete.as_str())
+                    .await,
+            );
 
-        info!(
-            "Attacker: {} attempted to delete directory: {}",
-            client.username, dir_to_delete
-        );
+            info!(
+                "Attacker: {} attempted to delete directory: {}",
+                client.username, dir_to_delete
+            );
 
-        if success {
-            if !send_response(
+            if success {
+                if !send_response(
+                    stream,
+                    StatusCode::RequestedFileActionOkayCompleted,
+                    "Directory removed.",
+                )
+                .await
+                {
+                    return false;
+                }
+            } else if !send_response(
                 stream,
-                StatusCode::RequestedFileActionOkayCompleted,
-                "Directory removed.",
+                StatusCode::DirectoryCreationFailed,
+                "Directory not removed.",
             )
             .await
             {
                 return false;
             }
-        } else if !send_response(
-            stream,
-            StatusCode::DirectoryCreationFailed,
-            "Directory not removed.",
-        )
-        .await
-        {
-            return false;
         }
     }
     true
@@ -1,40 +1,54 @@
 //! FTP related helper functions
 
+use std::time::Duration;
+
 use log::{error, info};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
+use rand::seq::SliceRandom;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::time::sleep;
 
+use crate::configuration::config::get_config;
+use crate::database::models::captures::Sender;
+use crate::database::models::transcripts::Direction;
 use crate::honeypot::encoder::Encoder;
 use crate::honeypot::ftp::{Reply, ReplyMessage, StatusCode};
+use crate::honeypot::personality::get_personality;
+use crate::honeypot::recorder::{capture_control, record_packet};
+use crate::honeypot::stream::FtpStream;
 
-/// Reads a packet sent by the client over the communication TCP stream.
+/// Encodes the packet of a [Reply].
 ///
-/// Upon successful reading of the packet information about the packet a logged.
-/// Upon Error the error is logged.
+/// Single line replies are worded as defined by the
+/// [Personality][crate::honeypot::personality::Personality].
 ///
-/// * `stream`   - The [TcpStream] used to communicate with the client.
-/// * `buf`      - The buffer holding the packet data.
-pub async fn read_packet(stream: &mut TcpStream, buf: &mut [u8]) {
-    match stream.read(buf).await {
-        Ok(_) => {
-            info!("Read packet: {:?}", String::from_utf8_lossy(buf))
-        }
-        Err(e) => {
-            error!("Connection error: {}", e);
-        }
-    }
+/// * `reply`    - The [Reply] to the client.
+fn encode_packet(reply: &Reply) -> Vec<u8> {
+    let packet = match &reply.msg {
+        ReplyMessage::Is(message) => Encoder::encode(&Reply::new(
+            reply.code,
+            ReplyMessage::Is(get_personality().reply(reply.code, message)),
+        )),
+        ReplyMessage::Lines(_) => Encoder::encode(reply),
+    };
+
+    packet.unwrap()
 }
 
 /// Writes a packet to the client over the TCP connection.
 ///
-/// Upon successful writing of the packet information about the packet a logged.
-/// Upon Error the error is logged.
+/// Upon successful writing of the packet information about the packet a logged and the packet is
+/// added to the transcript and the capture of the session. Upon Error the error is logged.
 ///
-/// * `stream`   - The [TcpStream] used to communicate with the client.
+/// * `stream`   - The [FtpStream] used to communicate with the client.
 /// * `reply`    - The [Reply] to the client.
-pub async fn write_packet(stream: &mut TcpStream, reply: &Reply) -> bool {
-    match stream.write(&Encoder::encode(reply).unwrap()).await {
+pub async fn write_packet(stream: &mut FtpStream, reply: &Reply) -> bool {
+    let packet = encode_packet(reply);
+
+    match stream.write_all(&packet).await {
         Ok(_) => {
+            capture_control(Sender::Server, &packet);
+            record_packet(Direction::Reply, &packet);
             info!("Wrote packet: {} OK", reply.code);
             true
         }
@@ -49,11 +63,77 @@ pub async fn write_packet(stream: &mut TcpStream, reply: &Reply) -> bool {
 ///
 /// A new [Reply] is constructed and the `status` and `msg` are set accordingly.
 ///
-/// * `stream`   - The [TcpStream] used to communicate with the client.
+/// * `stream`   - The [FtpStream] used to communicate with the client.
 /// * `status`   - The current [StatusCode].
 /// * `msg`      - The message.
-pub async fn send_response(stream: &mut TcpStream, status: StatusCode, msg: &str) -> bool {
+pub async fn send_response(stream: &mut FtpStream, status: StatusCode, msg: &str) -> bool {
+    let reply = Reply::new(status, ReplyMessage::Is(String::from(msg)));
+
+    if !write_packet(stream, &reply).await {
+        return false;
+    }
+
+    true
+}
+
+/// Sends a new response to the client byte by byte, waiting `byte_delay` after each byte.
+///
+/// * `stream`       - The [FtpStream] used to communicate with the client.
+/// * `status`       - The current [StatusCode].
+/// * `msg`          - The message.
+/// * `byte_delay`   - The time to wait after each byte.
+pub async fn send_response_slowly(
+    stream: &mut FtpStream,
+    status: StatusCode,
+    msg: &str,
+    byte_delay: Duration,
+) -> bool {
     let reply = Reply::new(status, ReplyMessage::Is(String::from(msg)));
+    let packet = encode_packet(&reply);
+
+    for &byte in &packet {
+        let written = match stream.write_all(&[byte]).await {
+            Ok(_) => stream.flush().await,
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = written {
+            error!("Connection error: {}", e);
+            return false;
+        }
+
+        capture_control(Sender::Server, &[byte]);
+
+        sleep(byte_delay).await;
+    }
+
+    record_packet(Direction::Reply, &packet);
+    info!("Wrote packet slowly: {} OK", reply.code);
+    true
+}
+
+/// Sends a multi-line response to the client over the TCP connection.
+///
+/// A new [Reply] is constructed with the `lines` between the first and the last line of the
+/// reply, each indented by a single space. The `last` line may span multiple lines itself.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `status`   - The current [StatusCode].
+/// * `first`    - The first line of the reply.
+/// * `lines`    - The lines in between.
+/// * `last`     - The last line of the reply.
+pub async fn send_multiline_response(
+    stream: &mut FtpStream,
+    status: StatusCode,
+    first: &str,
+    lines: &[String],
+    last: &str,
+) -> bool {
+    let mut message = vec![first.to_string()];
+    message.extend(lines.iter().map(|line| format!(" {}", line)));
+    message.extend(last.lines().map(str::to_string));
+
+    let reply = Reply::new(status, ReplyMessage::Lines(message));
 
     if !write_packet(stream, &reply).await {
         return false;
@@ -65,8 +145,8 @@ pub async fn send_response(stream: &mut TcpStream, status: StatusCode, msg: &str
 /// Denies access to all FTP commands that require access if the attacker isn't logged in.
 ///
 ///
-/// * `stream`   - The [TcpStream] used to communicate with the client.
-pub async fn deny_access(stream: &mut TcpStream) -> bool {
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+pub async fn deny_access(stream: &mut FtpStream) -> bool {
     let reply = Reply::new(
         StatusCode::NotLoggedIn,
         ReplyMessage::Is(String::from("Please login with USER and PASS.")),
@@ -78,3 +158,37 @@ pub async fn deny_access(stream: &mut TcpStream) -> bool {
 
     true
 }
+
+/// Opens a new listener for a passive data connection.
+///
+/// A random free port out of the configured passive port range is used. The listener is bound to
+/// the local address of the control connection.
+/// Upon Error [None] is returned and the error is logged.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+pub async fn open_passive_listener(stream: &FtpStream) -> Option<TcpListener> {
+    let config = get_config();
+    let local_ip = match stream.local_addr() {
+        Ok(addr) => addr.ip(),
+        Err(e) => {
+            error!("Connection error: {}", e);
+            return None;
+        }
+    };
+
+    let mut ports: Vec<u16> = (config.passive_min_port..=config.passive_max_port).collect();
+    ports.shuffle(&mut rand::thread_rng());
+
+    for port in ports {
+        if let Ok(listener) = TcpListener::bind((local_ip, port)).await {
+            info!("Opened passive listener on port: {}", port);
+            return Some(listener);
+        }
+    }
+
+    error!(
+        "No free port for a passive listener between {} and {}!",
+        config.passive_min_port, config.passive_max_port
+    );
+    None
+}
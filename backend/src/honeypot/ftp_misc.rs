@@ -1,26 +1,37 @@
 //! FTP related misc handling functions
 
-use log::info;
-use tokio::net::{TcpSocket, TcpStream};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use log::{error, info};
+use serde_json::json;
+use strum_macros::Display;
+use tokio::net::TcpSocket;
 
 use crate::configuration::config::get_config;
-use crate::honeypot::client::Client;
-use crate::honeypot::ftp::{Request, StatusCode};
-use crate::honeypot::ftp_helper::{deny_access, send_response};
+use crate::database::models::events::EventKind;
+use crate::database::service::DatabaseImplementation;
+use crate::honeypot::client::{Client, DataConnection};
+use crate::honeypot::ftp::{Command, Request, StatusCode};
+use crate::honeypot::ftp_event::save_event;
+use crate::honeypot::ftp_helper::{
+    deny_access, open_passive_listener, send_multiline_response, send_response,
+};
+use crate::honeypot::personality::get_personality;
+use crate::honeypot::stream::FtpStream;
 
 /// Handles the FTP MODE packet.
 ///
 /// Only the Stream transfer mode is allowed. All other requests are ignored.
 ///
-/// * `stream`   - The [TcpStream] used to communicate with the client.
+/// * `stream`   - The [FtpStream] used to communicate with the client.
 /// * `client`   - The current [Client] struct.
 /// * `request`  - The [Request] used to handle the argument.
-pub async fn mode(stream: &mut TcpStream, client: &mut Client, request: &Request) -> bool {
+pub async fn mode(stream: &mut FtpStream, client: &mut Client, request: &Request) -> bool {
     if client.model.is_none() {
         if !deny_access(stream).await {
             return false;
         }
-    } else if request.argument == "S" {
+    } else if request.argument.eq_ignore_ascii_case("S") {
         if !send_response(stream, StatusCode::Okay, "Using Stream transfer mode").await {
             return false;
         }
@@ -37,16 +48,88 @@ pub async fn mode(stream: &mut TcpStream, client: &mut Client, request: &Request
     true
 }
 
+/// The amount of commands listed per line in the HELP reply.
+const HELP_COMMANDS_PER_LINE: usize = 14;
+
 /// Handles the FTP HELP packet.
 ///
-/// A help message is sent to the client.
+/// All recognized commands are listed to the client, followed by the help message of the
+/// [Personality][crate::honeypot::personality::Personality].
+/// Like on most servers, this works without being logged in.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+pub async fn help(stream: &mut FtpStream) -> bool {
+    let lines = Command::recognized()
+        .chunks(HELP_COMMANDS_PER_LINE)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .map(|command| format!("{:<4}", command))
+                .collect::<Vec<String>>()
+                .join(" ")
+        })
+        .collect::<Vec<String>>();
+
+    if !send_multiline_response(
+        stream,
+        StatusCode::HelpMessage,
+        "The following commands are recognized.",
+        &lines,
+        get_personality().help.as_str(),
+    )
+    .await
+    {
+        return false;
+    }
+
+    true
+}
+
+/// Returns the features announced by FEAT.
+///
+/// AUTH TLS, PBSZ and PROT are only announced if FTPS is enabled.
+///
+/// * `tls` - Whether AUTH TLS can be used on the connection.
+fn features(tls: bool) -> Vec<String> {
+    let mut features = vec![];
+
+    if tls {
+        features.push("AUTH TLS");
+    }
+
+    features.extend([
+        "EPRT",
+        "EPSV",
+        "MDTM",
+        "MLST type*;size*;modify*;perm*;unique*;",
+        "PASV",
+    ]);
+
+    if tls {
+        features.extend(["PBSZ", "PROT"]);
+    }
+
+    features.extend(["REST STREAM", "SIZE", "UTF8"]);
+    features.into_iter().map(str::to_string).collect()
+}
+
+/// Handles the FTP FEAT packet.
+///
+/// The supported extensions are listed to the client, framed like the server of the
+/// [Personality][crate::honeypot::personality::Personality] does. Clients send this right after
+/// connecting, so it works without being logged in.
 ///
-/// * `stream`   - The [TcpStream] used to communicate with the client.
-pub async fn help(stream: &mut TcpStream) -> bool {
-    if !send_response(
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+pub async fn feat(stream: &mut FtpStream, client: &mut Client) -> bool {
+    let (first, last) = get_personality().feat_frame();
+
+    if !send_multiline_response(
         stream,
-        StatusCode::NotLoggedIn,
-        get_config().ftp_help_message.as_str(),
+        StatusCode::SystemStatus,
+        first,
+        &features(client.tls_acceptor.is_some()),
+        last,
     )
     .await
     {
@@ -56,13 +139,51 @@ pub async fn help(stream: &mut TcpStream) -> bool {
     true
 }
 
+/// Handles the FTP OPTS packet.
+///
+/// `OPTS UTF8 ON` is accepted, as we are always in UTF8 mode. The MLST facts can be selected, but
+/// all facts are always sent. All other options are rejected.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `request`  - The [Request] used to handle the argument.
+pub async fn opts(stream: &mut FtpStream, request: &Request) -> bool {
+    let (option, value) = request
+        .argument
+        .split_once(' ')
+        .unwrap_or((request.argument.as_str(), ""));
+
+    let sent = if option.eq_ignore_ascii_case("UTF8") && value.eq_ignore_ascii_case("ON") {
+        send_response(stream, StatusCode::Okay, "Always in UTF8 mode.").await
+    } else if option.eq_ignore_ascii_case("MLST") {
+        send_response(
+            stream,
+            StatusCode::Okay,
+            format!("MLST OPTS {}", value).as_str(),
+        )
+        .await
+    } else {
+        send_response(
+            stream,
+            StatusCode::SyntaxErrorInParameters,
+            "Option not understood.",
+        )
+        .await
+    };
+
+    if !sent {
+        return false;
+    }
+
+    true
+}
+
 /// Handles the FTP NOOP packet.
 ///
 /// As the packet name already dictates, No Operation does nothing.
 ///
-/// * `stream`   - The [TcpStream] used to communicate with the client.
+/// * `stream`   - The [FtpStream] used to communicate with the client.
 /// * `client`   - The current [Client] struct.
-pub async fn noop(stream: &mut TcpStream, client: &mut Client) -> bool {
+pub async fn noop(stream: &mut FtpStream, client: &mut Client) -> bool {
     if client.model.is_none() {
         if !deny_access(stream).await {
             return false;
@@ -78,15 +199,15 @@ pub async fn noop(stream: &mut TcpStream, client: &mut Client) -> bool {
 ///
 /// Only the File structure mode is allowed. All other requests are ignored.
 ///
-/// * `stream`   - The [TcpStream] used to communicate with the client.
+/// * `stream`   - The [FtpStream] used to communicate with the client.
 /// * `client`   - The current [Client] struct.
 /// * `request`  - The [Request] used to handle the argument.
-pub async fn stru(stream: &mut TcpStream, client: &mut Client, request: &Request) -> bool {
+pub async fn stru(stream: &mut FtpStream, client: &mut Client, request: &Request) -> bool {
     if client.model.is_none() {
         if !deny_access(stream).await {
             return false;
         }
-    } else if request.argument != "F" {
+    } else if !request.argument.eq_ignore_ascii_case("F") {
         if !send_response(
             stream,
             StatusCode::CommandNotImplemented,
@@ -105,61 +226,463 @@ pub async fn stru(stream: &mut TcpStream, client: &mut Client, request: &Request
 
 /// Handles the FTP SYST packet.
 ///
-/// Returns information about the OS used. In our case this could be fake, depending on the host
-/// that runs this honeypot.
+/// Returns information about the OS used, as defined by the
+/// [Personality][crate::honeypot::personality::Personality].
 ///
-/// * `stream`   - The [TcpStream] used to communicate with the client.
-pub async fn syst(stream: &mut TcpStream, client: &mut Client) -> bool {
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+pub async fn syst(stream: &mut FtpStream, client: &mut Client) -> bool {
     if client.model.is_none() {
         if !deny_access(stream).await {
             return false;
         }
-    } else if !send_response(stream, StatusCode::NameSystemType, "UNIX Type: L8").await {
+    } else if !send_response(
+        stream,
+        StatusCode::NameSystemType,
+        get_personality().syst.as_str(),
+    )
+    .await
+    {
         return false;
     }
 
     true
 }
 
+/// Parses the argument of a PORT packet in the form h1,h2,h3,h4,p1,p2 to a [SocketAddr].
+///
+/// * `argument` - The argument of the PORT packet.
+fn parse_port_argument(argument: &str) -> Option<SocketAddr> {
+    let nums = argument
+        .trim()
+        .split(',')
+        .map(|num| num.trim().parse::<u8>())
+        .collect::<Result<Vec<u8>, _>>()
+        .ok()?;
+
+    if nums.len() != 6 {
+        return None;
+    }
+
+    let ip = Ipv4Addr::new(nums[0], nums[1], nums[2], nums[3]);
+    let port = u16::from(nums[4]) * 256 + u16::from(nums[5]);
+
+    Some(SocketAddr::new(IpAddr::V4(ip), port))
+}
+
+/// Parses the argument of an EPRT packet in the form |protocol|address|port| to a [SocketAddr].
+///
+/// The delimiter is the first character of the argument. The protocol is 1 for IPv4 and 2 for
+/// IPv6 and has to match the address.
+/// Returns the [StatusCode] to reply with if the argument is invalid.
+///
+/// * `argument` - The argument of the EPRT packet.
+fn parse_eprt_argument(argument: &str) -> Result<SocketAddr, StatusCode> {
+    let delimiter = argument
+        .chars()
+        .next()
+        .ok_or(StatusCode::SyntaxErrorInParameters)?;
+    let parts: Vec<&str> = argument.split(delimiter).collect();
+
+    if parts.len() != 5 || !parts[0].is_empty() || !parts[4].is_empty() {
+        return Err(StatusCode::SyntaxErrorInParameters);
+    }
+
+    let ip = parts[2]
+        .parse::<IpAddr>()
+        .map_err(|_| StatusCode::SyntaxErrorInParameters)?;
+    let port = parts[3]
+        .parse::<u16>()
+        .map_err(|_| StatusCode::SyntaxErrorInParameters)?;
+
+    match (parts[1], ip) {
+        ("1", IpAddr::V4(_)) | ("2", IpAddr::V6(_)) => Ok(SocketAddr::new(ip, port)),
+        ("1", _) | ("2", _) => Err(StatusCode::SyntaxErrorInParameters),
+        _ => Err(StatusCode::NetworkProtocolNotSupported),
+    }
+}
+
+/// Saves an active data connection to `addr` in the current [Client].
+///
+/// * `client`   - The current [Client] struct.
+/// * `addr`     - The address the client listens on.
+fn set_active_connection(client: &mut Client, addr: SocketAddr) -> bool {
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()
+    } else {
+        TcpSocket::new_v6()
+    };
+
+    match socket {
+        Ok(socket) => {
+            // this fixes not being able to connect from localhost to localhost
+            socket.set_reuseaddr(true).unwrap_or_default();
+
+            info!("New TCP connection: {}", addr);
+            client.data_connection = Some(DataConnection::Active(socket, addr));
+            true
+        }
+        Err(e) => {
+            error!("Could not create data socket: {}", e);
+            false
+        }
+    }
+}
+
+#[derive(Debug, Display, PartialEq)]
+#[strum(serialize_all = "snake_case")]
+/// The reasons a PORT or EPRT target is treated as FTP bounce attempt.
+enum BounceReason {
+    /// The target port is a privileged port below 1024.
+    PrivilegedPort,
+    /// The target is an internal address other than the one of the client.
+    PrivateAddress,
+    /// The target is a public address other than the one of the client.
+    ForeignAddress,
+}
+
+/// Returns whether the `ip` is only reachable from inside of a network.
+///
+/// * `ip` - The canonical target IP.
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+        }
+        IpAddr::V6(ip) => {
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (ip.segments()[0] & 0xfe00) == 0xfc00
+                || (ip.segments()[0] & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+/// Returns why the `target` of PORT or EPRT is a bounce attempt, or [None] if the client only
+/// asked to connect back to itself.
+///
+/// * `target`   - The address the client asked to connect to.
+/// * `peer`     - The IP of the control connection.
+fn bounce_reason(target: SocketAddr, peer: &str) -> Option<BounceReason> {
+    let ip = target.ip().to_canonical();
+
+    if target.port() < 1024 {
+        Some(BounceReason::PrivilegedPort)
+    } else if ip.to_string() == peer {
+        None
+    } else if is_internal(ip) {
+        Some(BounceReason::PrivateAddress)
+    } else {
+        Some(BounceReason::ForeignAddress)
+    }
+}
+
+/// Sets up the active data connection of PORT and EPRT.
+///
+/// Targets other than the client itself are FTP bounce attempts and stored as event. They are
+/// refused, or if configured, accepted but the data connection is made to the sink instead.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `db`       - The [DatabaseImplementation]
+/// * `command`  - The name of the command, PORT or EPRT.
+/// * `addr`     - The address the client listens on.
+async fn active_mode(
+    stream: &mut FtpStream,
+    client: &mut Client,
+    db: &DatabaseImplementation,
+    command: &str,
+    addr: SocketAddr,
+) -> bool {
+    let mut addr = addr;
+
+    if let Some(reason) = bounce_reason(addr, &client.ip) {
+        let config = get_config();
+        let sink = if config.bounce_sink {
+            match config.bounce_sink_address.parse::<SocketAddr>() {
+                Ok(sink) => Some(sink),
+                Err(e) => {
+                    error!(
+                        "Invalid bounce sink address {}: {}",
+                        config.bounce_sink_address, e
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        info!(
+            "Attacker: {} attempted an FTP bounce with {} to: {} ({})",
+            client.ip, command, addr, reason
+        );
+        save_event(
+            client,
+            db,
+            EventKind::Bounce,
+            json!({
+                "command": command,
+                "host": addr.ip().to_canonical().to_string(),
+                "port": addr.port(),
+                "reason": reason.to_string(),
+                "contained": sink.is_some(),
+            }),
+        )
+        .await;
+
+        match sink {
+            Some(sink) => addr = sink,
+            None if reason == BounceReason::PrivilegedPort => {
+                return send_response(
+                    stream,
+                    StatusCode::CommandNotImplementedForParameter,
+                    "Command not implemented for that parameter.",
+                )
+                .await;
+            }
+            None => {
+                return send_response(
+                    stream,
+                    StatusCode::SyntaxError,
+                    format!("Illegal {} command.", command).as_str(),
+                )
+                .await;
+            }
+        }
+    }
+
+    if !set_active_connection(client, addr) {
+        return send_response(
+            stream,
+            StatusCode::CantOpenDataConnection,
+            "Can't open data connection.",
+        )
+        .await;
+    }
+
+    send_response(
+        stream,
+        StatusCode::Okay,
+        format!("{} command successful.", command).as_str(),
+    )
+    .await
+}
+
 /// Handles the FTP PORT packet.
 ///
 /// Creates a new TCP connection as instructed by the client.
 /// The PORT request has a parameter in the form of h1,h2,h3,h4,p1,p2
 /// Meaning that the client is listening for connections on TCP port p1*256+p2 at
 /// IP address h1.h2.h3.h4. The Address is constructed, saved in the current [Client] and sent
-/// to the client.
+/// to the client. Addresses other than the one of the client are handled as FTP bounce.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `db`       - The [DatabaseImplementation]
+/// * `request`  - The [Request] used to handle the argument.
+pub async fn port(
+    stream: &mut FtpStream,
+    client: &mut Client,
+    db: &DatabaseImplementation,
+    request: &Request,
+) -> bool {
+    if client.model.is_none() {
+        if !deny_access(stream).await {
+            return false;
+        }
+    } else if let Some(addr) = parse_port_argument(&request.argument) {
+        if !active_mode(stream, client, db, "PORT", addr).await {
+            return false;
+        }
+    } else if !send_response(
+        stream,
+        StatusCode::SyntaxErrorInParameters,
+        "Illegal PORT command.",
+    )
+    .await
+    {
+        return false;
+    }
+
+    true
+}
+
+/// Handles the FTP EPRT packet.
+///
+/// Works like [port], but the argument is in the form |protocol|address|port| so IPv6
+/// addresses can be used as well. The Address is saved in the current [Client].
 ///
-/// * `stream`   - The [TcpStream] used to communicate with the client.
+/// * `stream`   - The [FtpStream] used to communicate with the client.
 /// * `client`   - The current [Client] struct.
+/// * `db`       - The [DatabaseImplementation]
 /// * `request`  - The [Request] used to handle the argument.
-pub async fn port(stream: &mut TcpStream, client: &mut Client, request: &Request) -> bool {
+pub async fn eprt(
+    stream: &mut FtpStream,
+    client: &mut Client,
+    db: &DatabaseImplementation,
+    request: &Request,
+) -> bool {
     if client.model.is_none() {
         if !deny_access(stream).await {
             return false;
         }
     } else {
-        let nums: Vec<&str> = request.argument.split(',').collect();
-        let port_one = nums[4].parse::<u16>().unwrap();
-        let port_two = nums[5].parse::<u16>().unwrap();
-
-        let port = port_one * 256 + port_two;
-        let host = format!(
-            "{}{}{}{}{}{}{}",
-            nums[0], '.', nums[1], '.', nums[2], '.', nums[3]
+        match parse_eprt_argument(&request.argument) {
+            Ok(addr) => {
+                if !active_mode(stream, client, db, "EPRT", addr).await {
+                    return false;
+                }
+            }
+            Err(StatusCode::NetworkProtocolNotSupported) => {
+                if !send_response(
+                    stream,
+                    StatusCode::NetworkProtocolNotSupported,
+                    "Network protocol not supported, use (1,2)",
+                )
+                .await
+                {
+                    return false;
+                }
+            }
+            Err(status) => {
+                if !send_response(stream, status, "Illegal EPRT command.").await {
+                    return false;
+                }
+            }
+        }
+    }
+
+    true
+}
+
+/// Returns the IPv4 address advertised in PASV replies.
+///
+/// The configured passive address is used if set, otherwise the address the client connected to,
+/// which is the address of the load balancer for connections forwarded with the PROXY protocol.
+///
+/// * `client`   - The current [Client] struct.
+fn passive_address(client: &Client) -> Option<Ipv4Addr> {
+    let configured = get_config().passive_address;
+    if !configured.is_empty() {
+        return match configured.parse::<Ipv4Addr>() {
+            Ok(ip) => Some(ip),
+            Err(e) => {
+                error!("Invalid passive address {}: {}", configured, e);
+                None
+            }
+        };
+    }
+
+    match client.server_addr.ip() {
+        IpAddr::V4(ip) => Some(ip),
+        IpAddr::V6(ip) => ip.to_ipv4_mapped(),
+    }
+}
+
+/// Handles the FTP PASV packet.
+///
+/// Opens a new listener on a port out of the configured passive port range and saves it in the
+/// current [Client], so the next transfer accepts the data connection there. The advertised
+/// address and the port are sent to the client in the form h1,h2,h3,h4,p1,p2.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+pub async fn pasv(stream: &mut FtpStream, client: &mut Client) -> bool {
+    if client.model.is_none() {
+        if !deny_access(stream).await {
+            return false;
+        }
+    } else if let (Some(ip), Some(listener)) =
+        (passive_address(client), open_passive_listener(stream).await)
+    {
+        let port = listener.local_addr().map(|addr| addr.port()).unwrap_or(0);
+        let octets = ip.octets();
+        let message = format!(
+            "Entering Passive Mode ({},{},{},{},{},{}).",
+            octets[0],
+            octets[1],
+            octets[2],
+            octets[3],
+            port / 256,
+            port % 256
         );
 
-        let host_port = format!("{}:{}", host, port);
-        info!("New TCP connection: {}", host_port);
+        info!("Attacker: {} used PASV: {}", client.username, message);
+        client.data_connection = Some(DataConnection::Passive(listener));
 
-        let socket = TcpSocket::new_v4().unwrap();
-        socket.set_reuseaddr(true).unwrap(); // this fixes not being able to connect from localhost to localhost
+        if !send_response(stream, StatusCode::EnteringPassiveMode, message.as_str()).await {
+            return false;
+        }
+    } else if !send_response(
+        stream,
+        StatusCode::CantOpenDataConnection,
+        "Can't open data connection.",
+    )
+    .await
+    {
+        return false;
+    }
+
+    true
+}
+
+/// Handles the FTP EPSV packet.
+///
+/// Works like [pasv], but only the port is sent to the client in the form (|||port|). The client
+/// connects to the same address it used for the control connection. `EPSV ALL` is accepted.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `request`  - The [Request] used to handle the argument.
+pub async fn epsv(stream: &mut FtpStream, client: &mut Client, request: &Request) -> bool {
+    if client.model.is_none() {
+        if !deny_access(stream).await {
+            return false;
+        }
+    } else if request.argument.eq_ignore_ascii_case("ALL") {
+        if !send_response(stream, StatusCode::Okay, "EPSV ALL ok.").await {
+            return false;
+        }
+    } else if !request.argument.is_empty() && request.argument != "1" && request.argument != "2" {
+        if !send_response(
+            stream,
+            StatusCode::NetworkProtocolNotSupported,
+            "Network protocol not supported, use (1,2)",
+        )
+        .await
+        {
+            return false;
+        }
+    } else if let Some(listener) = open_passive_listener(stream).await {
+        let port = listener.local_addr().map(|addr| addr.port()).unwrap_or(0);
+        let message = format!("Entering Extended Passive Mode (|||{}|)", port);
 
-        client.data_socket = Some(socket);
-        client.data_addr = Some(host_port);
+        info!("Attacker: {} used EPSV: {}", client.username, message);
+        client.data_connection = Some(DataConnection::Passive(listener));
 
-        if !send_response(stream, StatusCode::Okay, "PORT command successful.").await {
+        if !send_response(
+            stream,
+            StatusCode::EnteringExtendedPassiveMode,
+            message.as_str(),
+        )
+        .await
+        {
             return false;
         }
+    } else if !send_response(
+        stream,
+        StatusCode::CantOpenDataConnection,
+        "Can't open data connection.",
+    )
+    .await
+    {
+        return false;
     }
 
     true
@@ -170,10 +693,10 @@ pub async fn port(stream: &mut TcpStream, client: &mut Client, request: &Request
 /// We only allow binary mode. It is possible to also allow other modes like shown in the commented
 /// code block, but this wasn't required for our purposes.
 ///
-/// * `stream`   - The [TcpStream] used to communicate with the client.
+/// * `stream`   - The [FtpStream] used to communicate with the client.
 /// * `client`   - The current [Client] struct.
 /// * `_request` - The [Request] used to handle the argument.
-pub async fn fn_type(stream: &mut TcpStream, client: &mut Client, _request: &Request) -> bool {
+pub async fn fn_type(stream: &mut FtpStream, client: &mut Client, _request: &Request) -> bool {
     if client.model.is_none() {
         if !deny_access(stream).await {
             return false;
@@ -205,3 +728,103 @@ pub async fn fn_type(stream: &mut TcpStream, client: &mut Client, _request: &Req
 
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use crate::honeypot::ftp::StatusCode;
+    use crate::honeypot::ftp_misc::{
+        bounce_reason, features, parse_eprt_argument, parse_port_argument, BounceReason,
+    };
+
+    #[test]
+    fn valid_port_argument() {
+        let res = parse_port_argument("192,168,0,10,4,1");
+
+        assert_eq!(res, "192.168.0.10:1025".parse::<SocketAddr>().ok());
+    }
+
+    #[test]
+    fn invalid_port_argument() {
+        assert!(parse_port_argument("192,168,0,10,4").is_none());
+        assert!(parse_port_argument("192,168,0,300,4,1").is_none());
+        assert!(parse_port_argument("garbage").is_none());
+    }
+
+    #[test]
+    fn valid_eprt_argument() {
+        let v4 = parse_eprt_argument("|1|132.235.1.2|6275|");
+        let v6 = parse_eprt_argument("|2|1080::8:800:200C:417A|5282|");
+
+        assert_eq!(v4.ok(), "132.235.1.2:6275".parse::<SocketAddr>().ok());
+        assert_eq!(
+            v6.ok(),
+            "[1080::8:800:200c:417a]:5282".parse::<SocketAddr>().ok()
+        );
+    }
+
+    #[test]
+    fn invalid_eprt_argument() {
+        assert!(matches!(
+            parse_eprt_argument("|3|132.235.1.2|6275|"),
+            Err(StatusCode::NetworkProtocolNotSupported)
+        ));
+        assert!(matches!(
+            parse_eprt_argument("|2|132.235.1.2|6275|"),
+            Err(StatusCode::SyntaxErrorInParameters)
+        ));
+        assert!(matches!(
+            parse_eprt_argument("|1|132.235.1.2|"),
+            Err(StatusCode::SyntaxErrorInParameters)
+        ));
+    }
+
+    #[test]
+    fn features_without_tls() {
+        let features = features(false);
+
+        assert!(features.contains(&"SIZE".to_string()));
+        assert!(features.contains(&"UTF8".to_string()));
+        assert!(!features.contains(&"AUTH TLS".to_string()));
+        assert!(!features.contains(&"PBSZ".to_string()));
+    }
+
+    #[test]
+    fn features_with_tls() {
+        let features = features(true);
+
+        assert_eq!(features[0], "AUTH TLS");
+        assert!(features.contains(&"PROT".to_string()));
+    }
+
+    #[test]
+    fn bounce_reasons() {
+        let target = |addr: &str| addr.parse::<SocketAddr>().unwrap();
+
+        assert_eq!(
+            bounce_reason(target("203.0.113.7:40000"), "203.0.113.7"),
+            None
+        );
+        assert_eq!(
+            bounce_reason(target("[::ffff:203.0.113.7]:40000"), "203.0.113.7"),
+            None
+        );
+        assert_eq!(
+            bounce_reason(target("203.0.113.7:25"), "203.0.113.7"),
+            Some(BounceReason::PrivilegedPort)
+        );
+        assert_eq!(
+            bounce_reason(target("10.0.0.5:8080"), "203.0.113.7"),
+            Some(BounceReason::PrivateAddress)
+        );
+        assert_eq!(
+            bounce_reason(target("[fd00::1]:8080"), "203.0.113.7"),
+            Some(BounceReason::PrivateAddress)
+        );
+        assert_eq!(
+            bounce_reason(target("198.51.100.1:8080"), "203.0.113.7"),
+            Some(BounceReason::ForeignAddress)
+        );
+    }
+}
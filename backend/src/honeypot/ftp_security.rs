@@ -0,0 +1,245 @@
+//! FTP related security handling functions (RFC 4217)
+
+use std::time::Duration;
+
+use chrono::Local;
+use log::{error, info};
+use sea_orm::ActiveValue::Set;
+use sea_orm::{IntoActiveModel, NotSet};
+use tokio::time::timeout;
+
+use crate::configuration::config::get_config;
+
+use crate::database::models::tls_fingerprints;
+use crate::database::service::DatabaseImplementation;
+use crate::database::service_trait::DatabaseTrait;
+use crate::honeypot::client::Client;
+use crate::honeypot::ftp::{Request, StatusCode};
+use crate::honeypot::ftp_helper::send_response;
+use crate::honeypot::stream::FtpStream;
+use crate::honeypot::tls::ClientHello;
+
+/// Handles the FTP AUTH packet.
+///
+/// AUTH TLS, AUTH SSL and AUTH TLS-C upgrade the control connection to TLS. The ClientHello of the
+/// handshake is fingerprinted and stored for the attacker. If FTPS is disabled the command is
+/// rejected. If the handshake fails or doesn't finish within the command timeout the session is
+/// ended.
+/// AUTH is allowed before the client is logged in.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `db`       - The [DatabaseImplementation]
+/// * `request`  - The [Request] used to handle the argument.
+pub async fn auth(
+    stream: &mut FtpStream,
+    client: &mut Client,
+    db: &DatabaseImplementation,
+    request: &Request,
+) -> bool {
+    let acceptor = match client.tls_acceptor.clone() {
+        Some(acceptor) => acceptor,
+        None => {
+            return send_response(
+                stream,
+                StatusCode::CommandNotImplemented,
+                "Command not implemented.",
+            )
+            .await;
+        }
+    };
+
+    let mechanism = request.argument.to_ascii_uppercase();
+    if !matches!(mechanism.as_str(), "TLS" | "TLS-C" | "SSL" | "TLS-P") {
+        return send_response(
+            stream,
+            StatusCode::CommandNotImplementedForParameter,
+            "Unknown AUTH type.",
+        )
+        .await;
+    }
+
+    if stream.is_tls() {
+        return send_response(
+            stream,
+            StatusCode::BadSequenceOfCommands,
+            "Already using TLS.",
+        )
+        .await;
+    }
+
+    if !send_response(
+        stream,
+        StatusCode::SecurityExchangeOkay,
+        "Proceed with negotiation.",
+    )
+    .await
+    {
+        return false;
+    }
+
+    let handshake_timeout = Duration::from_secs(get_config().timeout_command);
+    match timeout(handshake_timeout, stream.upgrade(&acceptor)).await {
+        Ok(Ok(client_hello)) => {
+            info!(
+                "Attacker: {} upgraded to TLS with AUTH {}",
+                client.ip, mechanism
+            );
+
+            if let Some(client_hello) = client_hello {
+                save_tls_fingerprint(client, db, &client_hello).await;
+            }
+
+            client.protection_buffer_set = false;
+            client.private_data = false;
+
+            true
+        }
+        Ok(Err(e)) => {
+            error!("TLS handshake with {} failed: {}", client.ip, e);
+            false
+        }
+        Err(_) => {
+            error!("TLS handshake with {} timed out", client.ip);
+            false
+        }
+    }
+}
+
+/// Handles the FTP PBSZ packet.
+///
+/// Only a protection buffer size of 0 is used for TLS, which is always replied. PBSZ requires a
+/// TLS protected control connection.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+pub async fn pbsz(stream: &mut FtpStream, client: &mut Client) -> bool {
+    if !stream.is_tls() {
+        return send_response(
+            stream,
+            StatusCode::BadSequenceOfCommands,
+            "PBSZ needs a secure connection.",
+        )
+        .await;
+    }
+
+    client.protection_buffer_set = true;
+
+    send_response(stream, StatusCode::Okay, "PBSZ=0").await
+}
+
+/// Handles the FTP PROT packet.
+///
+/// PROT C keeps the data connections in plain text and PROT P upgrades them to TLS. The Safe and
+/// Confidential levels are not supported. PROT requires a preceding PBSZ.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `request`  - The [Request] used to handle the argument.
+pub async fn prot(stream: &mut FtpStream, client: &mut Client, request: &Request) -> bool {
+    if !client.protection_buffer_set {
+        return send_response(
+            stream,
+            StatusCode::BadSequenceOfCommands,
+            "PROT needs a previous PBSZ command.",
+        )
+        .await;
+    }
+
+    match request.argument.to_ascii_uppercase().as_str() {
+        "C" => {
+            client.private_data = false;
+            send_response(stream, StatusCode::Okay, "PROT now Clear.").await
+        }
+        "P" => {
+            client.private_data = true;
+            send_response(stream, StatusCode::Okay, "PROT now Private.").await
+        }
+        "S" | "E" => {
+            send_response(
+                stream,
+                StatusCode::ProtectionLevelNotSupported,
+                "PROT level not supported.",
+            )
+            .await
+        }
+        _ => {
+            send_response(
+                stream,
+                StatusCode::CommandNotImplementedForParameter,
+                "Unknown PROT level.",
+            )
+            .await
+        }
+    }
+}
+
+/// Stores the JA3 and JA4 fingerprint of the `client_hello`.
+///
+/// The fingerprint is linked to the attacker with the same IP, if it already exists. Otherwise it
+/// is linked on the next login attempt by [link_tls_fingerprint].
+///
+/// * `client`       - The current [Client] struct.
+/// * `db`           - The [DatabaseImplementation]
+/// * `client_hello` - The parsed ClientHello of the handshake.
+pub async fn save_tls_fingerprint(
+    client: &mut Client,
+    db: &DatabaseImplementation,
+    client_hello: &ClientHello,
+) {
+    let attacker_id = match &client.model {
+        Some(model) => Some(model.id),
+        None => db
+            .get_attacker_by_ip(&client.ip)
+            .await
+            .map(|model| model.id),
+    };
+
+    let ja4 = client_hello.ja4();
+    info!("Attacker: {} has TLS fingerprint: {}", client.ip, ja4);
+
+    let tls_fingerprint = tls_fingerprints::ActiveModel {
+        id: NotSet,
+        ip: Set(client.ip.to_string()),
+        listener: Set(client.listener.name.to_owned()),
+        attacker_id: Set(attacker_id),
+        ja3: Set(client_hello.ja3()),
+        ja3_hash: Set(client_hello.ja3_hash()),
+        ja4: Set(ja4),
+        server_name: Set(client_hello.server_name.clone()),
+        created: Set(Local::now()),
+    };
+    let tls_fingerprint = db.update_tls_fingerprint(tls_fingerprint).await;
+
+    client.tls_fingerprint = tls_fingerprint.try_into().ok();
+}
+
+/// Links a previously stored TLS fingerprint to the attacker once the attacker exists.
+///
+/// * `client`   - The current [Client] struct.
+/// * `db`       - The [DatabaseImplementation]
+pub async fn link_tls_fingerprint(client: &mut Client, db: &DatabaseImplementation) {
+    let tls_fingerprint = match &client.tls_fingerprint {
+        Some(tls_fingerprint) if tls_fingerprint.attacker_id.is_none() => tls_fingerprint.clone(),
+        _ => return,
+    };
+
+    let attacker_id = match &client.model {
+        Some(model) => Some(model.id),
+        None => db
+            .get_attacker_by_ip(&client.ip)
+            .await
+            .map(|model| model.id),
+    };
+
+    if attacker_id.is_some() {
+        let mut tls_fingerprint = tls_fingerprint.into_active_model();
+        tls_fingerprint.attacker_id = Set(attacker_id);
+
+        client.tls_fingerprint = db
+            .update_tls_fingerprint(tls_fingerprint)
+            .await
+            .try_into()
+            .ok();
+    }
+}
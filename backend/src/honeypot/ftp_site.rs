@@ -0,0 +1,401 @@
+//! FTP SITE command handling functions
+//!
+//! Besides the common SITE CHMOD, HELP, IDLE and UMASK commands, the ProFTPD mod_copy commands
+//! CPFR and CPTO are emulated. Like the vulnerable ProFTPD 1.3.5 (CVE-2015-3306), they are
+//! answered without login, so scanners copying e.g. `/etc/passwd` into the web root are caught.
+
+use std::str::FromStr;
+
+use log::info;
+use serde_json::json;
+use strum_macros::EnumString;
+
+use crate::database::models::events::EventKind;
+use crate::database::service::DatabaseImplementation;
+use crate::database::service_trait::DatabaseTrait;
+use crate::honeypot::client::Client;
+use crate::honeypot::ftp::{Request, StatusCode};
+use crate::honeypot::ftp_event::save_event_for_ip;
+use crate::honeypot::ftp_helper::{deny_access, send_multiline_response, send_response};
+use crate::honeypot::recorder::record_change;
+use crate::honeypot::stream::FtpStream;
+
+/// The path fragments of common web roots.
+const WEB_ROOTS: [&str; 7] = [
+    "/var/www",
+    "/srv/www",
+    "/srv/http",
+    "/usr/share/nginx",
+    "htdocs",
+    "public_html",
+    "wwwroot",
+];
+
+/// The extensions of server-side scripts.
+const SCRIPT_EXTENSIONS: [&str; 8] = [
+    ".php", ".phtml", ".php5", ".asp", ".aspx", ".jsp", ".cgi", ".pl",
+];
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, EnumString)]
+#[strum(ascii_case_insensitive)]
+/// The SITE commands which are handled.
+enum SiteCommand {
+    CPFR,
+    CPTO,
+    CHMOD,
+    HELP,
+    IDLE,
+    UMASK,
+    #[allow(non_camel_case_types)]
+    NOT_SUPPORTED,
+}
+
+/// Returns whether the `path` looks like a file served by a web server.
+///
+/// * `path` - The target of a copy.
+fn is_web_path(path: &str) -> bool {
+    let path = path.to_ascii_lowercase();
+
+    WEB_ROOTS.iter().any(|root| path.contains(root))
+        || SCRIPT_EXTENSIONS
+            .iter()
+            .any(|extension| path.ends_with(extension))
+}
+
+/// Parses the octal mode of SITE CHMOD, like `755` or `0644`.
+///
+/// * `mode` - The mode argument.
+fn parse_mode(mode: &str) -> Option<u32> {
+    match u32::from_str_radix(mode, 8) {
+        Ok(mode) if mode <= 0o7777 => Some(mode),
+        _ => None,
+    }
+}
+
+/// Handles the FTP SITE packet.
+///
+/// The first word of the argument selects the SITE command, the rest is its argument.
+/// CPFR and CPTO are answered without login, all other commands require it. Unknown SITE
+/// commands are stored verbatim as event.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `db`       - The [DatabaseImplementation]
+/// * `request`  - The [Request] used to handle the argument.
+pub async fn site(
+    stream: &mut FtpStream,
+    client: &mut Client,
+    db: &DatabaseImplementation,
+    request: &Request,
+) -> bool {
+    let (name, argument) = request
+        .argument
+        .split_once(' ')
+        .unwrap_or((request.argument.as_str(), ""));
+    let argument = argument.trim();
+
+    match SiteCommand::from_str(name).unwrap_or(SiteCommand::NOT_SUPPORTED) {
+        SiteCommand::CPTO | SiteCommand::CHMOD if client.read_only() => {
+            send_response(
+                stream,
+                StatusCode::DirectoryCreationFailed,
+                "Permission denied.",
+            )
+            .await
+        }
+        SiteCommand::CPFR => cpfr(stream, client, argument).await,
+        SiteCommand::CPTO => cpto(stream, client, db, argument).await,
+        SiteCommand::NOT_SUPPORTED => {
+            save_event_for_ip(
+                db,
+                &client.ip,
+                attacker_id(client, db).await,
+                EventKind::SiteCommand,
+                json!({ "command": request.argument }),
+            )
+            .await;
+
+            send_response(
+                stream,
+                StatusCode::SyntaxError,
+                format!("'SITE {}' not understood", name).as_str(),
+            )
+            .await
+        }
+        _ if client.model.is_none() => deny_access(stream).await,
+        SiteCommand::CHMOD => chmod(stream, client, db, argument).await,
+        SiteCommand::HELP => {
+            send_multiline_response(
+                stream,
+                StatusCode::HelpMessage,
+                "The following SITE commands are recognized.",
+                &["CHMOD CPFR CPTO HELP IDLE UMASK".to_string()],
+                "SITE HELP OK.",
+            )
+            .await
+        }
+        SiteCommand::IDLE => {
+            let message = if argument.is_empty() {
+                "Current idle time limit is 300 seconds; max 7200".to_string()
+            } else {
+                match argument.parse::<u32>() {
+                    Ok(seconds) if seconds <= 7200 => {
+                        format!("Idle time limit set to {} seconds", seconds)
+                    }
+                    _ => {
+                        return send_response(
+                            stream,
+                            StatusCode::SyntaxErrorInParameters,
+                            "Bad SITE IDLE value.",
+                        )
+                        .await;
+                    }
+                }
+            };
+
+            send_response(stream, StatusCode::Okay, message.as_str()).await
+        }
+        SiteCommand::UMASK => {
+            let message = if argument.is_empty() {
+                "Your current UMASK is 0022".to_string()
+            } else {
+                match parse_mode(argument) {
+                    Some(umask) if umask <= 0o777 => format!("UMASK set to {:04o}", umask),
+                    _ => {
+                        return send_response(
+                            stream,
+                            StatusCode::SyntaxErrorInParameters,
+                            "Bad UMASK value.",
+                        )
+                        .await;
+                    }
+                }
+            };
+
+            send_response(stream, StatusCode::Okay, message.as_str()).await
+        }
+    }
+}
+
+/// Returns the id of the attacker of the `client`, looked up by IP if not logged in.
+///
+/// * `client`   - The current [Client] struct.
+/// * `db`       - The [DatabaseImplementation]
+async fn attacker_id(client: &Client, db: &DatabaseImplementation) -> Option<i64> {
+    match &client.model {
+        Some(model) => Some(model.id),
+        None => db
+            .get_attacker_by_ip(&client.ip)
+            .await
+            .map(|model| model.id),
+    }
+}
+
+/// Handles SITE CPFR, which selects the source of the following CPTO.
+///
+/// Without login every source is accepted, as the vulnerable server did for existing files.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `path`     - The path of the file or directory to copy.
+async fn cpfr(stream: &mut FtpStream, client: &mut Client, path: &str) -> bool {
+    let exists = match client.model.as_mut() {
+        None => true,
+        Some(model) => model
+            .file_system
+            .as_mut()
+            .expect("Filesystem not set!")
+            .exists(path),
+    };
+
+    if path.is_empty() || !exists {
+        client.copy_from = None;
+
+        return send_response(
+            stream,
+            StatusCode::DirectoryCreationFailed,
+            format!("{}: No such file or directory", path).as_str(),
+        )
+        .await;
+    }
+
+    client.copy_from = Some(path.to_string());
+
+    send_response(
+        stream,
+        StatusCode::RequestedFileActionPendingFurtherInformation,
+        "File or directory exists, ready for destination name",
+    )
+    .await
+}
+
+/// Handles SITE CPTO, which copies the source given with the previous CPFR.
+///
+/// Logged in attackers copy inside their [FtpFileSystem][crate::filesystem::ftp_fs::FtpFileSystem],
+/// otherwise the copy is only pretended. Every copy is stored as event and copies into web roots
+/// or to server-side scripts are flagged as exploitation attempt.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `db`       - The [DatabaseImplementation]
+/// * `path`     - The path of the copy.
+async fn cpto(
+    stream: &mut FtpStream,
+    client: &mut Client,
+    db: &DatabaseImplementation,
+    path: &str,
+) -> bool {
+    let copy_from = match client.copy_from.take() {
+        Some(copy_from) if !path.is_empty() => copy_from,
+        _ => {
+            return send_response(
+                stream,
+                StatusCode::BadSequenceOfCommands,
+                "Bad sequence of commands",
+            )
+            .await;
+        }
+    };
+
+    let success = match client.model.as_mut() {
+        None => true,
+        Some(model) => record_change(
+            model
+                .file_system
+                .as_mut()
+                .expect("Filesystem not set!")
+                .copy(db, model.id, copy_from.as_str(), path)
+                .await,
+        ),
+    };
+
+    let exploit = is_web_path(path);
+    if exploit {
+        info!(
+            "Attacker: {} attempted to copy: {} into the web root: {}",
+            client.ip, copy_from, path
+        );
+    }
+
+    save_event_for_ip(
+        db,
+        &client.ip,
+        attacker_id(client, db).await,
+        EventKind::SiteCopy,
+        json!({
+            "from": copy_from,
+            "to": path,
+            "authenticated": client.model.is_some(),
+            "exploit": exploit,
+        }),
+    )
+    .await;
+
+    if !success {
+        return send_response(
+            stream,
+            StatusCode::DirectoryCreationFailed,
+            format!("{}: Permission denied", path).as_str(),
+        )
+        .await;
+    }
+
+    send_response(
+        stream,
+        StatusCode::RequestedFileActionOkayCompleted,
+        "Copy successful",
+    )
+    .await
+}
+
+/// Handles SITE CHMOD, which sets the mode of a file or directory.
+///
+/// * `stream`   - The [FtpStream] used to communicate with the client.
+/// * `client`   - The current [Client] struct.
+/// * `db`       - The [DatabaseImplementation]
+/// * `argument` - The octal mode followed by the path.
+async fn chmod(
+    stream: &mut FtpStream,
+    client: &mut Client,
+    db: &DatabaseImplementation,
+    argument: &str,
+) -> bool {
+    let (mode, path) = match argument.split_once(' ') {
+        Some((mode, path)) if !path.trim().is_empty() => (mode, path.trim()),
+        _ => {
+            return send_response(
+                stream,
+                StatusCode::SyntaxErrorInParameters,
+                "SITE CHMOD needs 2 arguments.",
+            )
+            .await;
+        }
+    };
+
+    let mode = match parse_mode(mode) {
+        Some(mode) => mode,
+        None => {
+            return send_response(stream, StatusCode::SyntaxErrorInParameters, "Bad mode.").await;
+        }
+    };
+
+    let model = client.model.as_mut().unwrap();
+    let success = record_change(
+        model
+            .file_system
+            .as_mut()
+            .expect("Filesystem not set!")
+            .chmod(db, model.id, path, mode)
+            .await,
+    );
+
+    info!(
+        "Attacker: {} changed the mode of: {} to: {:o}",
+        client.username, path, mode
+    );
+
+    if !success {
+        return send_response(
+            stream,
+            StatusCode::DirectoryCreationFailed,
+            "SITE CHMOD command failed.",
+        )
+        .await;
+    }
+
+    send_response(stream, StatusCode::Okay, "SITE CHMOD command ok.").await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::honeypot::ftp_site::{is_web_path, parse_mode, SiteCommand};
+
+    #[test]
+    fn web_paths() {
+        assert!(is_web_path("/var/www/html/x.php"));
+        assert!(is_web_path("/var/www/passwd.txt"));
+        assert!(is_web_path("C:/inetpub/wwwroot/default.htm"));
+        assert!(is_web_path("/tmp/shell.PHP"));
+        assert!(!is_web_path("/tmp/passwd"));
+        assert!(!is_web_path("/home/ftp/backup.tar.gz"));
+    }
+
+    #[test]
+    fn site_commands() {
+        assert_eq!(SiteCommand::from_str("cpfr"), Ok(SiteCommand::CPFR));
+        assert_eq!(SiteCommand::from_str("ChMod"), Ok(SiteCommand::CHMOD));
+        assert!(SiteCommand::from_str("EXEC").is_err());
+    }
+
+    #[test]
+    fn modes() {
+        assert_eq!(parse_mode("755"), Some(0o755));
+        assert_eq!(parse_mode("04755"), Some(0o4755));
+        assert_eq!(parse_mode("888"), None);
+        assert_eq!(parse_mode("17777"), None);
+        assert_eq!(parse_mode("+x"), None);
+    }
+}
@@ -1,70 +1,199 @@
 //! FTP handler that includes the main FTP server-client logic
 
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use log::{error, info};
-use tokio::net::TcpStream;
+use tokio::time::timeout;
+use tokio_rustls::TlsAcceptor;
 
-use crate::configuration::config::get_config;
+use crate::configuration::config::{get_config, ListenerConfig};
+use crate::database::models::sessions::Outcome;
+use crate::database::models::transcripts::Direction;
 use crate::database::service::DatabaseImplementation;
+use crate::database::service_trait::DatabaseTrait;
 use crate::honeypot::client::Client;
+use crate::honeypot::command_reader::{CommandReader, Packet};
 use crate::honeypot::decoder::Decoder;
 use crate::honeypot::ftp::{Command, StatusCode};
 use crate::honeypot::ftp_access::{acct, pass, quit, user};
-use crate::honeypot::ftp_fs::{cwd, dele, list, mkd, pwd, retr, rmd, stor};
-use crate::honeypot::ftp_helper::{read_packet, send_response};
-use crate::honeypot::ftp_misc::{fn_type, help, mode, noop, port, stru, syst};
+use crate::honeypot::ftp_fs::{
+    appe, cwd, dele, list, mdtm, mkd, mlsd, mlst, nlst, pwd, rest, retr, rmd, rnfr, rnto, size,
+    stat, stor, stou,
+};
+use crate::honeypot::ftp_helper::{send_response, send_response_slowly};
+use crate::honeypot::ftp_misc::{
+    eprt, epsv, feat, fn_type, help, mode, noop, opts, pasv, port, stru, syst,
+};
+use crate::honeypot::ftp_security::{auth, pbsz, prot, save_tls_fingerprint};
+use crate::honeypot::ftp_site::site;
+use crate::honeypot::personality;
+use crate::honeypot::personality::{get_personality, Personality};
+use crate::honeypot::proxy::Addresses;
+use crate::honeypot::recorder;
+use crate::honeypot::recorder::record_line;
+use crate::honeypot::sniffer;
+use crate::honeypot::stream::FtpStream;
+use crate::honeypot::tarpit;
+use crate::honeypot::tarpit::banner_byte_delay;
+use crate::honeypot::tls::ClientHello;
+
+/// Holds a slot of the `current_users` limit, which is released when the session ends.
+///
+/// The slot is released on drop, so it is released on every return as well as on a panic.
+struct UserSlot(Arc<Mutex<i32>>);
+
+impl UserSlot {
+    /// Takes a slot of the `current_users` limit.
+    ///
+    /// * `current_users`   - The amount of currently active users, wrapped around a [Arc] [Mutex].
+    fn take(current_users: Arc<Mutex<i32>>) -> Self {
+        *current_users.lock().unwrap() += 1;
+        UserSlot(current_users)
+    }
+}
+
+impl Drop for UserSlot {
+    fn drop(&mut self) {
+        // A panic while the lock was held must not keep the slot taken.
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+    }
+}
 
 /// Handles the main loop and logic of the FTP honeypot.
 ///
 /// The client is first greeted with a welcome message, as long as the `current_users` limit isn't reached.
 /// A new [Client] struct is created for each /// new connection to the FTP server.
-/// The main loop begins by reading packets and handling them
+/// The main loop begins by reading command lines with the [CommandReader] and handling them
 /// accordingly in their functions. The packets read are decoded by the [Decoder] to handle them.
-/// Commands that are not supported are replied to with an error message.
-/// Upon an error from the [Decoder] the main loop is broken out of and the connection is terminated.
+/// Commands that are not supported and lines the [Decoder] can't parse are replied to with an
+/// error message. When the client closes the connection the main loop is broken out of and the
+/// connection is terminated. Clients that send no command within the idle timeout or don't
+/// complete a started command line within the command timeout are disconnected with a 421 reply.
+/// The `stream` is upgraded to TLS in place when the client sends AUTH TLS. Streams of implicit
+/// FTPS listeners are already TLS protected, their ClientHello is stored right away. Each
+/// `listener` has its own `current_users` limit and [Personality].
+/// Sessions of IPs with many login attempts may be [tarpitted][tarpit], their welcome message is
+/// trickled in byte by byte. Clients that send first are [sniffed][sniffer] before the welcome
+/// message, probes of other protocols than FTP are stored as events. Every session is
+/// [recorded][recorder::record] with a transcript of the commands read and the replies written,
+/// and the bytes of its connections are captured.
+/// Connections forwarded by a trusted load balancer carry the client address of the
+/// [PROXY protocol][crate::honeypot::proxy] header in `addresses`.
 ///
-/// * `stream`          - The [TcpStream] used to communicate with the client.
-/// * `ip`              - The current IP of the attacker.
+/// * `stream`          - The [FtpStream] used to communicate with the client.
+/// * `addresses`       - The [Addresses] of the client and the server it connected to.
 /// * `db`              - The [DatabaseImplementation].
 /// * `current_users`   - The amount of currently active users, wrapped around a [Arc] [Mutex].
+/// * `tls_acceptor`    - The [TlsAcceptor] used for FTPS, [None] if FTPS is disabled.
+/// * `listener`        - The [ListenerConfig] of the listener the connection came in on.
+/// * `client_hello`    - The ClientHello of an implicit FTPS connection.
 pub async fn handle_connection(
-    stream: &mut TcpStream,
-    ip: String,
+    stream: &mut FtpStream,
+    addresses: Addresses,
     db: &DatabaseImplementation,
     current_users: Arc<Mutex<i32>>,
+    tls_acceptor: Option<TlsAcceptor>,
+    listener: ListenerConfig,
+    client_hello: Option<ClientHello>,
 ) {
-    if *current_users.lock().unwrap() >= get_config().max_concurrent_users {
+    let ip = addresses.client.ip().to_canonical().to_string();
+    let name = listener.name.to_owned();
+    let personality = Personality::for_listener(&get_config(), &listener);
+
+    personality::scope(
+        personality,
+        recorder::record(
+            db,
+            &ip,
+            addresses.client.port(),
+            Some(addresses.server),
+            &name,
+            serve(
+                stream,
+                addresses,
+                db,
+                current_users,
+                tls_acceptor,
+                listener,
+                client_hello,
+            ),
+        ),
+    )
+    .await;
+}
+
+/// Serves the session as described in [handle_connection] and returns its [Outcome].
+///
+/// * `stream`          - The [FtpStream] used to communicate with the client.
+/// * `addresses`       - The [Addresses] of the client and the server it connected to.
+/// * `db`              - The [DatabaseImplementation].
+/// * `current_users`   - The amount of currently active users, wrapped around a [Arc] [Mutex].
+/// * `tls_acceptor`    - The [TlsAcceptor] used for FTPS, [None] if FTPS is disabled.
+/// * `listener`        - The [ListenerConfig] of the listener the connection came in on.
+/// * `client_hello`    - The ClientHello of an implicit FTPS connection.
+async fn serve(
+    stream: &mut FtpStream,
+    addresses: Addresses,
+    db: &DatabaseImplementation,
+    current_users: Arc<Mutex<i32>>,
+    tls_acceptor: Option<TlsAcceptor>,
+    listener: ListenerConfig,
+    client_hello: Option<ClientHello>,
+) -> Outcome {
+    let ip = addresses.client.ip().to_canonical().to_string();
+
+    if *current_users.lock().unwrap() >= listener.max_concurrent_users {
         info!(
             "Max concurrent users reached! Blocking IP: {} with status code 421!",
             ip
         );
 
-        if !send_response(
+        send_response(
             stream,
             StatusCode::ServiceNotAvailable,
             "Please come back in 2040 seconds.",
         )
-        .await
-        {
-            return;
-        }
+        .await;
 
-        return;
+        return Outcome::Rejected;
     }
 
-    info!("New connection from: {}", ip);
+    info!("New connection from: {} on listener: {}", ip, listener.name);
 
-    *current_users.lock().unwrap() += 1;
+    let _user_slot = UserSlot::take(current_users);
 
-    if !send_response(
-        stream,
-        StatusCode::ServiceReadyForNewUser,
-        get_config().ftp_welcome_message.as_str(),
-    )
-    .await
-    {
-        return;
+    if let Some(protocol) = sniffer::sniff(stream).await {
+        sniffer::handle_probe(stream, &ip, db, protocol).await;
+        return Outcome::Probe;
+    }
+
+    let login_attempts = if get_config().tarpit_enabled {
+        db.get_attacker_by_ip(&ip)
+            .await
+            .map_or(0, |attacker| attacker.login_count)
+    } else {
+        0
+    };
+    let tarpit = tarpit::enter(login_attempts);
+    let banner = get_personality().banner;
+
+    let greeted = match tarpit {
+        Some(_) if !banner_byte_delay().is_zero() => {
+            info!("Tarpitting: {} after {} login attempts", ip, login_attempts);
+            send_response_slowly(
+                stream,
+                StatusCode::ServiceReadyForNewUser,
+                banner.as_str(),
+                banner_byte_delay(),
+            )
+            .await
+        }
+        _ => send_response(stream, StatusCode::ServiceReadyForNewUser, banner.as_str()).await,
+    };
+
+    if !greeted {
+        return Outcome::Disconnected;
     }
 
     let mut client = Client {
@@ -72,21 +201,94 @@ pub async fn handle_connection(
         password: "".to_string(),
         model: None,
         ip,
-        data_socket: None,
-        data_addr: None,
+        server_addr: addresses.server,
+        listener,
+        data_connection: None,
+        tls_acceptor,
+        tls_fingerprint: None,
+        protection_buffer_set: false,
+        private_data: false,
+        rename_from: None,
+        copy_from: None,
+        restart_offset: 0,
+        backdoor_triggered: false,
+        anonymous: false,
+        login_attempts,
+        tarpit,
     };
 
+    if let Some(client_hello) = client_hello {
+        save_tls_fingerprint(&mut client, db, &client_hello).await;
+    }
+
+    let config = get_config();
+    let idle_timeout = Duration::from_secs(config.timeout_idle);
+    let command_timeout = Duration::from_secs(config.timeout_command);
+    let mut reader = CommandReader::new(config.ftp_max_command_length, command_timeout);
+    let mut outcome = Outcome::Disconnected;
+
     loop {
-        let mut packet: [u8; 32] = [0; 32];
-        read_packet(stream, &mut packet).await;
+        recorder::flush(db).await;
+
+        let line = match timeout(idle_timeout, reader.read_command(stream)).await {
+            Ok(Packet::Line(line)) => line,
+            Ok(Packet::TooLong) => {
+                if !send_response(stream, StatusCode::SyntaxError, "Command line too long.").await {
+                    break;
+                }
+
+                continue;
+            }
+            Ok(Packet::Closed) => {
+                info!("Connection closed by: {}", client.ip);
+                outcome = Outcome::Closed;
+                break;
+            }
+            Ok(Packet::TimedOut) | Err(_) => {
+                info!("Connection of: {} timed out", client.ip);
+                send_response(stream, StatusCode::ServiceNotAvailable, "Timeout.").await;
+                outcome = Outcome::TimedOut;
+                break;
+            }
+        };
+        record_line(Direction::Command, &line);
 
-        match &Decoder::decode(String::from_utf8_lossy(&packet[..])) {
+        match &Decoder::decode(&line) {
             Ok(request) => match request.command {
+                _ if client.read_only() && request.command.modifies_filesystem() => {
+                    if !send_response(
+                        stream,
+                        StatusCode::DirectoryCreationFailed,
+                        "Permission denied.",
+                    )
+                    .await
+                    {
+                        break;
+                    }
+                }
                 Command::USER => {
                     if !user(stream, &mut client, request).await {
                         break;
                     }
                 }
+                Command::AUTH => {
+                    if !auth(stream, &mut client, db, request).await {
+                        break;
+                    }
+
+                    // Plain text sent before the handshake must not be read as a command.
+                    reader = CommandReader::new(config.ftp_max_command_length, command_timeout);
+                }
+                Command::PBSZ => {
+                    if !pbsz(stream, &mut client).await {
+                        break;
+                    }
+                }
+                Command::PROT => {
+                    if !prot(stream, &mut client, request).await {
+                        break;
+                    }
+                }
                 Command::PASS => {
                     if !pass(stream, &mut client, request, db).await {
                         break;
@@ -117,12 +319,33 @@ pub async fn handle_connection(
                         break;
                     }
                 }
+                Command::FEAT => {
+                    if !feat(stream, &mut client).await {
+                        break;
+                    }
+                }
+                Command::OPTS => {
+                    if !opts(stream, request).await {
+                        break;
+                    }
+                }
+                Command::SIZE => {
+                    if !size(stream, &mut client, request).await {
+                        break;
+                    }
+                }
+                Command::MDTM => {
+                    if !mdtm(stream, &mut client, request).await {
+                        break;
+                    }
+                }
                 Command::NOOP => {
                     if !noop(stream, &mut client).await {
                         break;
                     }
                 }
                 Command::QUIT => {
+                    outcome = Outcome::Quit;
                     if !quit(stream).await {
                         break;
                     }
@@ -133,7 +356,22 @@ pub async fn handle_connection(
                     }
                 }
                 Command::PORT => {
-                    if !port(stream, &mut client, request).await {
+                    if !port(stream, &mut client, db, request).await {
+                        break;
+                    }
+                }
+                Command::EPRT => {
+                    if !eprt(stream, &mut client, db, request).await {
+                        break;
+                    }
+                }
+                Command::PASV => {
+                    if !pasv(stream, &mut client).await {
+                        break;
+                    }
+                }
+                Command::EPSV => {
+                    if !epsv(stream, &mut client, request).await {
                         break;
                     }
                 }
@@ -162,6 +400,46 @@ pub async fn handle_connection(
                         break;
                     }
                 }
+                Command::NLST => {
+                    if !nlst(stream, &mut client, request).await {
+                        break;
+                    }
+                }
+                Command::MLSD => {
+                    if !mlsd(stream, &mut client, request).await {
+                        break;
+                    }
+                }
+                Command::MLST => {
+                    if !mlst(stream, &mut client, request).await {
+                        break;
+                    }
+                }
+                Command::RNFR => {
+                    if !rnfr(stream, &mut client, request).await {
+                        break;
+                    }
+                }
+                Command::RNTO => {
+                    if !rnto(stream, &mut client, db, request).await {
+                        break;
+                    }
+                }
+                Command::APPE => {
+                    if !appe(stream, &mut client, db, request).await {
+                        break;
+                    }
+                }
+                Command::STOU => {
+                    if !stou(stream, &mut client, db, request).await {
+                        break;
+                    }
+                }
+                Command::REST => {
+                    if !rest(stream, &mut client, request).await {
+                        break;
+                    }
+                }
                 Command::DELE => {
                     if !dele(stream, &mut client, db, request).await {
                         break;
@@ -192,17 +470,18 @@ pub async fn handle_connection(
                     }
                 }
                 Command::STAT => {
-                    if !send_response(
-                        stream,
-                        StatusCode::CommandNotImplementedForParameter,
-                        "Rejected.",
-                    )
-                    .await
-                    {
+                    if !stat(stream, &mut client, request).await {
+                        break;
+                    }
+                }
+                Command::SITE => {
+                    if !site(stream, &mut client, db, request).await {
                         break;
                     }
                 }
                 Command::NOT_SUPPORTED => {
+                    info!("Unsupported command from {}: {:?}", client.ip, request.raw);
+
                     if !send_response(
                         stream,
                         StatusCode::CommandNotImplemented,
@@ -216,10 +495,14 @@ pub async fn handle_connection(
             },
             Err(e) => {
                 error!("Error: {}", e);
-                break;
+
+                let (status, message) = e.reply();
+                if !send_response(stream, status, message).await {
+                    break;
+                }
             }
         }
     }
 
-    *current_users.lock().unwrap() -= 1;
+    outcome
 }
@@ -1,11 +1,23 @@
 //! FTP implementation
 
-mod client;
+pub mod backdoor;
+pub mod client;
+mod command_reader;
 mod decoder;
 mod encoder;
 mod ftp;
 mod ftp_access;
+mod ftp_event;
 mod ftp_fs;
 mod ftp_helper;
 mod ftp_misc;
+mod ftp_security;
+mod ftp_site;
 pub mod handler;
+pub mod personality;
+pub mod proxy;
+pub mod recorder;
+mod sniffer;
+pub mod stream;
+mod tarpit;
+pub mod tls;
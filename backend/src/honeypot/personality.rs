@@ -0,0 +1,390 @@
+//! Server personality profiles, which make the honeypot look like a specific FTP server product
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::str::FromStr;
+
+use log::warn;
+use strum_macros::{Display, EnumString};
+
+use crate::configuration::config::{get_config, Config, ListenerConfig};
+use crate::honeypot::ftp::StatusCode;
+
+/// The status codes of replies with a fixed text, only their text can be overridden. Replies
+/// with other codes carry details of the request or are shared by unrelated commands, like 530,
+/// and the 220 banner is set with the welcome message.
+const OVERRIDABLE_REPLIES: [u32; 4] = [221, 226, 230, 331];
+
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+#[strum(ascii_case_insensitive)]
+/// The built-in personality presets.
+pub enum Preset {
+    #[strum(serialize = "vsftpd")]
+    Vsftpd,
+    #[strum(serialize = "proftpd")]
+    Proftpd,
+    #[strum(serialize = "pure-ftpd")]
+    PureFtpd,
+    #[strum(serialize = "iis")]
+    Iis,
+}
+
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+/// The format of LIST replies.
+pub enum ListingFormat {
+    /// The `ls -l` format of UNIX servers.
+    Unix,
+    /// The MS-DOS format of the Microsoft FTP Service.
+    Msdos,
+}
+
+#[derive(Clone, Debug)]
+/// The personality of the server, which defines how it presents itself to the client.
+pub struct Personality {
+    pub preset: Preset,
+    /// The welcome message, sent as 220 reply.
+    pub banner: String,
+    /// The SYST reply.
+    pub syst: String,
+    /// The last line of the HELP reply.
+    pub help: String,
+    /// The server software shown by STAT.
+    pub version: String,
+    pub listing: ListingFormat,
+    /// The reply texts by status code, which replace the default wording.
+    pub replies: HashMap<u32, String>,
+}
+
+tokio::task_local! {
+    /// The personality of the listener the session served by the current task came in on.
+    static PERSONALITY: Personality;
+}
+
+/// Returns the [Personality] of the current session, set with [scope].
+///
+/// Outside of a session the [Personality] is created out of the [Config] with [get_config].
+pub fn get_personality() -> Personality {
+    PERSONALITY
+        .try_with(Personality::clone)
+        .unwrap_or_else(|_| Personality::from_config(&get_config()))
+}
+
+/// Serves the `session` with the `personality`, which is returned by [get_personality].
+///
+/// * `personality` - The [Personality] of the listener the session came in on.
+/// * `session`     - The session to serve.
+pub async fn scope<F: Future>(personality: Personality, session: F) -> F::Output {
+    PERSONALITY.scope(personality, session).await
+}
+
+impl Personality {
+    /// Creates the [Personality] of a built-in [Preset].
+    ///
+    /// * `preset` - The [Preset] to emulate.
+    pub fn from_preset(preset: Preset) -> Self {
+        let (banner, syst, help, version, listing, replies) = match preset {
+            Preset::Vsftpd => (
+                "(vsFTPd 3.0.3)",
+                "UNIX Type: L8",
+                "Help OK.",
+                "vsFTPd 3.0.3 - secure, fast, stable",
+                ListingFormat::Unix,
+                vec![
+                    (221, "Goodbye."),
+                    (230, "Login successful."),
+                    (331, "Please specify the password."),
+                ],
+            ),
+            Preset::Proftpd => (
+                "ProFTPD 1.3.5 Server (ProFTPD Default Installation)",
+                "UNIX Type: L8",
+                "Direct comments to root@localhost",
+                "ProFTPD 1.3.5 Server (ProFTPD Default Installation)",
+                ListingFormat::Unix,
+                vec![
+                    (221, "Goodbye."),
+                    (226, "Transfer complete"),
+                    (230, "User logged in"),
+                    (331, "Password required"),
+                ],
+            ),
+            Preset::PureFtpd => (
+                "---------- Welcome to Pure-FTPd [privsep] [TLS] ----------\n\
+                You are user number 1 of 50 allowed.\n\
+                This is a private system - No anonymous login\n\
+                You will be disconnected after 15 minutes of inactivity.",
+                "UNIX Type: L8",
+                "Pure-FTPd - http://pureftpd.org/",
+                "http://www.pureftpd.org/",
+                ListingFormat::Unix,
+                vec![
+                    (221, "Logout."),
+                    (230, "OK. Current directory is /"),
+                    (331, "User OK. Password required"),
+                ],
+            ),
+            Preset::Iis => (
+                "Microsoft FTP Service",
+                "Windows_NT",
+                "HELP command successful.",
+                "Microsoft FTP Service",
+                ListingFormat::Msdos,
+                vec![
+                    (221, "Goodbye."),
+                    (226, "Transfer complete."),
+                    (230, "User logged in."),
+                    (331, "Password required"),
+                ],
+            ),
+        };
+
+        Personality {
+            preset,
+            banner: banner.to_string(),
+            syst: syst.to_string(),
+            help: help.to_string(),
+            version: version.to_string(),
+            listing,
+            replies: replies
+                .into_iter()
+                .map(|(code, text)| (code, text.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Creates the [Personality] of the configured [Preset] and applies the configured overrides.
+    ///
+    /// The welcome and help message of the ftp settings replace the banner and help text of the
+    /// preset. Invalid values are logged and ignored.
+    ///
+    /// * `config` - The [Config] holding the personality settings.
+    pub fn from_config(config: &Config) -> Self {
+        Personality::with_preset(
+            config,
+            &config.personality_preset,
+            &config.ftp_welcome_message,
+        )
+    }
+
+    /// Creates the [Personality] of a listener, see [from_config][Personality::from_config].
+    ///
+    /// The preset and welcome message of the `listener` replace the ones of the personality and
+    /// ftp settings.
+    ///
+    /// * `config`   - The [Config] holding the personality settings.
+    /// * `listener` - The [ListenerConfig] of the listener.
+    pub fn for_listener(config: &Config, listener: &ListenerConfig) -> Self {
+        Personality::with_preset(config, &listener.personality, &listener.welcome_message)
+    }
+
+    /// Creates the [Personality] of the `preset` and applies the configured overrides.
+    ///
+    /// * `config`          - The [Config] holding the personality settings.
+    /// * `preset`          - The name of the [Preset].
+    /// * `welcome_message` - The welcome message, which replaces the banner if not empty.
+    fn with_preset(config: &Config, preset: &str, welcome_message: &str) -> Self {
+        let preset = Preset::from_str(preset).unwrap_or_else(|_| {
+            warn!("Unknown personality preset {}, using vsftpd.", preset);
+            Preset::Vsftpd
+        });
+        let mut personality = Personality::from_preset(preset);
+
+        let overrides = [
+            (&mut personality.banner, welcome_message),
+            (&mut personality.help, config.ftp_help_message.as_str()),
+            (&mut personality.syst, config.personality_syst.as_str()),
+            (
+                &mut personality.version,
+                config.personality_version.as_str(),
+            ),
+        ];
+        for (field, value) in overrides {
+            if !value.is_empty() {
+                *field = value.to_owned();
+            }
+        }
+
+        if !config.personality_listing.is_empty() {
+            match ListingFormat::from_str(&config.personality_listing) {
+                Ok(listing) => personality.listing = listing,
+                Err(_) => warn!(
+                    "Unknown listing format {}, using {}.",
+                    config.personality_listing, personality.listing
+                ),
+            }
+        }
+
+        for (code, text) in config.personality_replies.iter() {
+            match code.parse::<u32>() {
+                Ok(code) if OVERRIDABLE_REPLIES.contains(&code) => {
+                    personality.replies.insert(code, text.to_owned());
+                }
+                Ok(code) => warn!("The text of status code {} can't be overridden.", code),
+                Err(_) => warn!("Invalid status code {} in personality replies.", code),
+            }
+        }
+
+        personality
+    }
+
+    /// Returns the text to reply with for the `status`.
+    ///
+    /// * `status`   - The [StatusCode] of the reply.
+    /// * `message`  - The default text of the reply.
+    pub fn reply(&self, status: StatusCode, message: &str) -> String {
+        let code = status as u32;
+
+        match self.replies.get(&code) {
+            Some(text) if OVERRIDABLE_REPLIES.contains(&code) => text.to_owned(),
+            _ => message.to_string(),
+        }
+    }
+
+    /// Returns the first and the last line of the FEAT reply.
+    pub fn feat_frame(&self) -> (&'static str, &'static str) {
+        match self.preset {
+            Preset::Vsftpd | Preset::Proftpd => ("Features:", "End"),
+            Preset::PureFtpd => ("Extensions supported:", "End."),
+            Preset::Iis => ("Extended features supported:", "END"),
+        }
+    }
+
+    /// Returns the lines of the STAT reply without argument.
+    ///
+    /// * `ip`           - The IP address of the client.
+    /// * `username`     - The name of the logged in user.
+    /// * `control_tls`  - Whether the control connection is TLS protected.
+    /// * `data_tls`     - Whether the data connections are TLS protected.
+    pub fn status(
+        &self,
+        ip: &str,
+        username: &str,
+        control_tls: bool,
+        data_tls: bool,
+    ) -> Vec<String> {
+        let protection = |tls: bool| if tls { "TLS protected" } else { "plain text" };
+
+        match self.preset {
+            Preset::Vsftpd => vec![
+                "FTP server status:".to_string(),
+                format!("     Connected to {}", ip),
+                format!("     Logged in as {}", username),
+                "     TYPE: BINARY, STRU: File, MODE: Stream".to_string(),
+                "     No session bandwidth limit".to_string(),
+                format!("     Control connection is {}", protection(control_tls)),
+                format!("     Data connections will be {}", protection(data_tls)),
+                format!("     {}", self.version),
+                "End of status".to_string(),
+            ],
+            Preset::Proftpd => vec![
+                format!("Status of '{}'", self.version),
+                format!(" Connected from {} ({})", ip, ip),
+                format!(" Logged in as {}", username),
+                " TYPE: BINARY, STRUcture: File, Mode: Stream".to_string(),
+                " No data connection".to_string(),
+                "End of status".to_string(),
+            ],
+            Preset::PureFtpd => vec![self.version.to_owned()],
+            Preset::Iis => vec![
+                format!("{} status:", self.version),
+                format!("    Logged in user: {}", username),
+                "    TYPE: BINARY; STRUcture: FILE; transfer MODE: STREAM".to_string(),
+                "    No data connection".to_string(),
+                "End of status.".to_string(),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::configuration::config::Config;
+    use crate::honeypot::ftp::StatusCode;
+    use crate::honeypot::personality::{
+        get_personality, scope, ListingFormat, Personality, Preset,
+    };
+
+    #[test]
+    fn personality_from_config() {
+        let config = Config::new("application-test.toml");
+        let personality = Personality::from_config(&config);
+
+        assert_eq!(personality.preset, Preset::Iis);
+        assert_eq!(personality.banner, "welcome_msg\nsecond_line");
+        assert_eq!(personality.help, "help_msg");
+        assert_eq!(personality.syst, "UNIX Type: L8");
+        assert_eq!(personality.version, "Microsoft FTP Service 10.0");
+        assert_eq!(personality.listing, ListingFormat::Unix);
+        assert_eq!(
+            personality.reply(StatusCode::UserLoggedInProceed, "Login successful."),
+            "Welcome."
+        );
+        assert_eq!(
+            personality.reply(StatusCode::UserSuccessfulLogout, "Bye."),
+            "Goodbye."
+        );
+    }
+
+    #[actix_rt::test]
+    async fn personality_of_listener() {
+        let config = Config::new("application-test.toml");
+        let personality = Personality::for_listener(&config, &config.listeners[0]);
+
+        assert_eq!(personality.preset, Preset::Proftpd);
+        assert_eq!(personality.banner, "listener_welcome");
+        assert_eq!(personality.help, "help_msg");
+        assert_eq!(personality.syst, "UNIX Type: L8");
+
+        let banner = scope(personality, async { get_personality().banner }).await;
+        assert_eq!(banner, "listener_welcome");
+    }
+
+    #[test]
+    fn personality_defaults_to_vsftpd() {
+        let config = Config::new("invalid.toml");
+        let personality = Personality::from_config(&config);
+
+        assert_eq!(personality.preset, Preset::Vsftpd);
+        assert_eq!(personality.banner, "(vsFTPd 3.0.3)");
+        assert_eq!(personality.listing, ListingFormat::Unix);
+    }
+
+    #[test]
+    fn only_fixed_replies_are_overridden() {
+        let mut personality = Personality::from_preset(Preset::Proftpd);
+        personality.replies.insert(227, "Nope".to_string());
+        personality.replies.insert(200, "Nope".to_string());
+        personality.replies.insert(530, "Denied".to_string());
+        personality.replies.insert(221, "Bye.".to_string());
+
+        assert_eq!(
+            personality.reply(
+                StatusCode::EnteringPassiveMode,
+                "Entering Passive Mode (127,0,0,1,4,1)."
+            ),
+            "Entering Passive Mode (127,0,0,1,4,1)."
+        );
+        assert_eq!(personality.reply(StatusCode::Okay, "NOOP ok."), "NOOP ok.");
+        assert_eq!(
+            personality.reply(StatusCode::NotLoggedIn, "Login incorrect."),
+            "Login incorrect."
+        );
+        assert_eq!(
+            personality.reply(StatusCode::UserSuccessfulLogout, "Goodbye."),
+            "Bye."
+        );
+    }
+
+    #[test]
+    fn iis_status() {
+        let personality = Personality::from_preset(Preset::Iis);
+        let status = personality.status("10.0.0.1", "admin", false, false);
+
+        assert_eq!(personality.listing, ListingFormat::Msdos);
+        assert_eq!(personality.syst, "Windows_NT");
+        assert_eq!(status[0], "Microsoft FTP Service status:");
+        assert_eq!(status[1], "    Logged in user: admin");
+        assert_eq!(status.last().unwrap(), "End of status.");
+    }
+}
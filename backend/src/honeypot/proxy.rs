@@ -0,0 +1,333 @@
+//! PROXY protocol v1 and v2, which tells the real client address of connections forwarded by a
+//! load balancer
+//!
+//! Connections from [trusted][is_trusted] load balancers have to start with a PROXY protocol
+//! header, the addresses it holds replace the addresses of the socket. Connections from other
+//! sources are taken as they are, so clients can't spoof their address.
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use log::info;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::time::timeout;
+
+use crate::configuration::config::get_config;
+
+/// The signature every PROXY protocol v2 header starts with.
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+/// The maximum length of a PROXY protocol v1 header, including the line terminator.
+const V1_MAX_LENGTH: usize = 107;
+
+/// The addresses of a connection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Addresses {
+    /// The address of the client.
+    pub client: SocketAddr,
+    /// The address the client connected to.
+    pub server: SocketAddr,
+}
+
+/// Returns whether the `ip` is one of the trusted IPs or networks, like `10.0.0.0/8`.
+///
+/// * `ip`      - The IP of the peer.
+/// * `trusted` - The trusted IPs and networks.
+pub fn is_trusted(ip: IpAddr, trusted: &[String]) -> bool {
+    let ip = ip.to_canonical();
+
+    trusted.iter().any(|entry| {
+        let (network, prefix) = entry.split_once('/').unwrap_or((entry, ""));
+        let network = match network.trim().parse::<IpAddr>() {
+            Ok(network) => network.to_canonical(),
+            Err(_) => return false,
+        };
+
+        match (ip, network) {
+            (IpAddr::V4(ip), IpAddr::V4(network)) => {
+                matches_prefix(u32::from(ip).into(), u32::from(network).into(), 32, prefix)
+            }
+            (IpAddr::V6(ip), IpAddr::V6(network)) => {
+                matches_prefix(u128::from(ip), u128::from(network), 128, prefix)
+            }
+            _ => false,
+        }
+    })
+}
+
+/// Returns whether the first `prefix` bits of the addresses match, all bits if `prefix` is empty.
+///
+/// * `ip`      - The address as number.
+/// * `network` - The address of the network as number.
+/// * `bits`    - The length of the addresses in bits.
+/// * `prefix`  - The prefix length.
+fn matches_prefix(ip: u128, network: u128, bits: u32, prefix: &str) -> bool {
+    let prefix = if prefix.is_empty() {
+        bits
+    } else {
+        match prefix.trim().parse::<u32>() {
+            Ok(prefix) if prefix <= bits => prefix,
+            _ => return false,
+        }
+    };
+
+    prefix == 0 || (ip ^ network) >> (bits - prefix) == 0
+}
+
+/// Returns the [Addresses] of a new connection.
+///
+/// If the PROXY protocol is enabled and the peer of the `socket` is trusted, the header is read
+/// from the `stream` within the command timeout and its addresses are returned. Headers of health
+/// checks without addresses keep the addresses of the socket. A missing or invalid header is an
+/// error.
+///
+/// * `stream` - The connection.
+/// * `socket` - The [Addresses] of the socket.
+/// * `data`   - Whether the connection is a data connection.
+pub async fn resolve<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    socket: Addresses,
+    data: bool,
+) -> io::Result<Addresses> {
+    let config = get_config();
+    if !config.proxy_enabled
+        || (data && !config.proxy_data_connections)
+        || !is_trusted(socket.client.ip(), &config.proxy_trusted)
+    {
+        return Ok(socket);
+    }
+
+    let read_timeout = Duration::from_secs(config.timeout_command);
+    let header = match timeout(read_timeout, read_header(stream)).await {
+        Ok(header) => header?,
+        Err(_) => return Err(io::ErrorKind::TimedOut.into()),
+    };
+
+    match header {
+        Some(addresses) => {
+            info!(
+                "PROXY protocol: {} forwarded {} to {}",
+                socket.client, addresses.client, addresses.server
+            );
+            Ok(addresses)
+        }
+        None => Ok(socket),
+    }
+}
+
+/// Reads a PROXY protocol v1 or v2 header from the `stream`, no byte after the header is read.
+///
+/// Returns [None] for headers without addresses, like LOCAL or UNKNOWN.
+///
+/// * `stream` - The connection.
+pub async fn read_header<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Option<Addresses>> {
+    let mut start = [0; 12];
+    stream.read_exact(&mut start).await?;
+
+    if start == V2_SIGNATURE {
+        let mut header = [0; 4];
+        stream.read_exact(&mut header).await?;
+
+        let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).await?;
+
+        return parse_v2(header[0], header[1], &body);
+    }
+
+    if !start.starts_with(b"PROXY ") {
+        return Err(invalid("Missing PROXY protocol header"));
+    }
+
+    let mut line = start.to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LENGTH {
+            return Err(invalid("PROXY protocol v1 header too long"));
+        }
+
+        let mut byte = [0; 1];
+        stream.read_exact(&mut byte).await?;
+        line.push(byte[0]);
+    }
+
+    parse_v1(&String::from_utf8_lossy(&line[..line.len() - 2]))
+}
+
+/// Parses a PROXY protocol v1 header line without the line terminator, like
+/// `PROXY TCP4 192.0.2.1 198.51.100.1 56324 21`.
+///
+/// * `line` - The header line.
+fn parse_v1(line: &str) -> io::Result<Option<Addresses>> {
+    let parts: Vec<&str> = line.split(' ').collect();
+
+    match parts.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", protocol @ ("TCP4" | "TCP6"), client_ip, server_ip, client_port, server_port] => {
+            let ip = |ip: &str| {
+                ip.parse::<IpAddr>()
+                    .ok()
+                    .filter(|ip| ip.is_ipv4() == (*protocol == "TCP4"))
+                    .ok_or_else(|| invalid("Invalid address in PROXY protocol header"))
+            };
+            let port = |port: &str| {
+                port.parse::<u16>()
+                    .map_err(|_| invalid("Invalid port in PROXY protocol header"))
+            };
+
+            Ok(Some(Addresses {
+                client: SocketAddr::new(ip(client_ip)?, port(client_port)?),
+                server: SocketAddr::new(ip(server_ip)?, port(server_port)?),
+            }))
+        }
+        _ => Err(invalid("Invalid PROXY protocol v1 header")),
+    }
+}
+
+/// Parses the body of a PROXY protocol v2 header.
+///
+/// * `version_command` - The version and command byte.
+/// * `family`          - The address family and protocol byte.
+/// * `body`            - The addresses and TLVs.
+fn parse_v2(version_command: u8, family: u8, body: &[u8]) -> io::Result<Option<Addresses>> {
+    if version_command >> 4 != 2 {
+        return Err(invalid("Invalid PROXY protocol v2 version"));
+    }
+
+    match version_command & 0x0f {
+        // LOCAL, e.g. health checks of the load balancer.
+        0 => return Ok(None),
+        1 => {}
+        _ => return Err(invalid("Invalid PROXY protocol v2 command")),
+    }
+
+    let port = |offset: usize| u16::from_be_bytes([body[offset], body[offset + 1]]);
+    match family {
+        0x11 if body.len() >= 12 => {
+            let client = Ipv4Addr::new(body[0], body[1], body[2], body[3]);
+            let server = Ipv4Addr::new(body[4], body[5], body[6], body[7]);
+
+            Ok(Some(Addresses {
+                client: SocketAddr::new(client.into(), port(8)),
+                server: SocketAddr::new(server.into(), port(10)),
+            }))
+        }
+        0x21 if body.len() >= 36 => {
+            let client: [u8; 16] = body[0..16].try_into().unwrap();
+            let server: [u8; 16] = body[16..32].try_into().unwrap();
+
+            Ok(Some(Addresses {
+                client: SocketAddr::new(Ipv6Addr::from(client).into(), port(32)),
+                server: SocketAddr::new(Ipv6Addr::from(server).into(), port(34)),
+            }))
+        }
+        // UNSPEC and other protocols than TCP have no usable addresses.
+        0x00 | 0x12 | 0x22 | 0x31 | 0x32 => Ok(None),
+        _ => Err(invalid("Invalid PROXY protocol v2 addresses")),
+    }
+}
+
+/// Returns an [io::ErrorKind::InvalidData] error with the `message`.
+///
+/// * `message` - The error message.
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use tokio::io::AsyncReadExt;
+
+    use crate::honeypot::proxy::{is_trusted, read_header, Addresses, V2_SIGNATURE};
+
+    fn addresses(client: &str, server: &str) -> Option<Addresses> {
+        Some(Addresses {
+            client: client.parse::<SocketAddr>().unwrap(),
+            server: server.parse::<SocketAddr>().unwrap(),
+        })
+    }
+
+    #[test]
+    fn trusted_sources() {
+        let trusted = vec![
+            "192.0.2.10".to_string(),
+            "10.0.0.0/8".to_string(),
+            "2001:db8::/32".to_string(),
+        ];
+
+        assert!(is_trusted("192.0.2.10".parse().unwrap(), &trusted));
+        assert!(is_trusted("10.20.30.40".parse().unwrap(), &trusted));
+        assert!(is_trusted("::ffff:10.1.1.1".parse().unwrap(), &trusted));
+        assert!(is_trusted("2001:db8::1".parse().unwrap(), &trusted));
+        assert!(!is_trusted("192.0.2.11".parse().unwrap(), &trusted));
+        assert!(!is_trusted("11.0.0.1".parse().unwrap(), &trusted));
+        assert!(!is_trusted("2001:db9::1".parse().unwrap(), &trusted));
+        assert!(is_trusted(
+            "8.8.8.8".parse().unwrap(),
+            &["0.0.0.0/0".to_string()]
+        ));
+        assert!(!is_trusted(
+            "8.8.8.8".parse().unwrap(),
+            &["invalid".to_string()]
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn v1_header() {
+        let mut stream: &[u8] = b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 21\r\nUSER a\r\n";
+
+        assert_eq!(
+            read_header(&mut stream).await.unwrap(),
+            addresses("192.0.2.1:56324", "198.51.100.1:21")
+        );
+
+        let mut rest = String::new();
+        stream.read_to_string(&mut rest).await.unwrap();
+        assert_eq!(rest, "USER a\r\n");
+
+        let mut stream: &[u8] = b"PROXY TCP6 2001:db8::1 2001:db8::2 4000 21\r\n";
+        assert_eq!(
+            read_header(&mut stream).await.unwrap(),
+            addresses("[2001:db8::1]:4000", "[2001:db8::2]:21")
+        );
+
+        let mut stream: &[u8] = b"PROXY UNKNOWN\r\n";
+        assert_eq!(read_header(&mut stream).await.unwrap(), None);
+    }
+
+    #[actix_rt::test]
+    async fn v2_header() {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x21, 0x11, 0x00, 0x0f]);
+        header.extend_from_slice(&[192, 0, 2, 1, 198, 51, 100, 1, 0xdc, 0x04, 0x00, 0x15]);
+        header.extend_from_slice(&[0x04, 0x00, 0x00]);
+        header.extend_from_slice(b"USER a\r\n");
+        let mut stream: &[u8] = &header;
+
+        assert_eq!(
+            read_header(&mut stream).await.unwrap(),
+            addresses("192.0.2.1:56324", "198.51.100.1:21")
+        );
+        assert_eq!(stream, b"USER a\r\n");
+
+        let mut local = V2_SIGNATURE.to_vec();
+        local.extend_from_slice(&[0x20, 0x00, 0x00, 0x00]);
+        let mut stream: &[u8] = &local;
+        assert_eq!(read_header(&mut stream).await.unwrap(), None);
+    }
+
+    #[actix_rt::test]
+    async fn invalid_headers() {
+        for header in [
+            &b"USER anonymous\r\n"[..],
+            b"PROXY TCP4 192.0.2.1 198.51.100.1 56324\r\n",
+            b"PROXY TCP4 2001:db8::1 198.51.100.1 56324 21\r\n",
+            b"PROXY TCP4 192.0.2.1 198.51.100.1 70000 21\r\n",
+            b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 21 and a lot more bytes to exceed the limit of the header",
+        ] {
+            let mut stream: &[u8] = header;
+            assert!(read_header(&mut stream).await.is_err());
+        }
+    }
+}
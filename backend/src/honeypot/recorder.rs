@@ -0,0 +1,405 @@
+//! Session recorder, which keeps a transcript of the control connection of each session
+//!
+//! While a session is [recorded][record], it is stored in the Sessions table and every command
+//! line read and reply written is collected with a timestamp. The collected lines are written to
+//! the Transcripts table after each command, so the order is kept even if sessions overlap. The
+//! recorder can be turned off in the configuration, then sessions are served without a record.
+//!
+//! If traffic capture is enabled as well, the bytes of the control and data connections are
+//! [captured][capture_control] to the Captures table, so the session can be exported as PCAP.
+//! TLS protected connections are captured after decryption.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+
+use chrono::Local;
+use log::info;
+use sea_orm::ActiveValue::Set;
+use sea_orm::NotSet;
+
+use crate::configuration::config::get_config;
+use crate::database::models::captures::{Channel, Sender};
+use crate::database::models::sessions::Outcome;
+use crate::database::models::transcripts::Direction;
+use crate::database::models::{captures, sessions, transcripts};
+use crate::database::service::DatabaseImplementation;
+use crate::database::service_trait::DatabaseTrait;
+use crate::honeypot::proxy::Addresses;
+
+tokio::task_local! {
+    /// The transcript of the session recorded by the current task.
+    static TRANSCRIPT: RefCell<Transcript>;
+}
+
+/// The transcript of a recorded session, holding the lines and captures which aren't stored yet.
+struct Transcript {
+    session_id: i64,
+    sequence: i32,
+    lines: Vec<transcripts::ActiveModel>,
+    /// The client and server address of the control connection, [None] if nothing is captured.
+    control: Option<(SocketAddr, SocketAddr)>,
+    capture_sequence: i32,
+    captures: Vec<captures::ActiveModel>,
+    /// Set by an anonymous login, see [mark_anonymous].
+    anonymous: bool,
+}
+
+impl Transcript {
+    /// Creates an empty transcript of the session with the `session_id`.
+    ///
+    /// * `session_id` - The id of the recorded session.
+    fn new(session_id: i64) -> Self {
+        Transcript {
+            session_id,
+            sequence: 0,
+            lines: vec![],
+            control: None,
+            capture_sequence: 0,
+            captures: vec![],
+            anonymous: false,
+        }
+    }
+}
+
+/// Captures the bytes of a data connection, up to the configured maximum payload.
+///
+/// Does nothing outside of a recorded session or if traffic capture is disabled.
+pub struct DataCapture {
+    addresses: Option<(SocketAddr, SocketAddr)>,
+    remaining: usize,
+    last_sender: Sender,
+}
+
+impl DataCapture {
+    /// Starts the capture of a data connection.
+    ///
+    /// * `addresses` - The [Addresses] of the data connection, as told by the PROXY protocol.
+    pub fn new(addresses: Addresses) -> Self {
+        let config = get_config();
+        let addresses = config
+            .capture_enabled
+            .then_some((addresses.client, addresses.server));
+
+        DataCapture {
+            addresses,
+            remaining: config.capture_max_payload,
+            last_sender: Sender::Server,
+        }
+    }
+
+    /// Captures the `payload` sent over the data connection, cut off at the maximum payload.
+    ///
+    /// * `sender`  - The [Sender] of the payload.
+    /// * `payload` - The bytes read or written.
+    pub fn capture(&mut self, sender: Sender, payload: &[u8]) {
+        let payload = &payload[..payload.len().min(self.remaining)];
+        if payload.is_empty() {
+            return;
+        }
+
+        if let Some(addresses) = self.addresses {
+            self.remaining -= payload.len();
+            self.last_sender = sender;
+            capture(Channel::Data, addresses, sender, payload);
+        }
+    }
+
+    /// Marks the end of the data connection, as closed by the side that sent last.
+    pub fn finish(self) {
+        if let Some(addresses) = self.addresses {
+            capture(Channel::Data, addresses, self.last_sender, &[]);
+        }
+    }
+}
+
+/// Serves the `session` and records it, if the recorder is enabled.
+///
+/// The session is stored before it is served, so sessions are kept even if they never end
+/// properly. When the `session` ends, the remaining transcript lines are stored and the session
+/// is completed with its [Outcome], the attacker of the IP, if known by then, and whether the
+/// client [logged in anonymously][mark_anonymous].
+///
+/// * `db`       - The [DatabaseImplementation]
+/// * `ip`       - The IP of the client.
+/// * `port`     - The port of the client.
+/// * `server`   - The address of the honeypot the client connected to, used for the capture.
+/// * `listener` - The name of the listener the session came in on.
+/// * `session`  - The session to serve, returning its [Outcome].
+pub async fn record<F>(
+    db: &DatabaseImplementation,
+    ip: &str,
+    port: u16,
+    server: Option<SocketAddr>,
+    listener: &str,
+    session: F,
+) where
+    F: Future<Output = Outcome>,
+{
+    if !get_config().recorder_enabled {
+        session.await;
+        return;
+    }
+
+    let mut started = db
+        .update_session(sessions::ActiveModel {
+            id: NotSet,
+            ip: Set(ip.to_string()),
+            port: Set(port as i32),
+            listener: Set(listener.to_string()),
+            attacker_id: Set(None),
+            outcome: Set(None),
+            anonymous: Set(false),
+            started: Set(Local::now()),
+            ended: Set(None),
+        })
+        .await;
+    let session_id = started.id.clone().unwrap();
+
+    let mut transcript = Transcript::new(session_id);
+    if get_config().capture_enabled {
+        transcript.control = match (ip.parse::<IpAddr>(), server) {
+            (Ok(client), Some(server)) => Some((SocketAddr::new(client, port), server)),
+            _ => None,
+        };
+    }
+
+    let (outcome, anonymous) = TRANSCRIPT
+        .scope(RefCell::new(transcript), async {
+            let outcome = session.await;
+            let closed_by = match outcome {
+                Outcome::Closed => Sender::Client,
+                _ => Sender::Server,
+            };
+            capture_control(closed_by, &[]);
+            flush(db).await;
+            let anonymous = TRANSCRIPT.with(|transcript| transcript.borrow().anonymous);
+            (outcome, anonymous)
+        })
+        .await;
+
+    info!("Session: {} of: {} ended: {}", session_id, ip, outcome);
+
+    started.attacker_id = Set(db.get_attacker_by_ip(ip).await.map(|model| model.id));
+    started.outcome = Set(Some(outcome.to_string()));
+    started.anonymous = Set(anonymous);
+    started.ended = Set(Some(Local::now()));
+    db.update_session(started).await;
+}
+
+/// Adds a change of the filesystem to the transcript of the current session and returns whether
+/// the filesystem was changed.
+///
+/// * `change` - The description of the change, [None] if the filesystem wasn't changed.
+pub fn record_change(change: Option<String>) -> bool {
+    match change {
+        Some(change) => {
+            record_line(Direction::Filesystem, &change);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Marks the current session as anonymous, after an anonymous login.
+///
+/// Does nothing outside of a recorded session, e.g. if the recorder is disabled.
+pub fn mark_anonymous() {
+    let _ = TRANSCRIPT.try_with(|transcript| transcript.borrow_mut().anonymous = true);
+}
+
+/// Adds a line to the transcript of the current session.
+///
+/// Does nothing outside of a recorded session, e.g. if the recorder is disabled.
+///
+/// * `direction`    - The [Direction] of the line.
+/// * `line`         - The command line or reply.
+pub fn record_line(direction: Direction, line: &str) {
+    let _ = TRANSCRIPT.try_with(|transcript| {
+        let mut transcript = transcript.borrow_mut();
+        transcript.sequence += 1;
+
+        let line = transcripts::ActiveModel {
+            id: NotSet,
+            session_id: Set(transcript.session_id),
+            sequence: Set(transcript.sequence),
+            direction: Set(direction.to_string()),
+            line: Set(line.to_string()),
+            created: Set(Local::now()),
+        };
+        transcript.lines.push(line);
+    });
+}
+
+/// Adds an encoded packet to the transcript of the current session, see [record_line].
+///
+/// The line terminators are removed, the lines of multi-line replies are kept apart by `\n`.
+///
+/// * `direction`    - The [Direction] of the packet.
+/// * `packet`       - The packet as sent over the connection.
+pub fn record_packet(direction: Direction, packet: &[u8]) {
+    let packet = String::from_utf8_lossy(packet);
+
+    record_line(
+        direction,
+        packet
+            .trim_end_matches("\r\n")
+            .replace("\r\n", "\n")
+            .as_str(),
+    );
+}
+
+/// Captures bytes of the control connection of the current session.
+///
+/// Does nothing outside of a recorded session or if traffic capture is disabled.
+///
+/// * `sender`       - The [Sender] of the bytes.
+/// * `payload`      - The bytes read or written, empty to mark the end of the connection.
+pub fn capture_control(sender: Sender, payload: &[u8]) {
+    let control = TRANSCRIPT
+        .try_with(|transcript| transcript.borrow().control)
+        .ok()
+        .flatten();
+
+    if let Some(addresses) = control {
+        capture(Channel::Control, addresses, sender, payload);
+    }
+}
+
+/// Adds a capture to the current session.
+///
+/// * `channel`      - The [Channel] the bytes were sent on.
+/// * `addresses`    - The client and server address of the connection.
+/// * `sender`       - The [Sender] of the bytes.
+/// * `payload`      - The bytes, empty to mark the end of the connection.
+fn capture(channel: Channel, addresses: (SocketAddr, SocketAddr), sender: Sender, payload: &[u8]) {
+    let _ = TRANSCRIPT.try_with(|transcript| {
+        let mut transcript = transcript.borrow_mut();
+        transcript.capture_sequence += 1;
+
+        let capture = captures::ActiveModel {
+            id: NotSet,
+            session_id: Set(transcript.session_id),
+            sequence: Set(transcript.capture_sequence),
+            channel: Set(channel.to_string()),
+            sender: Set(sender.to_string()),
+            client_address: Set(addresses.0.to_string()),
+            server_address: Set(addresses.1.to_string()),
+            payload: Set(payload.to_vec()),
+            created: Set(Local::now()),
+        };
+        transcript.captures.push(capture);
+    });
+}
+
+/// Takes the transcript lines of the current session which aren't stored yet.
+fn take_lines() -> Vec<transcripts::ActiveModel> {
+    TRANSCRIPT
+        .try_with(|transcript| std::mem::take(&mut transcript.borrow_mut().lines))
+        .unwrap_or_default()
+}
+
+/// Takes the captures of the current session which aren't stored yet.
+fn take_captures() -> Vec<captures::ActiveModel> {
+    TRANSCRIPT
+        .try_with(|transcript| std::mem::take(&mut transcript.borrow_mut().captures))
+        .unwrap_or_default()
+}
+
+/// Stores the collected transcript lines and captures of the current session.
+///
+/// * `db`       - The [DatabaseImplementation]
+pub async fn flush(db: &DatabaseImplementation) {
+    db.insert_transcript_lines(take_lines()).await;
+    db.insert_captures(take_captures()).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use sea_orm::ActiveValue::Set;
+
+    use crate::database::models::captures::Sender;
+    use crate::database::models::transcripts::Direction;
+    use crate::honeypot::recorder::{
+        capture_control, record_line, record_packet, take_captures, take_lines, Transcript,
+        TRANSCRIPT,
+    };
+
+    #[actix_rt::test]
+    async fn lines_are_recorded_in_order() {
+        let lines = TRANSCRIPT
+            .scope(RefCell::new(Transcript::new(7)), async {
+                record_line(Direction::Command, "USER root");
+                record_packet(Direction::Reply, b"211-Features:\r\n UTF8\r\n211 End\r\n");
+
+                let lines = take_lines();
+                assert!(take_lines().is_empty());
+                lines
+            })
+            .await;
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].session_id, Set(7));
+        assert_eq!(lines[0].sequence, Set(1));
+        assert_eq!(lines[0].direction, Set("command".to_string()));
+        assert_eq!(lines[0].line, Set("USER root".to_string()));
+        assert_eq!(lines[1].sequence, Set(2));
+        assert_eq!(lines[1].direction, Set("reply".to_string()));
+        assert_eq!(
+            lines[1].line,
+            Set("211-Features:\n UTF8\n211 End".to_string())
+        );
+    }
+
+    #[actix_rt::test]
+    async fn control_bytes_are_captured() {
+        let mut transcript = Transcript::new(3);
+        transcript.control = Some((
+            "10.0.0.1:40000".parse().unwrap(),
+            "10.0.0.2:21".parse().unwrap(),
+        ));
+
+        let captures = TRANSCRIPT
+            .scope(RefCell::new(transcript), async {
+                capture_control(Sender::Client, b"USER root\r\n");
+                capture_control(Sender::Server, &[]);
+                take_captures()
+            })
+            .await;
+
+        assert_eq!(captures.len(), 2);
+        assert_eq!(captures[0].session_id, Set(3));
+        assert_eq!(captures[0].sequence, Set(1));
+        assert_eq!(captures[0].channel, Set("control".to_string()));
+        assert_eq!(captures[0].sender, Set("client".to_string()));
+        assert_eq!(
+            captures[0].client_address,
+            Set("10.0.0.1:40000".to_string())
+        );
+        assert_eq!(captures[0].server_address, Set("10.0.0.2:21".to_string()));
+        assert_eq!(captures[0].payload, Set(b"USER root\r\n".to_vec()));
+        assert_eq!(captures[1].sender, Set("server".to_string()));
+        assert_eq!(captures[1].payload, Set(vec![]));
+    }
+
+    #[actix_rt::test]
+    async fn nothing_is_captured_if_capture_is_disabled() {
+        let captures = TRANSCRIPT
+            .scope(RefCell::new(Transcript::new(3)), async {
+                capture_control(Sender::Client, b"USER root\r\n");
+                take_captures()
+            })
+            .await;
+
+        assert!(captures.is_empty());
+    }
+
+    #[test]
+    fn nothing_is_recorded_outside_of_a_session() {
+        record_line(Direction::Command, "NOOP");
+
+        assert!(take_lines().is_empty());
+    }
+}
@@ -0,0 +1,371 @@
+//! Protocol sniffer, which classifies non-FTP traffic hitting the FTP port
+//!
+//! FTP clients wait for the banner, while HTTP, TLS, SSH, RDP and SMB clients send first. So the
+//! honeypot waits a moment for the client before greeting it, and [classifies][classify] the bytes
+//! sent in that time. Probes of other protocols are stored as events instead of being handled as
+//! FTP commands. HTTP probes can be answered with a 404 page, so follow-up requests on the same
+//! connection are stored as well.
+//!
+//! The wait delays the banner for FTP clients too, which scanners measuring the time to the banner
+//! can notice. So the sniffer is disabled by default and the wait should be kept short, at the
+//! cost of missing probes that are sent later.
+
+use std::time::Duration;
+
+use log::info;
+use serde_json::{json, Value};
+use strum_macros::Display;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::timeout;
+
+use crate::configuration::config::get_config;
+use crate::database::models::captures::Sender;
+use crate::database::models::events::EventKind;
+use crate::database::models::transcripts::Direction;
+use crate::database::service::DatabaseImplementation;
+use crate::database::service_trait::DatabaseTrait;
+use crate::honeypot::ftp_event::save_event_for_ip;
+use crate::honeypot::recorder::{capture_control, record_line};
+use crate::honeypot::stream::FtpStream;
+
+/// The methods an HTTP request can start with.
+const HTTP_METHODS: [&str; 9] = [
+    "GET", "POST", "HEAD", "PUT", "DELETE", "OPTIONS", "CONNECT", "TRACE", "PATCH",
+];
+/// The maximum size of the head of an HTTP request.
+const MAX_HTTP_HEAD: usize = 8192;
+/// The maximum size of an HTTP request body, which is read to get to the next request.
+const MAX_HTTP_BODY: usize = 65536;
+/// The maximum number of HTTP requests answered on a single connection.
+const MAX_HTTP_REQUESTS: usize = 20;
+
+/// The body of the 404 page HTTP probes are answered with.
+const NOT_FOUND_PAGE: &str = "<html>\r\n<head><title>404 Not Found</title></head>\r\n<body>\r\n\
+<center><h1>404 Not Found</h1></center>\r\n<hr><center>nginx</center>\r\n</body>\r\n</html>\r\n";
+
+/// The protocols the sniffer recognizes.
+#[derive(Clone, Copy, Debug, Display, PartialEq)]
+#[strum(serialize_all = "snake_case")]
+pub enum Protocol {
+    Http,
+    Tls,
+    Ssh,
+    Rdp,
+    Smb,
+}
+
+/// The head of an HTTP request.
+#[derive(Debug, PartialEq)]
+struct HttpRequest {
+    method: String,
+    path: String,
+    version: String,
+    host: Option<String>,
+    user_agent: Option<String>,
+    content_length: usize,
+    /// The length of the request line and headers, including the empty line.
+    head_length: usize,
+}
+
+/// Returns the [Protocol] of the first bytes a client sent, or [None] if it may be FTP.
+///
+/// * `bytes` - The first bytes of the connection.
+pub fn classify(bytes: &[u8]) -> Option<Protocol> {
+    if HTTP_METHODS.iter().any(|method| {
+        bytes.len() > method.len()
+            && bytes[..method.len()].eq_ignore_ascii_case(method.as_bytes())
+            && bytes[method.len()] == b' '
+    }) {
+        return Some(Protocol::Http);
+    }
+
+    match bytes {
+        [0x16, 0x03, minor, ..] if *minor <= 0x04 => Some(Protocol::Tls),
+        [b'S', b'S', b'H', b'-', ..] => Some(Protocol::Ssh),
+        [0x03, 0x00, ..] => Some(Protocol::Rdp),
+        [0x00, _, _, _, 0xff | 0xfe, b'S', b'M', b'B', ..] => Some(Protocol::Smb),
+        _ => None,
+    }
+}
+
+/// Waits for the client to send first and returns the [Protocol] of its bytes.
+///
+/// Returns [None] if the sniffer is disabled, the connection is TLS protected, the client sent
+/// nothing within the configured wait or the bytes may be FTP. The bytes are only peeked, so they
+/// are read again as FTP commands or as probe.
+///
+/// * `stream` - The [FtpStream] of the new connection.
+pub async fn sniff(stream: &FtpStream) -> Option<Protocol> {
+    let config = get_config();
+    if !config.sniffer_enabled || stream.is_tls() {
+        return None;
+    }
+
+    let mut buf = [0; 16];
+    let wait = Duration::from_millis(config.sniffer_wait);
+    match timeout(wait, stream.peek(&mut buf)).await {
+        Ok(Ok(n)) => classify(&buf[..n]),
+        _ => None,
+    }
+}
+
+/// Reads the probe of the `protocol` and stores it as event.
+///
+/// HTTP requests are read one by one and answered with a 404 page, if configured. Probes of the
+/// other protocols are read once, then the connection is closed.
+///
+/// * `stream`   - The [FtpStream] of the probe.
+/// * `ip`       - The IP of the client.
+/// * `db`       - The [DatabaseImplementation]
+/// * `protocol` - The [Protocol] the probe was classified as.
+pub async fn handle_probe(
+    stream: &mut FtpStream,
+    ip: &str,
+    db: &DatabaseImplementation,
+    protocol: Protocol,
+) {
+    let config = get_config();
+    let read_timeout = Duration::from_secs(config.timeout_command);
+    let attacker_id = db.get_attacker_by_ip(ip).await.map(|model| model.id);
+    info!("Probe of protocol: {} from: {}", protocol, ip);
+
+    let mut buffer = vec![];
+    if protocol != Protocol::Http {
+        if read_more(stream, &mut buffer, read_timeout).await {
+            let data = probe_data(protocol, &buffer, config.sniffer_max_bytes);
+            save_event_for_ip(db, ip, attacker_id, EventKind::Probe, data).await;
+        }
+
+        return;
+    }
+
+    for _ in 0..MAX_HTTP_REQUESTS {
+        let request = loop {
+            if let Some(request) = parse_http_request(&buffer) {
+                break request;
+            }
+
+            if buffer.len() >= MAX_HTTP_HEAD || !read_more(stream, &mut buffer, read_timeout).await
+            {
+                if !buffer.is_empty() {
+                    let data = probe_data(protocol, &buffer, config.sniffer_max_bytes);
+                    save_event_for_ip(db, ip, attacker_id, EventKind::Probe, data).await;
+                }
+
+                return;
+            }
+        };
+
+        let length = request.head_length + request.content_length.min(MAX_HTTP_BODY);
+        while buffer.len() < length {
+            if !read_more(stream, &mut buffer, read_timeout).await {
+                break;
+            }
+        }
+        let raw: Vec<u8> = buffer.drain(..length.min(buffer.len())).collect();
+
+        record_line(
+            Direction::Command,
+            &format!("{} {} {}", request.method, request.path, request.version),
+        );
+        let data = probe_data(protocol, &raw, config.sniffer_max_bytes);
+        save_event_for_ip(db, ip, attacker_id, EventKind::Probe, data).await;
+
+        if !config.sniffer_http_response {
+            return;
+        }
+
+        let response = format!(
+            "HTTP/1.1 404 Not Found\r\nServer: nginx\r\nContent-Type: text/html\r\n\
+Content-Length: {}\r\nConnection: keep-alive\r\n\r\n{}",
+            NOT_FOUND_PAGE.len(),
+            if request.method == "HEAD" {
+                ""
+            } else {
+                NOT_FOUND_PAGE
+            }
+        );
+        if stream.write_all(response.as_bytes()).await.is_err() {
+            return;
+        }
+        capture_control(Sender::Server, response.as_bytes());
+        record_line(Direction::Reply, "HTTP/1.1 404 Not Found");
+    }
+}
+
+/// Reads more bytes of the probe into the `buffer`.
+///
+/// Returns `false` if the connection was closed, failed or nothing was sent in time.
+///
+/// * `stream`       - The [FtpStream] of the probe.
+/// * `buffer`       - The buffer of the bytes read so far.
+/// * `read_timeout` - The time to wait for more bytes.
+async fn read_more(stream: &mut FtpStream, buffer: &mut Vec<u8>, read_timeout: Duration) -> bool {
+    let mut chunk = [0; 1024];
+
+    match timeout(read_timeout, stream.read(&mut chunk)).await {
+        Ok(Ok(n)) if n > 0 => {
+            capture_control(Sender::Client, &chunk[..n]);
+            buffer.extend_from_slice(&chunk[..n]);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Parses the head of an HTTP request, returns [None] if it isn't complete yet or invalid.
+///
+/// * `bytes` - The bytes read from the connection.
+fn parse_http_request(bytes: &[u8]) -> Option<HttpRequest> {
+    let head_length = bytes.windows(4).position(|w| w == b"\r\n\r\n")? + 4;
+    let head = String::from_utf8_lossy(&bytes[..head_length]);
+    let mut lines = head.split("\r\n");
+
+    let mut request_line = lines.next()?.splitn(3, ' ');
+    let mut request = HttpRequest {
+        method: request_line.next()?.to_ascii_uppercase(),
+        path: request_line.next()?.to_string(),
+        version: request_line.next().unwrap_or("HTTP/0.9").to_string(),
+        host: None,
+        user_agent: None,
+        content_length: 0,
+        head_length,
+    };
+
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().to_string();
+
+        match name.trim().to_ascii_lowercase().as_str() {
+            "host" => request.host = Some(value),
+            "user-agent" => request.user_agent = Some(value),
+            "content-length" => request.content_length = value.parse().unwrap_or(0),
+            _ => {}
+        }
+    }
+
+    Some(request)
+}
+
+/// Returns the details of a probe stored with its event.
+///
+/// The first `max_bytes` are stored as hex. HTTP requests add the method, path, host and user
+/// agent, SSH the version line and RDP the `mstshash` cookie, if present.
+///
+/// * `protocol`  - The [Protocol] of the probe.
+/// * `bytes`     - The bytes of the probe.
+/// * `max_bytes` - The maximum number of bytes stored.
+fn probe_data(protocol: Protocol, bytes: &[u8], max_bytes: usize) -> Value {
+    let mut data = json!({
+        "protocol": protocol.to_string(),
+        "length": bytes.len(),
+        "bytes": hex::encode(&bytes[..bytes.len().min(max_bytes)]),
+    });
+
+    let text = String::from_utf8_lossy(bytes);
+    match protocol {
+        Protocol::Http => {
+            if let Some(request) = parse_http_request(bytes) {
+                data["method"] = json!(request.method);
+                data["path"] = json!(request.path);
+                data["version"] = json!(request.version);
+                data["host"] = json!(request.host);
+                data["user_agent"] = json!(request.user_agent);
+            }
+        }
+        Protocol::Ssh => {
+            data["version"] = json!(text.lines().next().unwrap_or_default().trim_end());
+        }
+        Protocol::Rdp => {
+            if let Some(start) = text.find("Cookie: mstshash=") {
+                let cookie = &text[start + "Cookie: mstshash=".len()..];
+                data["cookie"] = json!(cookie.split("\r\n").next().unwrap_or_default());
+            }
+        }
+        Protocol::Tls | Protocol::Smb => {}
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::honeypot::sniffer::{classify, parse_http_request, probe_data, Protocol};
+
+    #[test]
+    fn protocols_are_classified() {
+        assert_eq!(classify(b"GET / HTTP/1.1\r\n"), Some(Protocol::Http));
+        assert_eq!(classify(b"post /login HTTP/1.0\r\n"), Some(Protocol::Http));
+        assert_eq!(
+            classify(&[0x16, 0x03, 0x01, 0x02, 0x00]),
+            Some(Protocol::Tls)
+        );
+        assert_eq!(classify(b"SSH-2.0-Go\r\n"), Some(Protocol::Ssh));
+        assert_eq!(
+            classify(&[0x03, 0x00, 0x00, 0x2b, 0x26, 0xe0]),
+            Some(Protocol::Rdp)
+        );
+        assert_eq!(
+            classify(&[0x00, 0x00, 0x00, 0x85, 0xff, b'S', b'M', b'B', 0x72]),
+            Some(Protocol::Smb)
+        );
+        assert_eq!(
+            classify(&[0x00, 0x00, 0x00, 0x66, 0xfe, b'S', b'M', b'B', 0x40]),
+            Some(Protocol::Smb)
+        );
+    }
+
+    #[test]
+    fn ftp_is_not_classified() {
+        assert_eq!(classify(b"USER anonymous\r\n"), None);
+        assert_eq!(classify(b"OPTS UTF8 ON\r\n"), None);
+        assert_eq!(classify(b"GETX\r\n"), None);
+        assert_eq!(classify(b""), None);
+    }
+
+    #[test]
+    fn http_request_is_parsed() {
+        let request = parse_http_request(
+            b"GET /admin HTTP/1.1\r\nHost: 10.0.0.2:21\r\nUser-Agent: zgrab/0.x\r\n\
+Content-Length: 3\r\n\r\nabcGET",
+        )
+        .unwrap();
+
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/admin");
+        assert_eq!(request.version, "HTTP/1.1");
+        assert_eq!(request.host.as_deref(), Some("10.0.0.2:21"));
+        assert_eq!(request.user_agent.as_deref(), Some("zgrab/0.x"));
+        assert_eq!(request.content_length, 3);
+        assert_eq!(request.head_length, 84);
+
+        assert_eq!(parse_http_request(b"GET / HTTP/1.1\r\nHost: a"), None);
+    }
+
+    #[test]
+    fn probe_details_are_extracted() {
+        let http = probe_data(
+            Protocol::Http,
+            b"GET /.env HTTP/1.1\r\nUser-Agent: curl/8.0\r\n\r\n",
+            4,
+        );
+        assert_eq!(http["protocol"], json!("http"));
+        assert_eq!(http["bytes"], json!("47455420"));
+        assert_eq!(http["length"], json!(44));
+        assert_eq!(http["path"], json!("/.env"));
+        assert_eq!(http["user_agent"], json!("curl/8.0"));
+        assert_eq!(http["host"], json!(null));
+
+        let ssh = probe_data(Protocol::Ssh, b"SSH-2.0-libssh_0.9.6\r\n", 64);
+        assert_eq!(ssh["version"], json!("SSH-2.0-libssh_0.9.6"));
+
+        let mut rdp = vec![0x03, 0x00, 0x00, 0x2b, 0x26, 0xe0, 0x00, 0x00];
+        rdp.extend_from_slice(b"Cookie: mstshash=Administr\r\n");
+        let rdp = probe_data(Protocol::Rdp, &rdp, 64);
+        assert_eq!(rdp["cookie"], json!("Administr"));
+    }
+}
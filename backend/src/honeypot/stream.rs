@@ -0,0 +1,221 @@
+//! The control and data streams, which can be upgraded to TLS in place
+
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+use crate::honeypot::tls::ClientHello;
+
+/// The TLS record content type of handshake messages.
+const HANDSHAKE_RECORD: u8 = 22;
+/// The maximum amount of TLS records read to find a complete ClientHello.
+const MAX_HELLO_RECORDS: usize = 4;
+
+/// A [TcpStream] that returns already read bytes first.
+///
+/// Used to replay the ClientHello to the TLS acceptor after it was read for fingerprinting.
+pub struct Rewind {
+    prefix: Vec<u8>,
+    position: usize,
+    inner: TcpStream,
+}
+
+impl AsyncRead for Rewind {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if this.position < this.prefix.len() {
+            let remaining = &this.prefix[this.position..];
+            let n = remaining.len().min(buf.remaining());
+            buf.put_slice(&remaining[..n]);
+            this.position += n;
+
+            return Poll::Ready(Ok(()));
+        }
+
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Rewind {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// A control or data connection with the client, either in plain text or TLS protected.
+pub enum FtpStream {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<Rewind>>),
+    /// The stream was lost during a failed TLS upgrade.
+    Closed,
+}
+
+impl FtpStream {
+    /// Returns the local address of the connection.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            FtpStream::Plain(stream) => stream.local_addr(),
+            FtpStream::Tls(stream) => stream.get_ref().0.inner.local_addr(),
+            FtpStream::Closed => Err(io::ErrorKind::NotConnected.into()),
+        }
+    }
+
+    /// Returns the address of the client.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            FtpStream::Plain(stream) => stream.peer_addr(),
+            FtpStream::Tls(stream) => stream.get_ref().0.inner.peer_addr(),
+            FtpStream::Closed => Err(io::ErrorKind::NotConnected.into()),
+        }
+    }
+
+    /// Reads bytes from the connection without removing them, so they are read again later.
+    ///
+    /// Only plain text connections can be peeked.
+    ///
+    /// * `buf` - The buffer to read into.
+    pub async fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            FtpStream::Plain(stream) => stream.peek(buf).await,
+            _ => Err(io::ErrorKind::Unsupported.into()),
+        }
+    }
+
+    /// Returns whether the connection is TLS protected.
+    pub fn is_tls(&self) -> bool {
+        matches!(self, FtpStream::Tls(_))
+    }
+
+    /// Upgrades the plain text connection to TLS in place.
+    ///
+    /// The ClientHello is read first and parsed into a [ClientHello], so it can be fingerprinted.
+    /// Afterwards the bytes are replayed to the TLS acceptor. If the handshake fails, the stream
+    /// is [closed][FtpStream::Closed].
+    ///
+    /// * `acceptor` - The [TlsAcceptor] holding the server certificate.
+    pub async fn upgrade(&mut self, acceptor: &TlsAcceptor) -> io::Result<Option<ClientHello>> {
+        let mut tcp = match std::mem::replace(self, FtpStream::Closed) {
+            FtpStream::Plain(tcp) => tcp,
+            other => {
+                *self = other;
+                return Err(io::Error::other("Connection is not in plain text"));
+            }
+        };
+
+        let (raw, handshake) = read_client_hello(&mut tcp).await?;
+        let client_hello = ClientHello::parse(&handshake);
+
+        let rewind = Rewind {
+            prefix: raw,
+            position: 0,
+            inner: tcp,
+        };
+        let tls = acceptor.accept(rewind).await?;
+        *self = FtpStream::Tls(Box::new(tls));
+
+        Ok(client_hello)
+    }
+}
+
+/// Reads the TLS records holding the ClientHello from the `tcp` stream.
+///
+/// Returns the raw bytes read and the reassembled handshake message.
+///
+/// * `tcp`  - The [TcpStream] the client starts the handshake on.
+async fn read_client_hello(tcp: &mut TcpStream) -> io::Result<(Vec<u8>, Vec<u8>)> {
+    let mut raw = vec![];
+    let mut handshake = vec![];
+
+    for _ in 0..MAX_HELLO_RECORDS {
+        let mut header = [0; 5];
+        tcp.read_exact(&mut header).await?;
+        raw.extend_from_slice(&header);
+
+        if header[0] != HANDSHAKE_RECORD {
+            break;
+        }
+
+        let length = u16::from_be_bytes([header[3], header[4]]) as usize;
+        let mut body = vec![0; length];
+        tcp.read_exact(&mut body).await?;
+        raw.extend_from_slice(&body);
+        handshake.extend_from_slice(&body);
+
+        if handshake.len() >= 4 {
+            let message_length =
+                u32::from_be_bytes([0, handshake[1], handshake[2], handshake[3]]) as usize;
+            if handshake.len() >= message_length + 4 {
+                break;
+            }
+        }
+    }
+
+    Ok((raw, handshake))
+}
+
+impl AsyncRead for FtpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            FtpStream::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            FtpStream::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+            FtpStream::Closed => Poll::Ready(Err(io::ErrorKind::NotConnected.into())),
+        }
+    }
+}
+
+impl AsyncWrite for FtpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            FtpStream::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            FtpStream::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+            FtpStream::Closed => Poll::Ready(Err(io::ErrorKind::NotConnected.into())),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            FtpStream::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            FtpStream::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+            FtpStream::Closed => Poll::Ready(Err(io::ErrorKind::NotConnected.into())),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            FtpStream::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            FtpStream::Tls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+            FtpStream::Closed => Poll::Ready(Ok(())),
+        }
+    }
+}
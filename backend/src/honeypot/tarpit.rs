@@ -0,0 +1,124 @@
+//! Tarpit, which slows down brute-forcers
+//!
+//! Sessions of IPs with more login attempts than the threshold are tarpitted: their failed logins
+//! are answered with a growing delay and the welcome message is trickled in byte by byte. Only a
+//! limited number of sessions is tarpitted at once, so the honeypot itself stays responsive.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::configuration::config::{get_config, Config};
+
+/// The number of currently tarpitted sessions.
+static TARPITTED_SESSIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Marks a session as tarpitted, the slot is released when it's dropped with the session.
+#[derive(Debug)]
+pub struct TarpitSlot(());
+
+impl Drop for TarpitSlot {
+    fn drop(&mut self) {
+        TARPITTED_SESSIONS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Returns a [TarpitSlot] if the session has to be tarpitted, as configured by [get_config].
+///
+/// * `login_attempts` - The login attempts of the IP of the session.
+pub fn enter(login_attempts: i32) -> Option<TarpitSlot> {
+    enter_with_config(&get_config(), login_attempts)
+}
+
+/// Returns a [TarpitSlot] if the tarpit is enabled, the `login_attempts` reach the threshold and
+/// the maximum of tarpitted sessions isn't reached yet.
+///
+/// * `config`         - The [Config] holding the tarpit settings.
+/// * `login_attempts` - The login attempts of the IP of the session.
+fn enter_with_config(config: &Config, login_attempts: i32) -> Option<TarpitSlot> {
+    if !config.tarpit_enabled || login_attempts < config.tarpit_threshold {
+        return None;
+    }
+
+    TARPITTED_SESSIONS
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |sessions| {
+            (sessions < config.tarpit_max_sessions).then_some(sessions + 1)
+        })
+        .ok()
+        .map(|_| TarpitSlot(()))
+}
+
+/// Returns the delay of a failed login reply, as configured by [get_config].
+///
+/// * `login_attempts` - The login attempts of the IP of the session.
+pub fn login_delay(login_attempts: i32) -> Duration {
+    login_delay_with_config(&get_config(), login_attempts)
+}
+
+/// Returns the delay of a failed login reply, which doubles with every attempt over the
+/// threshold up to the maximum delay.
+///
+/// * `config`         - The [Config] holding the tarpit settings.
+/// * `login_attempts` - The login attempts of the IP of the session.
+fn login_delay_with_config(config: &Config, login_attempts: i32) -> Duration {
+    let exponent = (login_attempts - config.tarpit_threshold).clamp(0, 32) as u32;
+    let delay = config
+        .tarpit_delay
+        .saturating_mul(2u64.saturating_pow(exponent));
+
+    Duration::from_millis(delay.min(config.tarpit_max_delay))
+}
+
+/// Returns the delay between the bytes of the welcome message, as configured by [get_config].
+pub fn banner_byte_delay() -> Duration {
+    Duration::from_millis(get_config().tarpit_banner_byte_delay)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::configuration::config::Config;
+    use crate::honeypot::tarpit::{enter_with_config, login_delay_with_config};
+
+    #[test]
+    fn login_delay_grows_up_to_max() {
+        let config = Config::new("application-test.toml");
+
+        assert_eq!(
+            login_delay_with_config(&config, 5),
+            Duration::from_millis(10)
+        );
+        assert_eq!(
+            login_delay_with_config(&config, 6),
+            Duration::from_millis(20)
+        );
+        assert_eq!(
+            login_delay_with_config(&config, 8),
+            Duration::from_millis(80)
+        );
+        assert_eq!(
+            login_delay_with_config(&config, 9),
+            Duration::from_millis(100)
+        );
+        assert_eq!(
+            login_delay_with_config(&config, i32::MAX),
+            Duration::from_millis(100)
+        );
+    }
+
+    #[test]
+    fn tarpitted_sessions_are_limited() {
+        let config = Config::new("application-test.toml");
+
+        assert!(enter_with_config(&config, 4).is_none());
+
+        let first = enter_with_config(&config, 5);
+        let second = enter_with_config(&config, 100);
+        assert!(first.is_some());
+        assert!(second.is_some());
+        assert!(enter_with_config(&config, 5).is_none());
+
+        drop(first);
+        assert!(enter_with_config(&config, 5).is_some());
+    }
+}
@@ -0,0 +1,432 @@
+//! TLS setup for FTPS and fingerprinting of the ClientHello
+
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+
+use log::{error, info};
+use md5::{Digest, Md5};
+use sha2::Sha256;
+use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+use crate::configuration::config::get_config;
+
+/// The handshake type of a ClientHello.
+const CLIENT_HELLO: u8 = 1;
+/// The extension types read from the ClientHello.
+const SERVER_NAME: u16 = 0;
+const SUPPORTED_GROUPS: u16 = 10;
+const EC_POINT_FORMATS: u16 = 11;
+const SIGNATURE_ALGORITHMS: u16 = 13;
+const ALPN: u16 = 16;
+const SUPPORTED_VERSIONS: u16 = 43;
+
+/// Creates a [TlsAcceptor] used for FTPS.
+///
+/// The given certificate and private key are loaded. If no certificate is given, a self-signed
+/// certificate for the configured common name is generated.
+/// Returns [None] if the certificate can't be loaded.
+///
+/// * `certificate_path` - The path to the PEM certificate chain, may be empty.
+/// * `private_key_path` - The path to the PEM private key of the certificate.
+pub fn create_tls_acceptor(certificate_path: &str, private_key_path: &str) -> Option<TlsAcceptor> {
+    let (certificates, key) = if certificate_path.is_empty() {
+        let common_name = get_config().tls_common_name;
+        info!("Generating self-signed certificate for: {}", common_name);
+        generate_certificate(&common_name)?
+    } else {
+        load_certificate(certificate_path, private_key_path)?
+    };
+
+    let server_config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certificates, key);
+
+    match server_config {
+        Ok(server_config) => Some(TlsAcceptor::from(Arc::new(server_config))),
+        Err(e) => {
+            error!("Invalid TLS certificate: {}", e);
+            None
+        }
+    }
+}
+
+/// Generates a self-signed certificate and its private key.
+///
+/// * `common_name` - The name the certificate is issued for.
+fn generate_certificate(common_name: &str) -> Option<(Vec<Certificate>, PrivateKey)> {
+    let certificate = match rcgen::generate_simple_self_signed(vec![common_name.to_string()]) {
+        Ok(certificate) => certificate,
+        Err(e) => {
+            error!("Could not generate certificate: {}", e);
+            return None;
+        }
+    };
+
+    let der = certificate.serialize_der().ok()?;
+    let key = certificate.serialize_private_key_der();
+
+    Some((vec![Certificate(der)], PrivateKey(key)))
+}
+
+/// Loads a PEM certificate chain and private key from the filesystem.
+///
+/// * `certificate_path` - The path to the certificate chain.
+/// * `private_key_path` - The path to the PKCS#8 or RSA private key.
+fn load_certificate(
+    certificate_path: &str,
+    private_key_path: &str,
+) -> Option<(Vec<Certificate>, PrivateKey)> {
+    let certificates = File::open(certificate_path)
+        .and_then(|file| rustls_pemfile::certs(&mut BufReader::new(file)));
+    let keys = File::open(private_key_path).and_then(|file| {
+        let mut reader = BufReader::new(file);
+        let mut keys = rustls_pemfile::pkcs8_private_keys(&mut reader)?;
+        if keys.is_empty() {
+            let mut reader = BufReader::new(File::open(private_key_path)?);
+            keys = rustls_pemfile::rsa_private_keys(&mut reader)?;
+        }
+        Ok(keys)
+    });
+
+    match (certificates, keys) {
+        (Ok(certificates), Ok(mut keys)) if !certificates.is_empty() && !keys.is_empty() => Some((
+            certificates.into_iter().map(Certificate).collect(),
+            PrivateKey(keys.remove(0)),
+        )),
+        _ => {
+            error!(
+                "Could not load certificate {} with private key {}!",
+                certificate_path, private_key_path
+            );
+            None
+        }
+    }
+}
+
+/// Returns whether the value is a GREASE value (RFC 8701), which is ignored for fingerprints.
+fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+/// A minimal reader for the big-endian fields of a handshake message.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.data.len() < n {
+            return None;
+        }
+
+        let (taken, rest) = self.data.split_at(n);
+        self.data = rest;
+        Some(taken)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.take(2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u16_list(data: &[u8]) -> Vec<u16> {
+        data.chunks_exact(2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+            .collect()
+    }
+}
+
+/// The fields of a TLS ClientHello used to fingerprint the client's TLS library.
+#[derive(Debug, Default, PartialEq)]
+pub struct ClientHello {
+    pub version: u16,
+    pub cipher_suites: Vec<u16>,
+    pub extensions: Vec<u16>,
+    pub supported_groups: Vec<u16>,
+    pub ec_point_formats: Vec<u8>,
+    pub signature_algorithms: Vec<u16>,
+    pub supported_versions: Vec<u16>,
+    pub alpn: Vec<String>,
+    pub server_name: Option<String>,
+}
+
+impl ClientHello {
+    /// Parses a ClientHello handshake message.
+    ///
+    /// Returns [None] if the message isn't a valid ClientHello.
+    ///
+    /// * `handshake` - The handshake message including its 4 byte header.
+    pub fn parse(handshake: &[u8]) -> Option<Self> {
+        let mut reader = Reader { data: handshake };
+
+        if reader.u8()? != CLIENT_HELLO {
+            return None;
+        }
+        reader.take(3)?;
+
+        let mut hello = ClientHello {
+            version: reader.u16()?,
+            ..Default::default()
+        };
+
+        reader.take(32)?;
+        let session_id_length = reader.u8()? as usize;
+        reader.take(session_id_length)?;
+
+        let cipher_suites_length = reader.u16()? as usize;
+        hello.cipher_suites = Reader::u16_list(reader.take(cipher_suites_length)?);
+
+        let compression_length = reader.u8()? as usize;
+        reader.take(compression_length)?;
+
+        if reader.data.is_empty() {
+            return Some(hello);
+        }
+
+        let extensions_length = reader.u16()? as usize;
+        let mut extensions = Reader {
+            data: reader.take(extensions_length)?,
+        };
+
+        while !extensions.data.is_empty() {
+            let extension_type = extensions.u16()?;
+            let length = extensions.u16()? as usize;
+            let mut data = Reader {
+                data: extensions.take(length)?,
+            };
+            hello.extensions.push(extension_type);
+
+            match extension_type {
+                SERVER_NAME => {
+                    data.take(3)?;
+                    let name_length = data.u16()? as usize;
+                    let name = data.take(name_length)?;
+                    hello.server_name = Some(String::from_utf8_lossy(name).to_string());
+                }
+                SUPPORTED_GROUPS => {
+                    let list_length = data.u16()? as usize;
+                    hello.supported_groups = Reader::u16_list(data.take(list_length)?);
+                }
+                EC_POINT_FORMATS => {
+                    let list_length = data.u8()? as usize;
+                    hello.ec_point_formats = data.take(list_length)?.to_vec();
+                }
+                SIGNATURE_ALGORITHMS => {
+                    let list_length = data.u16()? as usize;
+                    hello.signature_algorithms = Reader::u16_list(data.take(list_length)?);
+                }
+                ALPN => {
+                    let list_length = data.u16()? as usize;
+                    let mut list = Reader {
+                        data: data.take(list_length)?,
+                    };
+                    while !list.data.is_empty() {
+                        let protocol_length = list.u8()? as usize;
+                        let protocol = list.take(protocol_length)?;
+                        hello
+                            .alpn
+                            .push(String::from_utf8_lossy(protocol).to_string());
+                    }
+                }
+                SUPPORTED_VERSIONS => {
+                    let list_length = data.u8()? as usize;
+                    hello.supported_versions = Reader::u16_list(data.take(list_length)?);
+                }
+                _ => {}
+            }
+        }
+
+        Some(hello)
+    }
+
+    /// Returns the JA3 string: version, ciphers, extensions, groups and point formats.
+    pub fn ja3(&self) -> String {
+        let join = |values: Vec<String>| values.join("-");
+        let without_grease = |values: &[u16]| {
+            values
+                .iter()
+                .filter(|value| !is_grease(**value))
+                .map(|value| value.to_string())
+                .collect::<Vec<String>>()
+        };
+
+        format!(
+            "{},{},{},{},{}",
+            self.version,
+            join(without_grease(&self.cipher_suites)),
+            join(without_grease(&self.extensions)),
+            join(without_grease(&self.supported_groups)),
+            join(
+                self.ec_point_formats
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect()
+            )
+        )
+    }
+
+    /// Returns the MD5 hash of the [JA3 string][ClientHello::ja3].
+    pub fn ja3_hash(&self) -> String {
+        hex::encode(Md5::digest(self.ja3().as_bytes()))
+    }
+
+    /// Returns the JA4 fingerprint in the form `t13d1516h2_<ciphers>_<extensions>`.
+    pub fn ja4(&self) -> String {
+        let version = self
+            .supported_versions
+            .iter()
+            .copied()
+            .filter(|value| !is_grease(*value))
+            .max()
+            .unwrap_or(self.version);
+        let version = match version {
+            0x0304 => "13",
+            0x0303 => "12",
+            0x0302 => "11",
+            0x0301 => "10",
+            0x0300 => "s3",
+            _ => "00",
+        };
+
+        let mut ciphers: Vec<u16> = self
+            .cipher_suites
+            .iter()
+            .copied()
+            .filter(|value| !is_grease(*value))
+            .collect();
+        let mut extensions: Vec<u16> = self
+            .extensions
+            .iter()
+            .copied()
+            .filter(|value| !is_grease(*value))
+            .collect();
+
+        let alpn = match self.alpn.first().map(|alpn| alpn.as_bytes()) {
+            Some([first, .., last])
+                if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() =>
+            {
+                format!("{}{}", *first as char, *last as char)
+            }
+            Some([single]) if single.is_ascii_alphanumeric() => {
+                format!("{}{}", *single as char, *single as char)
+            }
+            Some([first, .., last]) => format!("{:x}{:x}", first >> 4, last & 0x0f),
+            _ => "00".to_string(),
+        };
+
+        let ja4_a = format!(
+            "t{}{}{:02}{:02}{}",
+            version,
+            if self.server_name.is_some() { "d" } else { "i" },
+            ciphers.len().min(99),
+            extensions.len().min(99),
+            alpn
+        );
+
+        ciphers.sort_unstable();
+        extensions.retain(|value| *value != SERVER_NAME && *value != ALPN);
+        extensions.sort_unstable();
+
+        let to_hex = |values: &[u16]| {
+            values
+                .iter()
+                .map(|value| format!("{:04x}", value))
+                .collect::<Vec<String>>()
+                .join(",")
+        };
+        let truncated_hash = |value: String| {
+            if value.is_empty() {
+                "000000000000".to_string()
+            } else {
+                hex::encode(Sha256::digest(value.as_bytes()))[..12].to_string()
+            }
+        };
+
+        let mut ja4_c = to_hex(&extensions);
+        if !ja4_c.is_empty() && !self.signature_algorithms.is_empty() {
+            ja4_c = format!("{}_{}", ja4_c, to_hex(&self.signature_algorithms));
+        }
+
+        format!(
+            "{}_{}_{}",
+            ja4_a,
+            truncated_hash(to_hex(&ciphers)),
+            truncated_hash(ja4_c)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::honeypot::tls::ClientHello;
+
+    /// A TLS 1.3 ClientHello with a GREASE cipher, SNI "ftp.test", groups, point formats,
+    /// signature algorithms, ALPN "ftp" and supported versions.
+    fn client_hello() -> Vec<u8> {
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0; 32]);
+        body.push(0);
+        body.extend_from_slice(&[0x00, 0x06, 0x0a, 0x0a, 0x13, 0x01, 0xc0, 0x2f]);
+        body.extend_from_slice(&[0x01, 0x00]);
+
+        let extensions: Vec<u8> = [
+            // server_name "ftp.test"
+            vec![
+                0x00, 0x00, 0x00, 0x0d, 0x00, 0x0b, 0x00, 0x00, 0x08, b'f', b't', b'p', b'.', b't',
+                b'e', b's', b't',
+            ],
+            // supported_groups x25519, secp256r1
+            vec![0x00, 0x0a, 0x00, 0x06, 0x00, 0x04, 0x00, 0x1d, 0x00, 0x17],
+            // ec_point_formats uncompressed
+            vec![0x00, 0x0b, 0x00, 0x02, 0x01, 0x00],
+            // signature_algorithms ecdsa_secp256r1_sha256, rsa_pss_rsae_sha256
+            vec![0x00, 0x0d, 0x00, 0x06, 0x00, 0x04, 0x04, 0x03, 0x08, 0x04],
+            // alpn "ftp"
+            vec![0x00, 0x10, 0x00, 0x06, 0x00, 0x04, 0x03, b'f', b't', b'p'],
+            // supported_versions TLS 1.3, TLS 1.2
+            vec![0x00, 0x2b, 0x00, 0x05, 0x04, 0x03, 0x04, 0x03, 0x03],
+        ]
+        .concat();
+        body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        body.extend_from_slice(&extensions);
+
+        let mut handshake = vec![0x01, 0x00];
+        handshake.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        handshake.extend_from_slice(&body);
+        handshake
+    }
+
+    #[test]
+    fn parse_client_hello() {
+        let hello = ClientHello::parse(&client_hello()).unwrap();
+
+        assert_eq!(hello.version, 0x0303);
+        assert_eq!(hello.cipher_suites, vec![0x0a0a, 0x1301, 0xc02f]);
+        assert_eq!(hello.extensions, vec![0, 10, 11, 13, 16, 43]);
+        assert_eq!(hello.server_name, Some("ftp.test".to_string()));
+        assert_eq!(hello.alpn, vec!["ftp"]);
+        assert_eq!(hello.supported_versions, vec![0x0304, 0x0303]);
+    }
+
+    #[test]
+    fn fingerprint_client_hello() {
+        let hello = ClientHello::parse(&client_hello()).unwrap();
+
+        assert_eq!(hello.ja3(), "771,4865-49199,0-10-11-13-16-43,29-23,0");
+        assert_eq!(hello.ja3_hash().len(), 32);
+        assert!(hello.ja4().starts_with("t13d0206fp_"));
+    }
+
+    #[test]
+    fn parse_invalid_client_hello() {
+        assert!(ClientHello::parse(&[0x02, 0x00, 0x00, 0x01, 0x03]).is_none());
+        assert!(ClientHello::parse(&client_hello()[..20]).is_none());
+    }
+}
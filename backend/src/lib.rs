@@ -0,0 +1,32 @@
+//! This application is an FTP-Honeypot with additional functionalities like:
+//! - automatically verifying uploaded files on virustotal
+//! - sending results to a given url
+//! - highly configurable login logic for attackers (see [login])
+//! - custom fake filesystem (see [filesystem])
+//! - highly configurable file management (see [configuration])
+//! - a queue that manages API calls (see [queue])
+//! - implementation (of most) of the necessary FTP verbs (see [honeypot])
+//! - logging
+//! - several listeners in one process, each with its own port, personality and filesystem (see
+//!   [configuration])
+//! - recorded sessions, which can be replayed with the `replay` binary and exported as PCAP with
+//!   the `pcap` binary
+//!
+//! # Startup
+//! To start the application the following steps have to be done.
+//! 1. Startup a mysql database. E.g. with the preconfigured docker config we provide: ```docker-compose up```
+//! 2. Configure the config file that can be found in ```<path-to-project>/backend/application.toml```
+//! 3. (optional) Configure the settings for the logging framework via ```<path-to-project>/backend/log4rs.yml```
+//!
+//! # Testing
+//! Some of the tests are disabled in production mode. To fully test the application enable
+//! the ```testing``` feature! This will activate Database mocks to test the [login::login_service]
+//! and deactivate problematic other settings
+
+pub mod configuration;
+pub mod database;
+pub mod external_api;
+pub mod filesystem;
+pub mod honeypot;
+pub mod login;
+pub mod queue;
@@ -1,24 +1,47 @@
 //! Service that contains login rules and manages logins.
 
 use std::cmp::Ordering;
+use std::net::IpAddr;
+use std::str::FromStr;
 
-use log::info;
+use log::{info, warn};
 use sea_orm::ActiveValue::Set;
 use sea_orm::{IntoActiveModel, NotSet};
 
 use crate::configuration;
+use crate::configuration::config::ListenerConfig;
 use crate::database::models::attacker::Model;
 use crate::database::models::{attacker, attacker_to_credentials, credentials};
 use crate::database::service::DatabaseImplementation;
 use crate::database::service_trait::DatabaseTrait;
-use crate::filesystem::ftp_fs::new_fs_of_attacker;
+use crate::filesystem::ftp_fs::{get_public_fs, new_fs_of_attacker, Template};
 
-/// Creates a new attacker and saves value to database table
-async fn add_new_attacker(ip: &str, db: &DatabaseImplementation) -> i64 {
+/// The usernames of anonymous FTP.
+const ANONYMOUS_USERNAMES: [&str; 2] = ["anonymous", "ftp"];
+
+/// Returns the canonical text form of an IP address
+///
+/// IPv6 addresses are written in their compressed lower case form and IPv4-mapped IPv6 addresses
+/// are converted to plain IPv4 addresses, so the same attacker always matches the same
+/// [Attacker][attacker] entry. Values that are no IP address are returned unchanged.
+pub fn normalize_ip(ip: &str) -> String {
+    match ip
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        Ok(ip) => ip.to_canonical().to_string(),
+        Err(_) => ip.to_string(),
+    }
+}
+
+/// Creates a new attacker, which came in on the `listener`, and saves value to database table
+async fn add_new_attacker(ip: &str, listener: &ListenerConfig, db: &DatabaseImplementation) -> i64 {
     let attacker = attacker::ActiveModel {
         id: NotSet,
         ip: Set(ip.to_string()),
         login_count: Set(1),
+        listener: Set(Some(listener.name.to_owned())),
         ..Default::default()
     };
     let result = db.update_attacker(attacker).await;
@@ -67,15 +90,28 @@ async fn get_credentials_and_update_count(
 }
 
 /// Updates a given attacker
+///
+/// Attackers getting their first credentials get a filesystem of the [Template] of the `listener`.
 async fn update_attacker(
     attacker: &Model,
     credentials_id_optional: Option<i64>,
+    listener: &ListenerConfig,
     db: &DatabaseImplementation,
 ) -> attacker::ActiveModel {
     let mut attacker = attacker.clone().into_active_model();
     if credentials_id_optional.is_some() {
         if attacker.credentials_id.unwrap().is_none() {
-            attacker.file_system = Set(Some(new_fs_of_attacker(attacker.id.clone().unwrap())));
+            let template = Template::from_str(&listener.filesystem).unwrap_or_else(|_| {
+                warn!(
+                    "Unknown filesystem template {}, using home.",
+                    listener.filesystem
+                );
+                Template::Home
+            });
+            attacker.file_system = Set(Some(new_fs_of_attacker(
+                attacker.id.clone().unwrap(),
+                template,
+            )));
         }
 
         attacker.credentials_id = Set(credentials_id_optional);
@@ -115,6 +151,7 @@ async fn check_credentials_of_attacker(
     db: &DatabaseImplementation,
     attacker: &Model,
     ip: &str,
+    listener: &ListenerConfig,
 ) -> bool {
     let result = db
         .get_credentials_by_id_from_attacker(attacker, credentials_id)
@@ -125,7 +162,7 @@ async fn check_credentials_of_attacker(
                 "Attacker with IP: '{}' did not try the credentials already, accepted.",
                 ip
             );
-            update_attacker(attacker, Some(credentials_id), db).await;
+            update_attacker(attacker, Some(credentials_id), listener, db).await;
             true
         }
         Some(_) => {
@@ -133,7 +170,7 @@ async fn check_credentials_of_attacker(
                 "Attacker with IP: '{}' already tried the credentials, declined.",
                 ip
             );
-            update_attacker(attacker, None, db).await;
+            update_attacker(attacker, None, listener, db).await;
             false
         }
     }
@@ -142,9 +179,12 @@ async fn check_credentials_of_attacker(
 /// Manages the [Attacker][attacker] login
 ///
 /// # Checks if the given IP address is already known
+/// The IP address is [normalized][normalize_ip] first, so IPv6 addresses match regardless of
+/// their notation.
 ///
 /// ## If not known
-/// Create a new database entry for the [Attacker][attacker] and deny access.
+/// Create a new database entry for the [Attacker][attacker], which came in on the `listener`, and
+/// deny access.
 ///
 /// ## If known
 /// Checks the [login count][credentials::Model#structfield.count] of the [Attacker][attacker] and
@@ -161,16 +201,19 @@ async fn check_credentials_of_attacker(
 /// #### Greater
 /// - updates models
 /// - If attacker already has credentials:
-///     Allows access if the current [Credentials][credentials] match the [Credentials][credentials] of the [Attacker][attacker]
-/// -  If attacker has no credentials:
-///     Allows access if the [Attacker][attacker] has not previously tried the current [Credentials][credentials]
+///   Allows access if the current [Credentials][credentials] match the [Credentials][credentials] of the [Attacker][attacker]
+/// - If attacker has no credentials:
+///   Allows access if the [Attacker][attacker] has not previously tried the current [Credentials][credentials]
 ///
 pub async fn is_login_valid(
     username: &str,
     password: &str,
     ip: &str,
+    listener: &ListenerConfig,
     db: &DatabaseImplementation,
 ) -> Option<Model> {
+    let ip = normalize_ip(ip);
+    let ip = ip.as_str();
     let attacker_optional = db.get_attacker_by_ip(ip).await;
     let _login_count: i32 = configuration::config::get_config().number_of_tries_before_success;
 
@@ -180,7 +223,7 @@ pub async fn is_login_valid(
                 "Attacker with IP: '{}' not found. Adding new Attacker and updating tables.",
                 ip
             );
-            let attacker_id = add_new_attacker(ip, db).await;
+            let attacker_id = add_new_attacker(ip, listener, db).await;
             let credentials = get_credentials_and_update_count(username, password, db).await;
             update_credentials_of_attacker(attacker_id, credentials.id.unwrap(), db).await;
 
@@ -194,7 +237,7 @@ pub async fn is_login_valid(
                     ip, attacker.login_count, _login_count
                 );
                 let credentials = get_credentials_and_update_count(username, password, db).await;
-                update_attacker(&attacker, None, db).await;
+                update_attacker(&attacker, None, listener, db).await;
                 update_credentials_of_attacker(attacker.id, credentials.id.unwrap(), db).await;
 
                 None
@@ -209,7 +252,8 @@ pub async fn is_login_valid(
                 let credentials: credentials::ActiveModel =
                     get_credentials_and_update_count(username, password, db).await;
 
-                check_credentials(db, credentials.id.clone().unwrap(), &attacker, ip).await
+                check_credentials(db, credentials.id.clone().unwrap(), &attacker, ip, listener)
+                    .await
             }
             Ordering::Greater => {
                 let credentials: credentials::ActiveModel =
@@ -222,15 +266,27 @@ pub async fn is_login_valid(
                         ({}). Accepting login if credentials are not declined and updating tables",
                         ip, attacker.login_count, _login_count
                     );
-                        check_credentials(db, credentials.id.clone().unwrap(), &attacker, ip).await
+                        check_credentials(
+                            db,
+                            credentials.id.clone().unwrap(),
+                            &attacker,
+                            ip,
+                            listener,
+                        )
+                        .await
                     }
                     Some(_) => {
                         let cur_credentials: credentials::Model = db
                             .get_credentials_by_id(attacker.credentials_id.unwrap())
                             .await
                             .unwrap();
-                        update_attacker(&attacker, Some(attacker.credentials_id.unwrap()), db)
-                            .await;
+                        update_attacker(
+                            &attacker,
+                            Some(attacker.credentials_id.unwrap()),
+                            listener,
+                            db,
+                        )
+                        .await;
                         info!(
                         "Attacker with IP: '{}' found. Login count ({}) is greater than the threshold \
                         ({}). Checking credentials and updating tables. Are credentials equal? {}",
@@ -251,13 +307,72 @@ pub async fn is_login_valid(
     }
 }
 
+/// Returns whether the credentials are an anonymous login.
+///
+/// Anonymous users log in as `anonymous` or `ftp` and give their email address as password.
+pub fn is_anonymous(username: &str, password: &str) -> bool {
+    ANONYMOUS_USERNAMES
+        .iter()
+        .any(|anonymous| username.eq_ignore_ascii_case(anonymous))
+        && password.contains('@')
+}
+
+/// Manages the anonymous [Attacker][attacker] login
+///
+/// Anonymous logins always succeed and don't count towards the login thresholds. Unknown IP
+/// addresses get a new [Attacker][attacker], the [Credentials][credentials] are stored like on
+/// every other login. The returned [Model] holds the [public filesystem][get_public_fs] instead
+/// of the filesystem of the [Attacker][attacker].
+pub async fn anonymous_login(
+    username: &str,
+    password: &str,
+    ip: &str,
+    listener: &ListenerConfig,
+    db: &DatabaseImplementation,
+) -> Model {
+    let ip = normalize_ip(ip);
+    let ip = ip.as_str();
+
+    let attacker = match db.get_attacker_by_ip(ip).await {
+        Some(attacker) => attacker,
+        None => {
+            info!(
+                "Anonymous attacker with IP: '{}' not found. Adding new Attacker.",
+                ip
+            );
+            add_new_attacker(ip, listener, db).await;
+            db.get_attacker_by_ip(ip)
+                .await
+                .expect("Could not find new attacker!")
+        }
+    };
+
+    let credentials = get_credentials_and_update_count(username, password, db).await;
+    let credentials_id = credentials.id.unwrap();
+    if db
+        .get_credentials_by_id_from_attacker(&attacker, credentials_id)
+        .await
+        .is_none()
+    {
+        update_credentials_of_attacker(attacker.id, credentials_id, db).await;
+    }
+
+    info!("Attacker with IP: '{}' logged in anonymously.", ip);
+
+    Model {
+        file_system: Some(get_public_fs()),
+        ..attacker
+    }
+}
+
 async fn check_credentials(
     db: &DatabaseImplementation,
     credentials_id: i64,
     attacker: &Model,
     ip: &str,
+    listener: &ListenerConfig,
 ) -> Option<Model> {
-    if check_credentials_of_attacker(credentials_id, db, attacker, ip).await {
+    if check_credentials_of_attacker(credentials_id, db, attacker, ip, listener).await {
         db.get_attacker_by_ip(ip).await
     } else {
         None
@@ -269,10 +384,91 @@ async fn check_credentials(
 mod test {
     use sea_orm::{DatabaseBackend, DatabaseConnection, MockDatabase, MockExecResult};
 
+    use crate::configuration::config::{get_config, ListenerConfig};
     use crate::database::models::{attacker, attacker_to_credentials, credentials};
     use crate::database::service::DatabaseImplementation;
     use crate::filesystem::ftp_fs::new_fs;
-    use crate::login::login_service::{get_credentials_and_update_count, is_login_valid};
+    use crate::login::login_service::{
+        anonymous_login, get_credentials_and_update_count, is_anonymous, is_login_valid,
+        normalize_ip,
+    };
+
+    fn listener() -> ListenerConfig {
+        get_config().listeners.remove(0)
+    }
+
+    #[test]
+    fn test_normalize_ip() {
+        assert_eq!(normalize_ip("127.0.0.1"), "127.0.0.1");
+        assert_eq!(normalize_ip("2001:DB8:0:0::1"), "2001:db8::1");
+        assert_eq!(normalize_ip("[2001:db8::1]"), "2001:db8::1");
+        assert_eq!(normalize_ip("::ffff:192.0.2.1"), "192.0.2.1");
+        assert_eq!(normalize_ip("ip"), "ip");
+    }
+
+    #[test]
+    fn test_is_anonymous() {
+        assert!(is_anonymous("anonymous", "mozilla@example.com"));
+        assert!(is_anonymous("FTP", "IEUser@"));
+        assert!(!is_anonymous("anonymous", "guest"));
+        assert!(!is_anonymous("admin", "admin@example.com"));
+    }
+
+    #[actix_rt::test]
+    async fn test_anonymous_login_attacker_known() {
+        let username = String::from("anonymous");
+        let password = String::from("scanner@example.com");
+        let ip = String::from("ip");
+        let db = MockDatabase::new(DatabaseBackend::MySql)
+            .append_query_results([[attacker::Model {
+                id: 1,
+                ip: ip.clone(),
+                login_count: 2,
+                credentials_id: None,
+                updated: Default::default(),
+                file_system: Some(new_fs()),
+                listener: None,
+            }]])
+            .append_query_results([
+                [credentials::Model {
+                    id: 11,
+                    username: username.clone(),
+                    password: password.clone(),
+                    count: 5,
+                }],
+                [credentials::Model {
+                    id: 11,
+                    username: username.clone(),
+                    password: password.clone(),
+                    count: 6,
+                }],
+            ])
+            .append_exec_results([MockExecResult {
+                last_insert_id: 11,
+                rows_affected: 1,
+            }])
+            .append_query_results([[credentials::Model {
+                id: 11,
+                username: username.clone(),
+                password: password.clone(),
+                count: 6,
+            }]])
+            .into_connection();
+
+        let res = anonymous_login(
+            &username,
+            &password,
+            &ip,
+            &listener(),
+            &DatabaseImplementation { db },
+        )
+        .await;
+        let file_system = res.file_system.unwrap();
+        assert_eq!(1, res.id);
+        assert_eq!(2, res.login_count);
+        assert!(file_system.public);
+        assert!(file_system.root.dirs.contains_key("pub"));
+    }
 
     #[actix_rt::test]
     async fn test_is_login_valid_attacker_known_1_6() {
@@ -287,6 +483,7 @@ mod test {
                 credentials_id: None,
                 updated: Default::default(),
                 file_system: None,
+                listener: None,
             }]])
             .append_query_results([
                 [credentials::Model {
@@ -313,6 +510,7 @@ mod test {
                 credentials_id: None,
                 updated: Default::default(),
                 file_system: None,
+                listener: None,
             }]])
             .append_exec_results([MockExecResult {
                 last_insert_id: 1,
@@ -328,7 +526,14 @@ mod test {
             }])
             .into_connection();
 
-        let res = is_login_valid(&username, &password, &ip, &DatabaseImplementation { db }).await;
+        let res = is_login_valid(
+            &username,
+            &password,
+            &ip,
+            &listener(),
+            &DatabaseImplementation { db },
+        )
+        .await;
         assert!(res.is_none())
     }
 
@@ -345,6 +550,7 @@ mod test {
                 credentials_id: None,
                 updated: Default::default(),
                 file_system: Some(new_fs()),
+                listener: None,
             }]])
             .append_query_results([
                 [credentials::Model {
@@ -372,6 +578,7 @@ mod test {
                 credentials_id: Some(11),
                 updated: Default::default(),
                 file_system: Some(new_fs()),
+                listener: None,
             }]])
             .append_exec_results([MockExecResult {
                 last_insert_id: 1,
@@ -384,10 +591,18 @@ mod test {
                 credentials_id: Some(11),
                 updated: Default::default(),
                 file_system: Some(new_fs()),
+                listener: None,
             }]])
             .into_connection();
 
-        let res = is_login_valid(&username, &password, &ip, &DatabaseImplementation { db }).await;
+        let res = is_login_valid(
+            &username,
+            &password,
+            &ip,
+            &listener(),
+            &DatabaseImplementation { db },
+        )
+        .await;
         assert!(res.is_some());
         assert_eq!(1, res.clone().unwrap().id);
         assert_eq!(11, res.unwrap().credentials_id.unwrap());
@@ -406,6 +621,7 @@ mod test {
                 credentials_id: None,
                 updated: Default::default(),
                 file_system: Some(new_fs()),
+                listener: None,
             }]])
             .append_query_results([
                 [credentials::Model {
@@ -438,6 +654,7 @@ mod test {
                 credentials_id: Some(11),
                 updated: Default::default(),
                 file_system: Some(new_fs()),
+                listener: None,
             }]])
             .append_exec_results([MockExecResult {
                 last_insert_id: 1,
@@ -445,7 +662,14 @@ mod test {
             }])
             .into_connection();
 
-        let res = is_login_valid(&username, &password, &ip, &DatabaseImplementation { db }).await;
+        let res = is_login_valid(
+            &username,
+            &password,
+            &ip,
+            &listener(),
+            &DatabaseImplementation { db },
+        )
+        .await;
         assert!(res.is_none());
     }
 
@@ -462,6 +686,7 @@ mod test {
                 credentials_id: None,
                 updated: Default::default(),
                 file_system: Some(new_fs()),
+                listener: None,
             }]])
             .append_query_results([
                 [credentials::Model {
@@ -489,6 +714,7 @@ mod test {
                 credentials_id: Some(11),
                 updated: Default::default(),
                 file_system: Some(new_fs()),
+                listener: None,
             }]])
             .append_exec_results([MockExecResult {
                 last_insert_id: 1,
@@ -501,10 +727,18 @@ mod test {
                 credentials_id: Some(11),
                 updated: Default::default(),
                 file_system: Some(new_fs()),
+                listener: None,
             }]])
             .into_connection();
 
-        let res = is_login_valid(&username, &password, &ip, &DatabaseImplementation { db }).await;
+        let res = is_login_valid(
+            &username,
+            &password,
+            &ip,
+            &listener(),
+            &DatabaseImplementation { db },
+        )
+        .await;
         assert!(res.is_some());
         assert_eq!(1, res.clone().unwrap().id);
         assert_eq!(11, res.unwrap().credentials_id.unwrap());
@@ -523,6 +757,7 @@ mod test {
                 credentials_id: None,
                 updated: Default::default(),
                 file_system: Some(new_fs()),
+                listener: None,
             }]])
             .append_query_results([
                 [credentials::Model {
@@ -555,6 +790,7 @@ mod test {
                 credentials_id: Some(11),
                 updated: Default::default(),
                 file_system: Some(new_fs()),
+                listener: None,
             }]])
             .append_exec_results([MockExecResult {
                 last_insert_id: 1,
@@ -562,7 +798,14 @@ mod test {
             }])
             .into_connection();
 
-        let res = is_login_valid(&username, &password, &ip, &DatabaseImplementation { db }).await;
+        let res = is_login_valid(
+            &username,
+            &password,
+            &ip,
+            &listener(),
+            &DatabaseImplementation { db },
+        )
+        .await;
         assert!(res.is_none());
     }
 
@@ -573,7 +816,14 @@ mod test {
         let ip = String::from("ip");
         let db = db_helper(username.clone(), password.clone(), ip.clone());
 
-        let res = is_login_valid(&username, &password, &ip, &DatabaseImplementation { db }).await;
+        let res = is_login_valid(
+            &username,
+            &password,
+            &ip,
+            &listener(),
+            &DatabaseImplementation { db },
+        )
+        .await;
         assert!(res.is_some());
     }
 
@@ -584,7 +834,14 @@ mod test {
         let ip = String::from("ip");
         let db = db_helper(username.clone(), password.clone(), ip.clone());
 
-        let res = is_login_valid("invalid", &password, &ip, &DatabaseImplementation { db }).await;
+        let res = is_login_valid(
+            "invalid",
+            &password,
+            &ip,
+            &listener(),
+            &DatabaseImplementation { db },
+        )
+        .await;
         assert!(res.is_none());
     }
 
@@ -597,6 +854,7 @@ mod test {
                 credentials_id: Some(11),
                 updated: Default::default(),
                 file_system: Some(new_fs()),
+                listener: None,
             }]])
             .append_query_results([
                 [credentials::Model {
@@ -629,6 +887,7 @@ mod test {
                 credentials_id: Some(11),
                 updated: Default::default(),
                 file_system: Some(new_fs()),
+                listener: None,
             }]])
             .append_exec_results([MockExecResult {
                 last_insert_id: 1,
@@ -652,6 +911,7 @@ mod test {
                     credentials_id: Some(11),
                     updated: Default::default(),
                     file_system: None,
+                    listener: None,
                 }],
             ])
             .append_exec_results([MockExecResult {
@@ -686,7 +946,14 @@ mod test {
             }])
             .into_connection();
 
-        let res = is_login_valid(&username, &password, &ip, &DatabaseImplementation { db }).await;
+        let res = is_login_valid(
+            &username,
+            &password,
+            &ip,
+            &listener(),
+            &DatabaseImplementation { db },
+        )
+        .await;
         assert!(res.is_none());
     }
 
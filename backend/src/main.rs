@@ -1,44 +1,27 @@
-//! This application is an FTP-Honeypot with additional functionalities like:
-//! - automatically verifying uploaded files on virustotal
-//! - sending results to a given url
-//! - highly configurable login logic for attackers (see [login])
-//! - custom fake filesystem (see [filesystem])
-//! - highly configurable file management (see [configuration])
-//! - a queue that manages API calls (see [queue])
-//! - implementation (of most) of the necessary FTP verbs (see [honeypot])
-//! - logging
-//!
-//! # Startup
-//! To start the application the following steps have to be done.
-//! 1. Startup a mysql database. E.g. with the preconfigured docker config we provide: ```docker-compose up```
-//! 2. Configure the config file that can be found in ```<path-to-project>/backend/application.toml```
-//! 3. (optional) Configure the settings for the logging framework via ```<path-to-project>/backend/log4rs.yml```
-//!
-//! # Testing
-//! Some of the tests are disabled in production mode. To fully test the application enable
-//! the ```testing``` feature! This will activate Database mocks to test the [login::login_service]
-//! and deactivate problematic other settings
+//! The FTP honeypot server, see the [rust_ftp_honeypot] crate for an overview.
 
+use std::net::{IpAddr, SocketAddr};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use log::{error, info};
+use socket2::{Domain, Socket, Type};
 use tokio::net::TcpListener;
+use tokio::time::timeout;
+use tokio_rustls::TlsAcceptor;
 
-use database::*;
-
-use crate::configuration::config::get_config;
-use crate::database::service::DatabaseImplementation;
-use crate::database::service_trait::DatabaseTrait;
-use crate::honeypot::handler;
-use crate::queue::queue_service::start_queue;
-
-mod configuration;
-mod database;
-mod external_api;
-mod filesystem;
-mod honeypot;
-mod login;
-mod queue;
+use rust_ftp_honeypot::configuration::config::{get_config, ListenerConfig};
+use rust_ftp_honeypot::database::connection;
+use rust_ftp_honeypot::database::service::DatabaseImplementation;
+use rust_ftp_honeypot::database::service_trait::DatabaseTrait;
+use rust_ftp_honeypot::filesystem::ftp_fs::load_public_fs;
+use rust_ftp_honeypot::honeypot::backdoor;
+use rust_ftp_honeypot::honeypot::handler;
+use rust_ftp_honeypot::honeypot::proxy;
+use rust_ftp_honeypot::honeypot::proxy::Addresses;
+use rust_ftp_honeypot::honeypot::stream::FtpStream;
+use rust_ftp_honeypot::honeypot::tls::create_tls_acceptor;
+use rust_ftp_honeypot::queue::queue_service::start_queue;
 
 /// Initializes the logging framework and [starts][start] the app
 #[tokio::main]
@@ -63,26 +46,235 @@ async fn start() {
         }
     };
     create_table(&db).await;
+    load_public_fs(&db).await;
+
+    let config = get_config();
+    let tls_acceptor = if config.tls_enabled {
+        create_tls_acceptor(&config.tls_certificate_path, &config.tls_private_key_path)
+    } else {
+        None
+    };
 
-    let ftp_port = get_config().ftp_port;
-    let listener = TcpListener::bind(format!("127.0.0.1:{}", ftp_port))
-        .await
-        .unwrap();
-    let current_users = Arc::new(Mutex::new(0));
+    let implicit_tls = config
+        .listeners
+        .iter()
+        .any(|listener| listener.implicit_tls);
+    let implicit_tls_acceptor = if !implicit_tls {
+        None
+    } else if config.implicit_tls_certificate_path.is_empty() {
+        tls_acceptor.clone().or_else(|| {
+            create_tls_acceptor(&config.tls_certificate_path, &config.tls_private_key_path)
+        })
+    } else {
+        create_tls_acceptor(
+            &config.implicit_tls_certificate_path,
+            &config.implicit_tls_private_key_path,
+        )
+    };
 
     start_queue(db.clone());
+
+    if config.backdoor_enabled {
+        start_backdoor(&config.listeners, &db);
+    }
+
+    let mut accept_tasks = vec![];
+    for listener in config.listeners {
+        let acceptor = if listener.implicit_tls {
+            match &implicit_tls_acceptor {
+                Some(acceptor) => Some(acceptor.clone()),
+                None => {
+                    error!(
+                        "No certificate for the implicit FTPS listener {}, it is disabled!",
+                        listener.name
+                    );
+                    continue;
+                }
+            }
+        } else {
+            tls_acceptor.clone()
+        };
+        // The limit of concurrent users applies to each listener on its own.
+        let current_users = Arc::new(Mutex::new(0));
+
+        for address in &listener.bind_addresses {
+            let ip: IpAddr = match address.parse() {
+                Ok(ip) => ip,
+                Err(e) => {
+                    error!("Invalid bind address {}: {}", address, e);
+                    continue;
+                }
+            };
+
+            let addr = SocketAddr::new(ip, listener.port);
+            match bind_listener(addr) {
+                Ok(tcp_listener) => {
+                    info!("Listening for {} on: {}", listener.name, addr);
+                    accept_tasks.push(tokio::spawn(accept_connections(
+                        tcp_listener,
+                        listener.clone(),
+                        db.clone(),
+                        Arc::clone(&current_users),
+                        acceptor.clone(),
+                    )));
+                }
+                Err(e) => error!("Could not bind {} to {}: {}", listener.name, addr, e),
+            }
+        }
+    }
+
+    if accept_tasks.is_empty() {
+        panic!("Could not bind to any of the configured addresses!");
+    }
+
+    for task in accept_tasks {
+        task.await.expect("Listener task failed!");
+    }
+}
+
+/// Starts the [backdoor shell listener][backdoor::listen] on the backdoor port of every address
+/// the FTP listeners are bound to.
+///
+/// * `listeners` - The [ListenerConfig]s of the FTP listeners.
+/// * `db`        - The [DatabaseImplementation].
+#[cfg(not(feature = "testing"))]
+fn start_backdoor(listeners: &[ListenerConfig], db: &DatabaseImplementation) {
+    let mut addresses: Vec<&String> = listeners
+        .iter()
+        .flat_map(|listener| &listener.bind_addresses)
+        .collect();
+    addresses.sort();
+    addresses.dedup();
+
+    for address in addresses {
+        let ip: IpAddr = match address.parse() {
+            Ok(ip) => ip,
+            Err(_) => continue,
+        };
+
+        let addr = SocketAddr::new(ip, get_config().backdoor_port);
+        match bind_listener(addr) {
+            Ok(tcp_listener) => {
+                info!("Listening for backdoor shells on: {}", addr);
+                tokio::spawn(backdoor::listen(tcp_listener, db.clone()));
+            }
+            Err(e) => error!("Could not bind the backdoor shell to {}: {}", addr, e),
+        }
+    }
+}
+
+/// Binds a new [TcpListener] to the given address.
+///
+/// IPv6 listeners are bound as IPv6 only, so an IPv4 and an IPv6 wildcard address can be used
+/// with the same port.
+///
+/// * `addr` - The address to listen on.
+#[cfg(not(feature = "testing"))]
+fn bind_listener(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+
+    TcpListener::from_std(socket.into())
+}
+
+/// Accepts new connections on the `listener` and [handles][handler::handle_connection] each of
+/// them in a new task.
+///
+/// The address of the attacker is taken from the peer address, or from the PROXY protocol header
+/// of connections forwarded by a trusted load balancer, which is read before anything else.
+/// Connections of trusted peers without a valid header are dropped.
+/// Connections of implicit FTPS listeners are wrapped in TLS before they are handled, connections
+/// that don't finish the handshake within the command timeout are dropped.
+///
+/// * `tcp_listener`    - The [TcpListener] to accept connections on.
+/// * `listener`        - The [ListenerConfig] of the listener the connections come in on.
+/// * `db`              - The [DatabaseImplementation].
+/// * `current_users`   - The amount of currently active users of the listener, wrapped around a
+///   [Arc] [Mutex].
+/// * `tls_acceptor`    - The [TlsAcceptor] used for FTPS, [None] if FTPS is disabled.
+#[cfg(not(feature = "testing"))]
+async fn accept_connections(
+    tcp_listener: TcpListener,
+    listener: ListenerConfig,
+    db: DatabaseImplementation,
+    current_users: Arc<Mutex<i32>>,
+    tls_acceptor: Option<TlsAcceptor>,
+) {
     loop {
-        let (mut tcp_stream, socket) = listener.accept().await.unwrap();
-        let socket_string = socket.to_string();
-        let vector_string: Vec<&str> = socket_string.split(':').collect();
-        let ip = vector_string[0].to_string();
+        let (tcp_stream, socket) = match tcp_listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                error!("Could not accept connection: {}", e);
+                continue;
+            }
+        };
         let db_impl_clone = db.clone();
+        let tls_acceptor = tls_acceptor.clone();
+        let listener = listener.clone();
 
         tokio::spawn({
             let current_users = Arc::clone(&current_users);
             async move {
-                handler::handle_connection(&mut tcp_stream, ip, &db_impl_clone, current_users)
-                    .await;
+                let mut tcp_stream = tcp_stream;
+                let server = match tcp_stream.local_addr() {
+                    Ok(server) => server,
+                    Err(e) => {
+                        error!("Connection error: {}", e);
+                        return;
+                    }
+                };
+                let socket = Addresses {
+                    client: socket,
+                    server,
+                };
+                let addresses = match proxy::resolve(&mut tcp_stream, socket, false).await {
+                    Ok(addresses) => addresses,
+                    Err(e) => {
+                        error!(
+                            "Invalid PROXY protocol header from {}: {}",
+                            socket.client, e
+                        );
+                        return;
+                    }
+                };
+                let ip = addresses.client.ip().to_canonical().to_string();
+                let mut stream = FtpStream::Plain(tcp_stream);
+                let mut client_hello = None;
+
+                if listener.implicit_tls {
+                    let acceptor = tls_acceptor.as_ref().expect("TLS acceptor not set!");
+
+                    let handshake_timeout = Duration::from_secs(get_config().timeout_command);
+                    let handshake = timeout(handshake_timeout, stream.upgrade(acceptor));
+                    client_hello = match handshake.await {
+                        Ok(Ok(client_hello)) => client_hello,
+                        Ok(Err(e)) => {
+                            error!("TLS handshake with {} failed: {}", ip, e);
+                            return;
+                        }
+                        Err(_) => {
+                            error!("TLS handshake with {} timed out", ip);
+                            return;
+                        }
+                    };
+                }
+
+                handler::handle_connection(
+                    &mut stream,
+                    addresses,
+                    &db_impl_clone,
+                    current_users,
+                    tls_acceptor,
+                    listener,
+                    client_hello,
+                )
+                .await;
             }
         });
     }
@@ -110,6 +302,14 @@ async fn create_table(db: &DatabaseImplementation) {
             .expect("Could not set default and on update for Attacker table");
     }
 
+    let attacker_listener_res = db.add_attacker_listener_column().await;
+    if attacker_listener_res.is_err() {
+        error!(
+            "Could not add listener column to Attacker table! Error: {}",
+            attacker_listener_res.err().unwrap()
+        )
+    }
+
     let uploaded_files_res = db.create_uploaded_files_table().await;
     if uploaded_files_res.is_err() {
         error!(
@@ -125,4 +325,68 @@ async fn create_table(db: &DatabaseImplementation) {
             attacker_to_credentials_res.err().unwrap()
         )
     }
+
+    let tls_fingerprints_res = db.create_tls_fingerprints_table().await;
+    if tls_fingerprints_res.is_err() {
+        error!(
+            "Could not create TlsFingerprints table! Error: {}",
+            tls_fingerprints_res.err().unwrap()
+        )
+    }
+
+    let events_res = db.create_events_table().await;
+    if events_res.is_err() {
+        error!(
+            "Could not create Events table! Error: {}",
+            events_res.err().unwrap()
+        )
+    }
+
+    let sessions_res = db.create_sessions_table().await;
+    if sessions_res.is_err() {
+        error!(
+            "Could not create Sessions table! Error: {}",
+            sessions_res.err().unwrap()
+        )
+    }
+
+    let sessions_update_res = db.update_sessions_table().await;
+    if sessions_update_res.is_err() {
+        error!(
+            "Could not update Sessions table! Error: {}",
+            sessions_update_res.err().unwrap()
+        )
+    }
+
+    let transcripts_res = db.create_transcripts_table().await;
+    if transcripts_res.is_err() {
+        error!(
+            "Could not create Transcripts table! Error: {}",
+            transcripts_res.err().unwrap()
+        )
+    }
+
+    let captures_res = db.create_captures_table().await;
+    if captures_res.is_err() {
+        error!(
+            "Could not create Captures table! Error: {}",
+            captures_res.err().unwrap()
+        )
+    }
+
+    let public_file_systems_res = db.create_public_file_systems_table().await;
+    if public_file_systems_res.is_err() {
+        error!(
+            "Could not create PublicFileSystems table! Error: {}",
+            public_file_systems_res.err().unwrap()
+        )
+    }
+
+    let captures_update_res = db.update_captures_table().await;
+    if captures_update_res.is_err() {
+        error!(
+            "Could not update Captures table! Error: {}",
+            captures_update_res.err().unwrap()
+        )
+    }
 }
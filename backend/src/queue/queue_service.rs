@@ -79,8 +79,8 @@ async fn clean_up_attackers_and_files(db: &DatabaseImplementation) {
                 files.len()
             );
             for file in files.iter() {
-                if file.location.is_some() {
-                    delete_file("", file.location.as_ref().unwrap().as_str())
+                if let Some(location) = &file.location {
+                    delete_file("", location.as_str())
                 }
             }
         }